@@ -0,0 +1,379 @@
+//! Minimal archive readers for `--search-archives`.
+//!
+//! Supports `.zip`/`.jar` (via the central directory, with stored
+//! and deflated members) and `.tar`/`.tar.gz`/`.tgz` (via the
+//! 512-byte header walk). Each member is surfaced as a synthetic
+//! "file" named `archive!inner/path` and fed through the ordinary
+//! per-reader search path.
+
+use std::io;
+use std::path::Path;
+
+/// One regular-file member of an archive.
+pub(crate) struct ArchiveEntry {
+    /// The member's path inside the archive.
+    pub(crate) name: String,
+
+    /// The member's full (decompressed) contents.
+    pub(crate) contents: Vec<u8>,
+}
+
+/// The archive formats the searcher can descend into.
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// The archive format the path's name implies, if any.
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let extension = path.extension()?.to_str()?;
+
+    match extension {
+        "zip" | "jar" => Some(ArchiveKind::Zip),
+        "tar" => Some(ArchiveKind::Tar),
+        "tgz" => Some(ArchiveKind::TarGz),
+        "gz" => {
+            // Only `.tar.gz`; a plain `.gz` belongs to `-z`.
+            let stem = path.file_stem()?.to_str()?;
+
+            if stem.ends_with(".tar") {
+                Some(ArchiveKind::TarGz)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the path names a supported archive format.
+pub(crate) fn is_archive(path: &Path) -> bool {
+    archive_kind(path).is_some()
+}
+
+/// Every regular-file member of the archive, fully decompressed.
+pub(crate) fn entries(path: &Path, bytes: &[u8]) -> io::Result<Vec<ArchiveEntry>> {
+    match archive_kind(path) {
+        Some(ArchiveKind::Zip) => read_zip(bytes),
+        Some(ArchiveKind::Tar) => Ok(read_tar(bytes)),
+        Some(ArchiveKind::TarGz) => {
+            let decoder = crate::decompress::decoder_for(Path::new("inner.gz"))
+                .expect("The gzip decoder is always registered.");
+
+            Ok(read_tar(&decoder.decode(bytes)?))
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Walks a tar stream's 512-byte header blocks, collecting every
+/// regular file. Headers are read leniently (checksums are not
+/// verified); the walk stops at the terminating zero block or at
+/// the first truncated entry.
+fn read_tar(bytes: &[u8]) -> Vec<ArchiveEntry> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while offset + 512 <= bytes.len() {
+        let header = &bytes[offset..offset + 512];
+        offset += 512;
+
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let name = nul_terminated_str(&header[..100]);
+
+        // The ustar prefix field extends names past 100 bytes.
+        let prefix = nul_terminated_str(&header[345..500]);
+
+        let size = match octal_field(&header[124..136]) {
+            Some(size) => size,
+            None => break,
+        };
+
+        let type_flag = header[156];
+        let is_regular_file = type_flag == b'0' || type_flag == 0;
+
+        if is_regular_file && offset + size <= bytes.len() && !name.is_empty() {
+            let full_name = if prefix.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+
+            entries.push(ArchiveEntry {
+                name: full_name,
+                contents: bytes[offset..offset + size].to_vec(),
+            });
+        }
+
+        // Contents are padded out to the 512-byte block boundary.
+        offset += (size + 511) / 512 * 512;
+    }
+
+    entries
+}
+
+/// Reads a zip archive via its central directory, which (unlike
+/// streaming the local headers) is immune to data-descriptor
+/// entries since every size is recorded up front. Only the
+/// `stored` and `deflate` compression methods are supported;
+/// members using anything else are skipped.
+fn read_zip(bytes: &[u8]) -> io::Result<Vec<ArchiveEntry>> {
+    // Every offset below comes from untrusted archive bytes, so
+    // all reads are bounds-checked; a slice that runs off the end
+    // marks the archive malformed rather than panicking.
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "Malformed zip archive.");
+
+    let eocd = find_end_of_central_directory(bytes)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No zip central directory."))?;
+
+    let entry_count = read_u16(bytes, eocd + 10).ok_or_else(malformed)? as usize;
+    let mut offset = read_u32(bytes, eocd + 16).ok_or_else(malformed)? as usize;
+
+    let mut entries = Vec::new();
+
+    for _ in 0..entry_count {
+        let signature = bytes.get(offset..offset + 4).ok_or_else(malformed)?;
+
+        if signature != [0x50, 0x4b, 0x01, 0x02] {
+            break;
+        }
+
+        let method = read_u16(bytes, offset + 10).ok_or_else(malformed)?;
+        let compressed_size = read_u32(bytes, offset + 20).ok_or_else(malformed)? as usize;
+        let name_len = read_u16(bytes, offset + 28).ok_or_else(malformed)? as usize;
+        let extra_len = read_u16(bytes, offset + 30).ok_or_else(malformed)? as usize;
+        let comment_len = read_u16(bytes, offset + 32).ok_or_else(malformed)? as usize;
+        let local_offset = read_u32(bytes, offset + 42).ok_or_else(malformed)? as usize;
+
+        let name_bytes = bytes
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(malformed)?;
+        let name = String::from_utf8_lossy(name_bytes).to_string();
+
+        offset += 46 + name_len + extra_len + comment_len;
+
+        // Directories are listed with a trailing slash.
+        if name.ends_with('/') {
+            continue;
+        }
+
+        // The local header repeats the name/extra fields with
+        // possibly different lengths, so it must be re-parsed to
+        // find where the member's data really starts.
+        let local_name_len = read_u16(bytes, local_offset + 26).ok_or_else(malformed)? as usize;
+        let local_extra_len = read_u16(bytes, local_offset + 28).ok_or_else(malformed)? as usize;
+        let data_start = local_offset + 30 + local_name_len + local_extra_len;
+
+        if data_start + compressed_size > bytes.len() {
+            continue;
+        }
+
+        let data = &bytes[data_start..data_start + compressed_size];
+
+        let contents = match method {
+            // Stored: the bytes are the contents.
+            0 => data.to_vec(),
+            // Deflate.
+            8 => {
+                use std::io::Read as _;
+
+                let mut decompressed = Vec::new();
+
+                if flate2::read::DeflateDecoder::new(data)
+                    .read_to_end(&mut decompressed)
+                    .is_err()
+                {
+                    continue;
+                }
+
+                decompressed
+            }
+            _ => continue,
+        };
+
+        entries.push(ArchiveEntry { name, contents });
+    }
+
+    Ok(entries)
+}
+
+/// The offset of the end-of-central-directory record, found by
+/// scanning backwards over the (up to 64KiB) trailing comment.
+fn find_end_of_central_directory(bytes: &[u8]) -> Option<usize> {
+    const EOCD_LEN: usize = 22;
+    const SIGNATURE: &[u8] = &[0x50, 0x4b, 0x05, 0x06];
+
+    if bytes.len() < EOCD_LEN {
+        return None;
+    }
+
+    let scan_start = bytes.len().saturating_sub(EOCD_LEN + u16::MAX as usize);
+
+    (scan_start..=bytes.len() - EOCD_LEN)
+        .rev()
+        .find(|&i| bytes[i..].starts_with(SIGNATURE))
+}
+
+/// The string content of a NUL-padded fixed-width field.
+fn nul_terminated_str(field: &[u8]) -> &str {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+
+    std::str::from_utf8(&field[..end]).unwrap_or("")
+}
+
+/// Parses a tar octal number field (NUL- or space-terminated).
+fn octal_field(field: &[u8]) -> Option<usize> {
+    let text = nul_terminated_str(field).trim();
+
+    if text.is_empty() {
+        return Some(0);
+    }
+
+    usize::from_str_radix(text, 8).ok()
+}
+
+/// Reads a little-endian u16; `None` if it runs off the end.
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let field = bytes.get(offset..offset + 2)?;
+
+    Some(u16::from_le_bytes([field[0], field[1]]))
+}
+
+/// Reads a little-endian u32; `None` if it runs off the end.
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let field = bytes.get(offset..offset + 4)?;
+
+    Some(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A one-file tar stream built by hand: one header block,
+    /// one (padded) content block, and the two terminating
+    /// zero blocks.
+    fn tar_with_one_file(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+
+        header[..name.len()].copy_from_slice(name.as_bytes());
+
+        let size = format!("{:011o}\0", contents.len());
+        header[124..124 + size.len()].copy_from_slice(size.as_bytes());
+
+        header[156] = b'0';
+
+        let mut tar = header;
+        tar.extend_from_slice(contents);
+        tar.resize(512 + 512, 0);
+        tar.extend_from_slice(&[0u8; 1024]);
+
+        tar
+    }
+
+    /// A one-file zip archive built by hand, with the member
+    /// stored uncompressed.
+    fn zip_with_one_stored_file(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut zip = Vec::new();
+
+        // Local file header.
+        zip.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04]);
+        zip.extend_from_slice(&[0; 10]); // version, flags, method, time, date
+        zip.extend_from_slice(&[0; 4]); // crc
+        zip.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes()); // extra len
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(contents);
+
+        let central_offset = zip.len();
+
+        // Central directory entry.
+        zip.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02]);
+        zip.extend_from_slice(&[0; 6]); // versions, flags
+        zip.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+        zip.extend_from_slice(&[0; 8]); // time, date, crc
+        zip.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&[0; 12]); // extra/comment lens, disk, attrs
+        zip.extend_from_slice(&0u32.to_le_bytes()); // local header offset
+        zip.extend_from_slice(name.as_bytes());
+
+        let central_size = zip.len() - central_offset;
+
+        // End of central directory.
+        zip.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06]);
+        zip.extend_from_slice(&[0; 4]); // disk numbers
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&1u16.to_le_bytes());
+        zip.extend_from_slice(&(central_size as u32).to_le_bytes());
+        zip.extend_from_slice(&(central_offset as u32).to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes()); // comment len
+
+        zip
+    }
+
+    #[test]
+    fn archive_paths_are_recognized() {
+        assert!(is_archive(Path::new("lib.jar")));
+        assert!(is_archive(Path::new("backup.tar.gz")));
+        assert!(is_archive(Path::new("backup.tgz")));
+        assert!(!is_archive(Path::new("notes.txt.gz")));
+        assert!(!is_archive(Path::new("notes.txt")));
+    }
+
+    #[test]
+    fn tar_members_are_extracted() {
+        let tar = tar_with_one_file("docs/readme.txt", b"hello tar\n");
+
+        let entries = entries(Path::new("fixture.tar"), &tar).unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!("docs/readme.txt", entries[0].name);
+        assert_eq!(b"hello tar\n".to_vec(), entries[0].contents);
+    }
+
+    #[test]
+    fn zip_members_are_extracted() {
+        let zip = zip_with_one_stored_file("src/lib.rs", b"hello zip\n");
+
+        let entries = entries(Path::new("fixture.zip"), &zip).unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!("src/lib.rs", entries[0].name);
+        assert_eq!(b"hello zip\n".to_vec(), entries[0].contents);
+    }
+
+    #[test]
+    fn zip_with_out_of_range_central_directory_is_an_error() {
+        let mut zip = zip_with_one_stored_file("src/lib.rs", b"hello zip\n");
+
+        // Point the central directory offset past the end of the
+        // archive, as a truncated download would.
+        let offset_field = zip.len() - 6;
+        zip[offset_field..offset_field + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(entries(Path::new("fixture.zip"), &zip).is_err());
+    }
+
+    #[test]
+    fn zip_with_oversized_name_length_is_an_error() {
+        let mut zip = zip_with_one_stored_file("src/lib.rs", b"hello zip\n");
+
+        // Inflate the central directory entry's name length so the
+        // name slice would run off the end of the archive.
+        let central = zip
+            .windows(4)
+            .rposition(|w| w == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        zip[central + 28..central + 30].copy_from_slice(&u16::MAX.to_le_bytes());
+
+        assert!(entries(Path::new("fixture.zip"), &zip).is_err());
+    }
+}
@@ -1,19 +1,1143 @@
 use crate::target::Target;
-use peeking_take_while::PeekableExt;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 
+/// Whether searches are case-insensitive when neither `-i` nor `-s`
+/// is given. Kept as a single knob so scripts that rely on the
+/// default get predictable behavior.
+const CASE_INSENSITIVE_BY_DEFAULT: bool = false;
+
+/// When colorized output should be produced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ColorMode {
+    /// Colorize only when stdout is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl Default for ColorMode {
+    fn default() -> Self {
+        ColorMode::Auto
+    }
+}
+
 #[derive(Debug, Default)]
 pub(crate) struct UserInput {
     pub(crate) search_pattern: String,
 
+    /// Patterns read from files via `-f`, combined as alternatives.
+    /// When non-empty, these are used instead of `search_pattern`.
+    pub(crate) patterns: Vec<String>,
+
     pub(crate) whole_word: bool,
+    pub(crate) whole_line: bool,
+    pub(crate) fixed_strings: bool,
+    pub(crate) multiline: bool,
+    pub(crate) engine: crate::matcher::Engine,
     pub(crate) case_insensitive: bool,
+
+    /// Set by `-s`; wins over `-i` regardless of flag order.
+    pub(crate) case_sensitive: bool,
+
+    /// Patterns from `--all-of`; a line must match every one.
+    pub(crate) all_of: Vec<String>,
+
+    /// Patterns from `--none-of`; a line must match none of them.
+    pub(crate) none_of: Vec<String>,
+
+    /// Skip all ignore-file processing during traversal.
+    pub(crate) no_ignore: bool,
+
+    /// Follow symlinked files and directories during traversal.
+    pub(crate) follow_symlinks: bool,
+
+    /// Descend into VCS directories (.git and friends),
+    /// which are skipped by default.
+    pub(crate) no_ignore_vcs: bool,
+
+    /// Type names from `-t`; only files of these types are searched.
+    pub(crate) types: Vec<String>,
+
+    /// Type names from `-T`; files of these types are skipped.
+    pub(crate) type_nots: Vec<String>,
     pub(crate) synchronous_printer: bool,
     pub(crate) quiet: bool,
 
     pub(crate) targets: Vec<Target>,
 
     pub(crate) stats: bool,
+
+    /// Print only a count of matching lines per target,
+    /// instead of the lines themselves.
+    pub(crate) count_only: bool,
+
+    /// Print only the names of targets containing at least one match.
+    pub(crate) files_with_matches: bool,
+
+    /// Forces (`-H`) or suppresses (`-h`) the per-file heading,
+    /// overriding the single-vs-many-targets inference in `main`.
+    pub(crate) filename: Option<bool>,
+
+    /// Print the files that would be searched, without
+    /// opening them; the pattern may be omitted.
+    pub(crate) files_only: bool,
+
+    /// Search exactly the files named in this list
+    /// (`-` reads the list from stdin), skipping traversal.
+    pub(crate) files_from: Option<String>,
+
+    /// The `--files-from` list is NUL-separated instead
+    /// of newline-separated.
+    pub(crate) files_from_nul: bool,
+
+    /// Stop searching each target after this many matching lines.
+    pub(crate) max_count: Option<usize>,
+
+    /// Don't descend into directories deeper than this many
+    /// levels below the search root.
+    pub(crate) max_depth: Option<usize>,
+
+    /// Skip files fewer than this many levels below the search root.
+    pub(crate) min_depth: Option<usize>,
+
+    /// Order results by this property (`--sort`/`--sortr`):
+    /// path, modified, created, or size.
+    pub(crate) sort: Option<String>,
+
+    /// Reverse the `--sort` ordering (`--sortr`).
+    pub(crate) sort_reverse: bool,
+
+    /// Emit per-file result groups in file-discovery order,
+    /// buffering output as needed (`--ordered`).
+    pub(crate) ordered: bool,
+
+    /// How many traversal workers and concurrent file searches
+    /// to run (`-j`); the default is one per available core.
+    pub(crate) threads: Option<usize>,
+
+    /// Cap how many files may be held open at once; the default
+    /// derives from the process's file-descriptor limit.
+    pub(crate) max_open_files: Option<usize>,
+
+    /// Transparently decompress compressed files (.gz and
+    /// friends) and search their contents (`-z`).
+    pub(crate) search_zip: bool,
+
+    /// Descend into zip/tar/jar archives and search each member
+    /// (`--search-archives`).
+    pub(crate) search_archives: bool,
+
+    /// Skip files whose contents are byte-identical to an
+    /// already-searched file (`--dedupe-contents`).
+    pub(crate) dedupe_contents: bool,
+
+    /// Search files even when binary detection would skip them
+    /// (`-a`/`--text`).
+    pub(crate) text: bool,
+
+    /// Decode inputs from this named encoding instead of relying
+    /// on BOM detection (`--encoding`).
+    pub(crate) encoding: Option<String>,
+
+    /// The byte records are split on, when overridden
+    /// (`--null-data`/`--line-terminator`).
+    pub(crate) line_terminator: Option<u8>,
+
+    /// Truncate (or summarize) printed lines longer than this
+    /// many bytes (`--max-columns`).
+    pub(crate) max_columns: Option<usize>,
+
+    /// Print the byte offset of each reported line (`-b`).
+    pub(crate) byte_offset: bool,
+
+    /// Show grouped headings with each file's match count
+    /// (`--heading-counts`).
+    pub(crate) heading_counts: bool,
+
+    /// Override the `--` marker printed between context blocks.
+    pub(crate) context_separator: Option<String>,
+
+    /// Suppress the context-block marker entirely.
+    pub(crate) no_context_separator: bool,
+
+    /// Override the blank line printed before each group heading.
+    pub(crate) group_separator: Option<String>,
+
+    /// Suppress the group separator entirely.
+    pub(crate) no_group_separator: bool,
+
+    /// Override the `:` between a match's fields and its text.
+    pub(crate) field_match_separator: Option<String>,
+
+    /// Override the `-` between a context line's fields and its text.
+    pub(crate) field_context_separator: Option<String>,
+
+    /// Block-buffer stdout instead of line-buffering it.
+    pub(crate) block_buffered: bool,
+
+    /// Preallocate this many line buffers in the pool
+    /// (`--buffer-count`).
+    pub(crate) buffer_count: Option<usize>,
+
+    /// Start each line buffer at this many bytes (`--buffer-size`).
+    pub(crate) buffer_size: Option<usize>,
+
+    /// Shrink grown line buffers back to their starting size when
+    /// they return to the pool (`--buffer-shrink`).
+    pub(crate) buffer_shrink: bool,
+
+    /// Emit results as JSON Lines events.
+    pub(crate) json: bool,
+
+    /// Render results as a Markdown report.
+    pub(crate) markdown: bool,
+
+    /// Also write matches to this path in Vim's quickfix layout.
+    pub(crate) quickfix: Option<String>,
+
+    /// Wrap printed paths in OSC-8 hyperlinks built from this template.
+    pub(crate) hyperlink_format: Option<String>,
+
+    /// Pad line numbers into aligned columns per file group.
+    pub(crate) align: bool,
+
+    /// Strip leading indentation from printed match lines.
+    pub(crate) trim: bool,
+
+    /// Group results under directory headings instead of per file.
+    pub(crate) group_by_dir: bool,
+
+    /// Emit the run's stats as a single JSON object.
+    pub(crate) stats_json: bool,
+
+    /// Print the N slowest files searched.
+    pub(crate) stats_files: Option<usize>,
+
+    /// Print per-extension aggregates of the searched files.
+    pub(crate) stats_by_type: bool,
+
+    /// Suppress all match output and print only the stats block.
+    pub(crate) stats_only: bool,
+
+    /// Browse results in a full-screen terminal UI instead of
+    /// printing them (`--tui`).
+    pub(crate) tui: bool,
+
+    /// Walk once, then repeatedly prompt for patterns to search
+    /// the cached file set (`--repl`).
+    pub(crate) repl: bool,
+
+    /// Serve JSON-RPC search requests over stdio (`--serve`).
+    pub(crate) serve: bool,
+
+    /// Explain, for each path argument, whether it would be
+    /// searched and which rule excludes it (`--explain`).
+    pub(crate) explain: bool,
+
+    /// Only content-search files whose path matches this regex
+    /// (`--path-regex`).
+    pub(crate) path_regex: Option<String>,
+
+    /// Suppress the end-of-run messages about files that could
+    /// not be opened or read.
+    pub(crate) no_messages: bool,
+
+    /// Report per-file diagnostics to stderr, e.g. which files
+    /// `--dedupe-contents` skipped and what they duplicated.
+    pub(crate) verbose: bool,
+
+    /// Stop searching after this many seconds, keeping whatever
+    /// was found by then.
+    pub(crate) timeout: Option<usize>,
+
+    /// When to colorize output.
+    pub(crate) color: ColorMode,
+
+    /// User-supplied color specs, e.g. `match:fg:yellow`.
+    pub(crate) color_specs: Vec<String>,
+
+    /// When present, print matching lines with each match
+    /// replaced by this template.
+    pub(crate) replace_template: Option<String>,
+
+    /// How many lines of context to print after each matching line.
+    pub(crate) after_context: usize,
+
+    /// How many lines of context to print before each matching line.
+    pub(crate) before_context: usize,
+}
+
+/// Where a flag appears in `--help`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Category {
+    Matching,
+    Selection,
+    Output,
+    Performance,
+    Stats,
+    General,
+}
+
+impl Category {
+    fn label(self) -> &'static str {
+        match self {
+            Category::Matching => "Pattern matching",
+            Category::Selection => "File selection and traversal",
+            Category::Output => "Output",
+            Category::Performance => "Resource usage",
+            Category::Stats => "Stats and diagnostics",
+            Category::General => "General",
+        }
+    }
+}
+
+/// The help categories, in display order.
+const CATEGORIES: &[Category] = &[
+    Category::Matching,
+    Category::Selection,
+    Category::Output,
+    Category::Performance,
+    Category::Stats,
+    Category::General,
+];
+
+/// What recognizing a flag does to the parsed input.
+enum Action {
+    /// The flag stands alone (`-i`).
+    Set(fn(&mut UserInput)),
+
+    /// The flag consumes a value (`--sort path`, `--sort=path`).
+    SetValue(fn(&mut UserInput, String)),
+}
+
+/// One row of the flag table: how the flag is spelled, where it
+/// appears in `--help`, and what recognizing it does. The parser,
+/// the bundling logic, and the help text are all driven by this
+/// one table, so they cannot drift apart.
+struct FlagSpec {
+    short: Option<&'static str>,
+    long: Option<&'static str>,
+    value_name: Option<&'static str>,
+    category: Category,
+    help: &'static str,
+
+    /// Undoes the flag, giving it a `--no-FOO` spelling; the last
+    /// of a flag/negation pair on the command line wins, so these
+    /// exist mainly to override config-file or TOYGREP_OPTS
+    /// defaults per invocation.
+    negate: Option<fn(&mut UserInput)>,
+
+    action: Action,
+}
+
+impl FlagSpec {
+    fn takes_value(&self) -> bool {
+        matches!(self.action, Action::SetValue(_))
+    }
+
+    /// The flag's spellings and value placeholder as shown in
+    /// `--help`, e.g. `-t, --type NAME`.
+    fn display(&self) -> String {
+        let mut display = String::new();
+
+        match (self.short, self.long) {
+            (Some(short), Some(long)) => {
+                display.push_str(short);
+                display.push_str(", ");
+                display.push_str(long);
+            }
+            (Some(short), None) => display.push_str(short),
+            (None, Some(long)) => display.push_str(long),
+            (None, None) => {}
+        }
+
+        if let Some(value_name) = self.value_name {
+            display.push(' ');
+            display.push_str(value_name);
+        }
+
+        display
+    }
+}
+
+/// Every flag toygrep understands. `--profile` is listed for the
+/// help text but applied by a pre-scan in `capture_input`, since
+/// its flags must be injected before ordinary parsing.
+const FLAG_SPECS: &[FlagSpec] = &[
+    FlagSpec {
+        short: Some("-i"),
+        long: Some("--case-insensitive"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Case insensitive match.",
+        negate: Some(|i| i.case_insensitive = false),
+        action: Action::Set(|i| i.case_insensitive = true),
+    },
+    FlagSpec {
+        short: Some("-s"),
+        long: Some("--case-sensitive"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Case sensitive match; overrides -i.",
+        negate: Some(|i| i.case_sensitive = false),
+        action: Action::Set(|i| i.case_sensitive = true),
+    },
+    FlagSpec {
+        short: Some("-w"),
+        long: Some("--whole-word"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Match whole word.",
+        negate: Some(|i| i.whole_word = false),
+        action: Action::Set(|i| i.whole_word = true),
+    },
+    FlagSpec {
+        short: Some("-x"),
+        long: Some("--line-regexp"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Require the pattern to match an entire line.",
+        negate: Some(|i| i.whole_line = false),
+        action: Action::Set(|i| i.whole_line = true),
+    },
+    FlagSpec {
+        short: Some("-F"),
+        long: Some("--fixed-strings"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Treat the pattern as a literal string, not a regex.",
+        negate: Some(|i| i.fixed_strings = false),
+        action: Action::Set(|i| i.fixed_strings = true),
+    },
+    FlagSpec {
+        short: Some("-U"),
+        long: Some("--multiline"),
+        value_name: None,
+        category: Category::Matching,
+        help: "Allow patterns to match across line boundaries.",
+        negate: Some(|i| i.multiline = false),
+        action: Action::Set(|i| i.multiline = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--engine"),
+        value_name: Some("WHICH"),
+        category: Category::Matching,
+        help: "Regex engine: auto, default, or fancy (lookaround support).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.engine = parse_engine(&v)),
+    },
+    FlagSpec {
+        short: Some("-f"),
+        long: Some("--file"),
+        value_name: Some("FILE"),
+        category: Category::Matching,
+        help: "Read patterns from FILE, one per line, combined as alternatives.",
+        negate: None,
+        action: Action::SetValue(|i, v| read_pattern_file(&v, &mut i.patterns)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--all-of"),
+        value_name: Some("PATTERN"),
+        category: Category::Matching,
+        help:
+            "Require lines to also match PATTERN; repeatable. The base pattern may then be omitted.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.all_of.push(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--none-of"),
+        value_name: Some("PATTERN"),
+        category: Category::Matching,
+        help: "Exclude lines matching PATTERN; repeatable.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.none_of.push(v)),
+    },
+    FlagSpec {
+        short: Some("-m"),
+        long: Some("--max-count"),
+        value_name: Some("NUM"),
+        category: Category::Matching,
+        help: "Stop searching each file after NUM matching lines.",
+        negate: Some(|i| i.max_count = None),
+        action: Action::SetValue(|i, v| i.max_count = Some(parse_num("--max-count", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--no-ignore"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Don't honor .gitignore/.ignore/.toygrepignore files.",
+        negate: None,
+        action: Action::Set(|i| i.no_ignore = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--no-ignore-vcs"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Descend into VCS directories like .git (skipped by default).",
+        negate: None,
+        action: Action::Set(|i| i.no_ignore_vcs = true),
+    },
+    FlagSpec {
+        short: Some("-L"),
+        long: Some("--follow"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Follow symlinks (with symlink-loop protection).",
+        negate: Some(|i| i.follow_symlinks = false),
+        action: Action::Set(|i| i.follow_symlinks = true),
+    },
+    FlagSpec {
+        short: Some("-t"),
+        long: Some("--type"),
+        value_name: Some("NAME"),
+        category: Category::Selection,
+        help: "Only search files of the named type (rust, py, md, ...); repeatable.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.types.push(v)),
+    },
+    FlagSpec {
+        short: Some("-T"),
+        long: Some("--type-not"),
+        value_name: Some("NAME"),
+        category: Category::Selection,
+        help: "Skip files of the named type; repeatable.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.type_nots.push(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--max-depth"),
+        value_name: Some("NUM"),
+        category: Category::Selection,
+        help: "Descend at most NUM directory levels (1 = the root itself).",
+        negate: Some(|i| i.max_depth = None),
+        action: Action::SetValue(|i, v| i.max_depth = Some(parse_num("--max-depth", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--min-depth"),
+        value_name: Some("NUM"),
+        category: Category::Selection,
+        help: "Skip files fewer than NUM levels below the root.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.min_depth = Some(parse_num("--min-depth", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--files"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Print the files that would be searched, without searching them.",
+        negate: None,
+        action: Action::Set(|i| i.files_only = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--files-with-name"),
+        value_name: Some("PATTERN"),
+        category: Category::Selection,
+        help: "Like --files, but list only files whose path matches PATTERN.",
+        negate: None,
+        action: Action::SetValue(|i, v| {
+            i.files_only = true;
+            i.search_pattern = v;
+        }),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--path-regex"),
+        value_name: Some("PATTERN"),
+        category: Category::Selection,
+        help: "Only search files whose path matches PATTERN.",
+        negate: Some(|i| i.path_regex = None),
+        action: Action::SetValue(|i, v| i.path_regex = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--files-from"),
+        value_name: Some("FILE"),
+        category: Category::Selection,
+        help: "Search the files listed in FILE, one per line ('-' for stdin).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.files_from = Some(v)),
+    },
+    FlagSpec {
+        short: Some("-0"),
+        long: None,
+        value_name: None,
+        category: Category::Selection,
+        help: "The --files-from list is NUL-separated (e.g. from xargs -0 producers).",
+        negate: None,
+        action: Action::Set(|i| i.files_from_nul = true),
+    },
+    FlagSpec {
+        short: Some("-z"),
+        long: Some("--search-zip"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Decompress and search .gz/.zst/.xz/.bz2 files.",
+        negate: Some(|i| i.search_zip = false),
+        action: Action::Set(|i| i.search_zip = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--search-archives"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Search inside .zip/.jar/.tar/.tar.gz archives.",
+        negate: Some(|i| i.search_archives = false),
+        action: Action::Set(|i| i.search_archives = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--dedupe-contents"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Skip files byte-identical to an already-searched file.",
+        negate: Some(|i| i.dedupe_contents = false),
+        action: Action::Set(|i| i.dedupe_contents = true),
+    },
+    FlagSpec {
+        short: Some("-a"),
+        long: Some("--text"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Search binary files as if they were text.",
+        negate: Some(|i| i.text = false),
+        action: Action::Set(|i| i.text = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--encoding"),
+        value_name: Some("NAME"),
+        category: Category::Selection,
+        help: "Decode inputs as utf-8, latin1, utf-16le, or utf-16be.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.encoding = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--null-data"),
+        value_name: None,
+        category: Category::Selection,
+        help: "Treat input records as NUL-separated (e.g. from find -print0).",
+        negate: None,
+        action: Action::Set(|i| i.line_terminator = Some(0)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--line-terminator"),
+        value_name: Some("BYTE"),
+        category: Category::Selection,
+        help: "Split records on BYTE: a character, an escape like \\0, or a number.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.line_terminator = Some(parse_terminator(&v))),
+    },
+    FlagSpec {
+        short: Some("-c"),
+        long: Some("--count"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print only a count of matching lines per file.",
+        negate: Some(|i| i.count_only = false),
+        action: Action::Set(|i| i.count_only = true),
+    },
+    FlagSpec {
+        short: Some("-l"),
+        long: Some("--files-with-matches"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print only the names of files containing matches.",
+        negate: Some(|i| i.files_with_matches = false),
+        action: Action::Set(|i| i.files_with_matches = true),
+    },
+    FlagSpec {
+        short: Some("-H"),
+        long: Some("--with-filename"),
+        value_name: None,
+        category: Category::Output,
+        help: "Show the file heading for every match, even with a single target.",
+        negate: None,
+        action: Action::Set(|i| i.filename = Some(true)),
+    },
+    FlagSpec {
+        short: Some("-h"),
+        long: Some("--no-filename"),
+        value_name: None,
+        category: Category::Output,
+        help: "Never show file headings, even with multiple targets.",
+        negate: None,
+        action: Action::Set(|i| i.filename = Some(false)),
+    },
+    FlagSpec {
+        short: Some("-q"),
+        long: Some("--quiet"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print nothing; the exit code reports whether anything matched.",
+        negate: Some(|i| i.quiet = false),
+        action: Action::Set(|i| i.quiet = true),
+    },
+    FlagSpec {
+        short: Some("-p"),
+        long: Some("--sync-print"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print synchronous with searching, instead of spawning a dedicated print thread.",
+        negate: Some(|i| i.synchronous_printer = false),
+        action: Action::Set(|i| i.synchronous_printer = true),
+    },
+    FlagSpec {
+        short: Some("-r"),
+        long: Some("--replace"),
+        value_name: Some("TEMPLATE"),
+        category: Category::Output,
+        help: "Print lines with matches replaced by TEMPLATE ($1, ${name} supported).",
+        negate: Some(|i| i.replace_template = None),
+        action: Action::SetValue(|i, v| i.replace_template = Some(v)),
+    },
+    FlagSpec {
+        short: Some("-A"),
+        long: Some("--after-context"),
+        value_name: Some("NUM"),
+        category: Category::Output,
+        help: "Print NUM lines of context after each match.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.after_context = parse_num("--after-context", &v)),
+    },
+    FlagSpec {
+        short: Some("-B"),
+        long: Some("--before-context"),
+        value_name: Some("NUM"),
+        category: Category::Output,
+        help: "Print NUM lines of context before each match.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.before_context = parse_num("--before-context", &v)),
+    },
+    FlagSpec {
+        short: Some("-C"),
+        long: Some("--context"),
+        value_name: Some("NUM"),
+        category: Category::Output,
+        help: "Print NUM lines of context before and after each match.",
+        negate: None,
+        action: Action::SetValue(|i, v| {
+            let num = parse_num("--context", &v);
+            i.after_context = num;
+            i.before_context = num;
+        }),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--max-columns"),
+        value_name: Some("NUM"),
+        category: Category::Output,
+        help: "Truncate printed lines longer than NUM bytes.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.max_columns = Some(parse_num("--max-columns", &v))),
+    },
+    FlagSpec {
+        short: Some("-b"),
+        long: Some("--byte-offset"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print each line's byte offset within its file.",
+        negate: Some(|i| i.byte_offset = false),
+        action: Action::Set(|i| i.byte_offset = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--heading-counts"),
+        value_name: None,
+        category: Category::Output,
+        help: "Show each file heading with its match count.",
+        negate: Some(|i| i.heading_counts = false),
+        action: Action::Set(|i| i.heading_counts = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--context-separator"),
+        value_name: Some("SEP"),
+        category: Category::Output,
+        help: "Print SEP (default: --) between context blocks.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.context_separator = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--no-context-separator"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print nothing between context blocks.",
+        negate: None,
+        action: Action::Set(|i| i.no_context_separator = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--group-separator"),
+        value_name: Some("SEP"),
+        category: Category::Output,
+        help: "Print SEP (default: a blank line) before file headings.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.group_separator = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--no-group-separator"),
+        value_name: None,
+        category: Category::Output,
+        help: "Print nothing before file headings.",
+        negate: None,
+        action: Action::Set(|i| i.no_group_separator = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--field-match-separator"),
+        value_name: Some("SEP"),
+        category: Category::Output,
+        help: "Delimit match-line fields with SEP (default: :).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.field_match_separator = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--field-context-separator"),
+        value_name: Some("SEP"),
+        category: Category::Output,
+        help: "Delimit context-line fields with SEP (default: -).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.field_context_separator = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--line-buffered"),
+        value_name: None,
+        category: Category::Output,
+        help: "Flush output after every line (the default).",
+        negate: None,
+        action: Action::Set(|i| i.block_buffered = false),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--block-buffered"),
+        value_name: None,
+        category: Category::Output,
+        help: "Flush output only as internal blocks fill.",
+        negate: None,
+        action: Action::Set(|i| i.block_buffered = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--color"),
+        value_name: Some("WHEN"),
+        category: Category::Output,
+        help: "When to colorize output: auto (the default), always, or never.",
+        negate: Some(|i| i.color = ColorMode::Never),
+        action: Action::SetValue(|i, v| i.color = parse_color_mode(&v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--colors"),
+        value_name: Some("SPEC"),
+        category: Category::Output,
+        help: "Override a color, e.g. 'match:fg:yellow' or 'line:style:bold'.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.color_specs.push(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--align"),
+        value_name: None,
+        category: Category::Output,
+        help: "Pad line numbers into aligned columns per file.",
+        negate: Some(|i| i.align = false),
+        action: Action::Set(|i| i.align = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--trim"),
+        value_name: None,
+        category: Category::Output,
+        help: "Strip leading indentation from printed lines.",
+        negate: Some(|i| i.trim = false),
+        action: Action::Set(|i| i.trim = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--group-by"),
+        value_name: Some("KEY"),
+        category: Category::Output,
+        help: "Group results by 'file' (default) or 'dir'.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.group_by_dir = parse_group_by(&v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--json"),
+        value_name: None,
+        category: Category::Output,
+        help: "Emit results as JSON Lines events.",
+        negate: Some(|i| i.json = false),
+        action: Action::Set(|i| i.json = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--markdown"),
+        value_name: None,
+        category: Category::Output,
+        help: "Render results as a Markdown report.",
+        negate: Some(|i| i.markdown = false),
+        action: Action::Set(|i| i.markdown = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--quickfix"),
+        value_name: Some("PATH"),
+        category: Category::Output,
+        help: "Also write matches to PATH for Vim's :cfile.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.quickfix = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--hyperlink-format"),
+        value_name: Some("TMPL"),
+        category: Category::Output,
+        help: "Hyperlink paths via TMPL, e.g. vscode://file/{path}:{line}.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.hyperlink_format = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--sort"),
+        value_name: Some("KEY"),
+        category: Category::Output,
+        help: "Sort results ascending by path, modified, created, or size.",
+        negate: Some(|i| {
+            i.sort = None;
+            i.sort_reverse = false;
+        }),
+        action: Action::SetValue(|i, v| i.sort = Some(v)),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--sortr"),
+        value_name: Some("KEY"),
+        category: Category::Output,
+        help: "Like --sort, but descending.",
+        negate: None,
+        action: Action::SetValue(|i, v| {
+            i.sort = Some(v);
+            i.sort_reverse = true;
+        }),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--tui"),
+        value_name: None,
+        category: Category::Output,
+        help: "Browse results interactively; Enter opens a match in $EDITOR.",
+        negate: Some(|i| i.tui = false),
+        action: Action::Set(|i| i.tui = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--repl"),
+        value_name: None,
+        category: Category::General,
+        help: "Walk once, then prompt for patterns to search the cached file set.",
+        negate: Some(|i| i.repl = false),
+        action: Action::Set(|i| i.repl = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--serve"),
+        value_name: None,
+        category: Category::General,
+        help: "Serve JSON-RPC search requests over stdio, for editor integrations.",
+        negate: Some(|i| i.serve = false),
+        action: Action::Set(|i| i.serve = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--explain"),
+        value_name: None,
+        category: Category::General,
+        help: "Explain whether each PATH would be searched, and which rule excludes it.",
+        negate: Some(|i| i.explain = false),
+        action: Action::Set(|i| i.explain = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--ordered"),
+        value_name: None,
+        category: Category::Output,
+        help: "Emit per-file groups in discovery order (buffers output).",
+        negate: Some(|i| i.ordered = false),
+        action: Action::Set(|i| i.ordered = true),
+    },
+    FlagSpec {
+        short: Some("-j"),
+        long: Some("--threads"),
+        value_name: Some("NUM"),
+        category: Category::Performance,
+        help: "Use NUM traversal workers and concurrent file searches (default: one per core).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.threads = Some(parse_num("--threads", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--max-open-files"),
+        value_name: Some("NUM"),
+        category: Category::Performance,
+        help: "Hold at most NUM files open at once (default: from ulimit -n).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.max_open_files = Some(parse_num("--max-open-files", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--buffer-count"),
+        value_name: Some("NUM"),
+        category: Category::Performance,
+        help: "Preallocate NUM line buffers (default: 4).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.buffer_count = Some(parse_num("--buffer-count", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--buffer-size"),
+        value_name: Some("NUM"),
+        category: Category::Performance,
+        help: "Start each line buffer at NUM bytes (default: 8192).",
+        negate: None,
+        action: Action::SetValue(|i, v| i.buffer_size = Some(parse_num("--buffer-size", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--buffer-shrink"),
+        value_name: None,
+        category: Category::Performance,
+        help: "Shrink grown line buffers back down between files.",
+        negate: Some(|i| i.buffer_shrink = false),
+        action: Action::Set(|i| i.buffer_shrink = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--timeout"),
+        value_name: Some("SECS"),
+        category: Category::Performance,
+        help: "Stop searching after SECS seconds, keeping results found so far.",
+        negate: Some(|i| i.timeout = None),
+        action: Action::SetValue(|i, v| i.timeout = Some(parse_num("--timeout", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--stats"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Print statistical information with output.",
+        negate: Some(|i| i.stats = false),
+        action: Action::Set(|i| i.stats = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--stats-json"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Emit the run's stats as one JSON object.",
+        negate: Some(|i| i.stats_json = false),
+        action: Action::Set(|i| i.stats_json = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--stats-files"),
+        value_name: Some("N"),
+        category: Category::Stats,
+        help: "Print the N slowest files searched.",
+        negate: None,
+        action: Action::SetValue(|i, v| i.stats_files = Some(parse_num("--stats-files", &v))),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--stats-by-type"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Print per-extension stats for searched files.",
+        negate: Some(|i| i.stats_by_type = false),
+        action: Action::Set(|i| i.stats_by_type = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--stats-only"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Print only the stats block, with no match output.",
+        negate: Some(|i| i.stats_only = false),
+        action: Action::Set(|i| i.stats_only = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--no-messages"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Suppress messages about unreadable files and directories.",
+        negate: None,
+        action: Action::Set(|i| i.no_messages = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--verbose"),
+        value_name: None,
+        category: Category::Stats,
+        help: "Report per-file diagnostics, e.g. content-dedupe skips.",
+        negate: Some(|i| i.verbose = false),
+        action: Action::Set(|i| i.verbose = true),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--profile"),
+        value_name: Some("NAME"),
+        category: Category::General,
+        help: "Apply the flags from [profile.NAME] in the config file.",
+        negate: None,
+        action: Action::SetValue(|_, _| {}),
+    },
+    FlagSpec {
+        short: None,
+        long: Some("--help"),
+        value_name: None,
+        category: Category::General,
+        help: "Print this help and exit.",
+        negate: None,
+        action: Action::Set(|_| {
+            print_help();
+            std::process::exit(0);
+        }),
+    },
+    FlagSpec {
+        short: Some("-V"),
+        long: Some("--version"),
+        value_name: None,
+        category: Category::General,
+        help: "Print the version and exit.",
+        negate: None,
+        action: Action::Set(|_| {
+            print_version();
+            std::process::exit(0);
+        }),
+    },
+];
+
+fn find_spec(name: &str) -> Option<&'static FlagSpec> {
+    FLAG_SPECS
+        .iter()
+        .find(|spec| spec.short == Some(name) || spec.long == Some(name))
 }
 
 pub(crate) fn print_help() {
@@ -23,61 +1147,541 @@ pub(crate) fn print_help() {
         os_str.to_string_lossy().into()
     };
 
+    print_version();
+    println!("Usage: {} [OPTION]... PATTERN [FILE]...", exec_name);
+
+    for category in CATEGORIES {
+        println!();
+        println!("{}:", category.label());
+
+        for spec in FLAG_SPECS.iter().filter(|spec| spec.category == *category) {
+            let display = spec.display();
+
+            // A spelling too wide for the column pushes its help
+            // text onto the next line, as grep does.
+            if display.len() > 27 {
+                println!("    {}", display);
+                println!("    {:27} {}", "", spec.help);
+            } else {
+                println!("    {:27} {}", display, spec.help);
+            }
+        }
+    }
+
+    println!();
     println!(
-        "Usage:
-{} [OPTION]... PATTERN [FILE]...
-    Options:
-    -i, --case-insensitive      Case insensitive match.
-    -w, --whole-word            Match whole word.
-    -t, --stats                 Print statistical information with output.
-    -p, --sync-print            Print synchronous with searching, instead of spawning a dedicated print thread.
-    -q, --quiet                 Don't run any priting logic at all.",
-        exec_name
+        "Most boolean flags accept a --no-FOO spelling that undoes them; the
+last of a flag/negation pair wins, so defaults from the config file or
+TOYGREP_OPTS can be overridden per invocation.
+
+Default flags are also read from ~/.toygreprc (override the path with
+TOYGREP_CONFIG) and the TOYGREP_OPTS environment variable; the command
+line overrides both."
     );
 }
 
+fn print_version() {
+    println!("toygrep {}", env!("CARGO_PKG_VERSION"));
+}
+
 /// Parses the given arguments, following this expected format:
 /// toygrep [OPTION]... PATTERN [FILE]...
+impl UserInput {
+    /// The effective case sensitivity after resolving `-s` against
+    /// `-i` and the built-in default; `-s` always wins.
+    pub(crate) fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive && !self.case_sensitive
+    }
+}
+
+/// Whether the short flag `-c` takes a value (`-t rs`), per the
+/// flag table; inside a bundle, the rest of the token -- or
+/// failing that, the next argument -- is that value.
+fn short_takes_value(c: char) -> bool {
+    let spelled = format!("-{}", c);
+
+    find_spec(&spelled).map_or(false, FlagSpec::takes_value)
+}
+
+/// Splits a POSIX-style bundled short token into its standalone
+/// spellings: `-iwt` becomes `-i -w -t`, and an attached value
+/// like `-B2` becomes `-B 2`. Anything that isn't a bundle (a
+/// long flag, a lone short, a bare `-`) passes through untouched.
+fn split_bundled_shorts(arg: &str) -> Option<Vec<String>> {
+    let body = arg.strip_prefix('-')?;
+
+    if arg.starts_with("--") || body.chars().count() < 2 {
+        return None;
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = body.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        tokens.push(format!("-{}", c));
+
+        // Everything after a value-taking flag is its value;
+        // `-B2` carries the value in the same token, `-iwB 2`
+        // leaves it to the following argument.
+        if short_takes_value(c) {
+            let value = &body[i + c.len_utf8()..];
+
+            if !value.is_empty() {
+                tokens.push(value.to_owned());
+            }
+
+            break;
+        }
+    }
+
+    Some(tokens)
+}
+
+/// The environment variable holding default flags applied to
+/// every invocation (like grep's old GREP_OPTIONS).
+const OPTS_ENV_VAR: &str = "TOYGREP_OPTS";
+
+fn env_default_args() -> Vec<String> {
+    std::env::var(OPTS_ENV_VAR)
+        .map(|opts| split_opts(&opts))
+        .unwrap_or_default()
+}
+
+/// Splits the variable's contents on whitespace. Quoting is not
+/// supported: a value with spaces in it belongs on the command
+/// line, not in the environment.
+fn split_opts(opts: &str) -> Vec<String> {
+    opts.split_whitespace().map(str::to_owned).collect()
+}
+
+/// Pulls every `--profile NAME` out of the argument queue before
+/// ordinary parsing -- the profile's flags must be injected ahead
+/// of the rest -- returning the last name given. The scan stops
+/// at `--`, where option parsing ends anyway.
+fn take_profile_flag(args: &mut VecDeque<String>) -> Option<String> {
+    let mut profile = None;
+    let mut i = 0;
+
+    while i < args.len() {
+        if args[i] == "--" {
+            break;
+        }
+
+        if args[i] == "--profile" {
+            args.remove(i);
+            profile = Some(expect_value("--profile", args.remove(i)));
+        } else {
+            i += 1;
+        }
+    }
+
+    profile
+}
+
 pub(crate) fn capture_input(args: impl Iterator<Item = String>) -> UserInput {
-    let mut user_input = UserInput::default();
+    capture_input_with(args, env_default_args(), crate::config::Config::load())
+}
+
+/// The injectable core of `capture_input`: the environment's
+/// default flags and the config file are parameters, so the unit
+/// tests stay hermetic whatever `TOYGREP_OPTS` holds in the
+/// developer's shell and whatever their `~/.toygreprc` says.
+fn capture_input_with(
+    args: impl Iterator<Item = String>,
+    env_defaults: Vec<String>,
+    config: crate::config::Config,
+) -> UserInput {
+    let mut user_input = UserInput {
+        case_insensitive: CASE_INSENSITIVE_BY_DEFAULT,
+        ..UserInput::default()
+    };
 
     // Skip the first arg (executable name).
-    let mut args = args.skip(1).peekable();
-
-    // Flags come first.
-    for arg in args.by_ref().peeking_take_while(|a| a.starts_with('-')) {
-        // TODO: support combined flags, like '-iwr'
-        match arg.as_str() {
-            "-i" | "--case-insensitive" => user_input.case_insensitive = true,
-            "-w" | "--whole-word" => user_input.whole_word = true,
-            "-t" | "--stats" => user_input.stats = true,
-            "-p" | "--sync-print" => user_input.synchronous_printer = true,
-            "-q" | "--quiet" => user_input.quiet = true,
-            _ => {
-                panic!("Unknown flag: {}", arg);
+    let mut args: VecDeque<String> = args.skip(1).collect();
+
+    // Shell-wide defaults from TOYGREP_OPTS are parsed before the
+    // command line proper, so anything given on the command line
+    // itself takes precedence.
+    for token in env_defaults.into_iter().rev() {
+        args.push_front(token);
+    }
+
+    // Config-file flags come before even those: the precedence is
+    // config defaults, then the chosen profile, then TOYGREP_OPTS,
+    // then the command line itself.
+    let profile = take_profile_flag(&mut args);
+
+    if let Some(name) = &profile {
+        let profile_args = config.profile(name).unwrap_or_else(|| {
+            die(format!(
+                "unknown profile: {} (not in the config file)",
+                name
+            ))
+        });
+
+        for token in profile_args.iter().rev() {
+            args.push_front(token.clone());
+        }
+    }
+
+    for token in config.defaults().iter().rev() {
+        args.push_front(token.clone());
+    }
+
+    // Flags and positionals (the pattern, then targets) may be
+    // intermixed, grep-style; positionals are collected here and
+    // assigned once every flag has been seen.
+    let mut positionals: Vec<String> = Vec::new();
+
+    while let Some(arg) = args.pop_front() {
+        // A lone `-` is the stdin target, not a flag.
+        if !arg.starts_with('-') || arg == "-" {
+            positionals.push(arg);
+            continue;
+        }
+
+        // The conventional `--` ends option parsing: everything
+        // after it is a positional, even if it starts with a dash
+        // (`toygrep -- -foo`).
+        if arg == "--" {
+            positionals.extend(args.drain(..));
+            break;
+        }
+
+        // A bundled token is replaced by its standalone spellings
+        // and the loop takes another pass over those.
+        if let Some(expanded) = split_bundled_shorts(&arg) {
+            for token in expanded.into_iter().rev() {
+                args.push_front(token);
+            }
+
+            continue;
+        }
+
+        // `--flag=value` carries the value in the same token.
+        let (name, attached) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+            None => (arg, None),
+        };
+
+        // An unrecognized `--no-FOO` is the negation of `--FOO`,
+        // when that flag is negatable.
+        let spec = match find_spec(&name) {
+            Some(spec) => spec,
+            None => {
+                let negate = name
+                    .strip_prefix("--no-")
+                    .and_then(|base| find_spec(&format!("--{}", base)))
+                    .and_then(|spec| spec.negate);
+
+                match negate {
+                    Some(negate) => {
+                        if attached.is_some() {
+                            die(format!("flag {} does not take a value", name));
+                        }
+
+                        negate(&mut user_input);
+                        continue;
+                    }
+                    None => die(format!("unknown flag: {}", name)),
+                }
+            }
+        };
+
+        match spec.action {
+            Action::Set(apply) => {
+                if attached.is_some() {
+                    die(format!("flag {} does not take a value", name));
+                }
+
+                apply(&mut user_input);
+            }
+            Action::SetValue(apply) => {
+                let value = attached.unwrap_or_else(|| expect_value(&name, args.pop_front()));
+
+                apply(&mut user_input, value);
             }
         }
     }
 
-    // The search pattern is next.
-    if let Some(pattern) = args.next() {
-        user_input.search_pattern = pattern;
+    let mut positionals = positionals.into_iter();
+
+    // The first positional is the search pattern, unless patterns
+    // were already supplied from a file or via `--all-of`, or
+    // listing/REPL mode needs none (in which case every
+    // positional is a target).
+    if user_input.patterns.is_empty()
+        && user_input.all_of.is_empty()
+        && !user_input.files_only
+        && !user_input.repl
+        && !user_input.explain
+    {
+        if let Some(pattern) = positionals.next() {
+            user_input.search_pattern = pattern;
+        }
     }
 
-    user_input.targets = if is_stdin_provided() {
-        vec![Target::Stdin]
-    } else {
-        args.map(|a| a.into()).map(Target::for_path).collect()
-    };
+    // A literal `-` target means stdin, and may be interleaved
+    // with ordinary file targets.
+    user_input.targets = positionals.map(|a| Target::for_arg(&a)).collect();
 
     if user_input.targets.is_empty() {
-        let current_dir = std::env::current_dir().expect("Unable to access the current directory.");
-        user_input.targets = vec![Target::for_path(current_dir.into())];
+        if is_stdin_provided() {
+            user_input.targets = vec![Target::Stdin];
+        } else {
+            let current_dir = std::env::current_dir()
+                .unwrap_or_else(|e| die(format!("unable to access the current directory: {}", e)));
+            user_input.targets = vec![Target::for_path(current_dir.into())];
+        }
     }
 
     user_input
 }
 
+/// Parses a flag's value as a number,
+/// dying with a helpful message if it is malformed.
+fn parse_num(flag: &str, value: &str) -> usize {
+    value
+        .parse()
+        .unwrap_or_else(|_| die(format!("flag {} expects a numeric value", flag)))
+}
+
+/// Returns the value following a flag,
+/// dying with a helpful message if it is missing.
+fn expect_value(flag: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| die(format!("flag {} expects a value", flag)))
+}
+
+/// Reports a usage error the way grep does -- a one-line message
+/// to stderr, no backtrace -- and exits with status 2.
+fn die(message: String) -> ! {
+    eprintln!("toygrep: {}", message);
+    std::process::exit(2);
+}
+
+/// Parses a `--line-terminator` argument: a single character,
+/// an escape like `\0`/`\n`/`\t`, or a byte value in decimal.
+fn parse_terminator(value: &str) -> u8 {
+    match value {
+        "\\0" => return 0,
+        "\\n" => return b'\n',
+        "\\r" => return b'\r',
+        "\\t" => return b'\t',
+        _ => {}
+    }
+
+    let mut chars = value.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii() {
+            return c as u8;
+        }
+    }
+
+    value.parse().unwrap_or_else(|_| {
+        die(format!(
+            "invalid line terminator: {} (expected a character, an escape like \\0, or 0-255)",
+            value
+        ))
+    })
+}
+
+fn parse_engine(value: &str) -> crate::matcher::Engine {
+    use crate::matcher::Engine;
+
+    match value {
+        "auto" => Engine::Auto,
+        "default" => Engine::Default,
+        "fancy" => Engine::Fancy,
+        _ => die(format!(
+            "unknown engine: {} (expected auto, default, or fancy)",
+            value
+        )),
+    }
+}
+
+fn parse_group_by(value: &str) -> bool {
+    match value {
+        "dir" => true,
+        "file" => false,
+        _ => die(format!(
+            "unknown group-by key: {} (expected file or dir)",
+            value
+        )),
+    }
+}
+
+fn parse_color_mode(value: &str) -> ColorMode {
+    match value {
+        "auto" => ColorMode::Auto,
+        "always" => ColorMode::Always,
+        "never" => ColorMode::Never,
+        _ => die(format!(
+            "unknown color mode: {} (expected auto, always, or never)",
+            value
+        )),
+    }
+}
+
+/// Reads patterns from the given file, one per line,
+/// skipping empty lines and duplicates.
+fn read_pattern_file(path: &str, patterns: &mut Vec<String>) {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| die(format!("unable to read pattern file {}: {}", path, e)));
+
+    for line in contents.lines() {
+        if line.is_empty() {
+            continue;
+        }
+
+        if !patterns.iter().any(|p| p == line) {
+            patterns.push(line.to_owned());
+        }
+    }
+}
+
 fn is_stdin_provided() -> bool {
     atty::isnt(atty::Stream::Stdin)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> UserInput {
+        let args = std::iter::once("toygrep".to_owned()).chain(args.iter().map(|a| a.to_string()));
+
+        // Empty env defaults and an empty config keep the tests
+        // hermetic: the suite must pass whatever TOYGREP_OPTS is
+        // set to and whatever the developer's config file says.
+        capture_input_with(args, Vec::new(), crate::config::Config::default())
+    }
+
+    #[test]
+    fn bundled_shorts_split_into_standalone_flags() {
+        assert_eq!(
+            Some(vec!["-i".to_owned(), "-w".to_owned(), "-U".to_owned()]),
+            split_bundled_shorts("-iwU")
+        );
+    }
+
+    #[test]
+    fn an_attached_value_belongs_to_the_value_taking_flag() {
+        assert_eq!(
+            Some(vec!["-B".to_owned(), "2".to_owned()]),
+            split_bundled_shorts("-B2")
+        );
+
+        assert_eq!(
+            Some(vec!["-i".to_owned(), "-t".to_owned(), "rs".to_owned()]),
+            split_bundled_shorts("-itrs")
+        );
+    }
+
+    #[test]
+    fn non_bundles_pass_through_untouched() {
+        assert_eq!(None, split_bundled_shorts("--follow"));
+        assert_eq!(None, split_bundled_shorts("-i"));
+        assert_eq!(None, split_bundled_shorts("-"));
+        assert_eq!(None, split_bundled_shorts("pattern"));
+    }
+
+    #[test]
+    fn a_bundle_parses_the_same_as_separate_flags() {
+        let bundled = parse(&["-iwB2", "pattern", "file.txt"]);
+        let separate = parse(&["-i", "-w", "-B", "2", "pattern", "file.txt"]);
+
+        assert!(bundled.case_insensitive && separate.case_insensitive);
+        assert!(bundled.whole_word && separate.whole_word);
+        assert_eq!(2, bundled.before_context);
+        assert_eq!(2, separate.before_context);
+        assert_eq!("pattern", bundled.search_pattern);
+    }
+
+    #[test]
+    fn flag_spellings_are_unique() {
+        let mut seen = std::collections::HashSet::new();
+
+        for spec in FLAG_SPECS {
+            for spelling in spec.short.iter().chain(spec.long.iter()) {
+                assert!(seen.insert(*spelling), "duplicate spelling: {}", spelling);
+            }
+        }
+    }
+
+    #[test]
+    fn filename_display_follows_the_last_of_h_and_no_filename() {
+        assert_eq!(Some(true), parse(&["-H", "pattern"]).filename);
+        assert_eq!(Some(false), parse(&["-H", "-h", "pattern"]).filename);
+        assert_eq!(None, parse(&["pattern"]).filename);
+    }
+
+    #[test]
+    fn files_with_name_enables_listing_with_a_path_pattern() {
+        let input = parse(&["--files-with-name", r"\.rs$", "src"]);
+
+        assert!(input.files_only);
+        assert_eq!(r"\.rs$", input.search_pattern);
+        assert_eq!(1, input.targets.len());
+    }
+
+    #[test]
+    fn a_negation_undoes_the_flag_and_the_last_one_wins() {
+        let negated = parse(&["-i", "--no-case-insensitive", "pattern"]);
+        let reasserted = parse(&["--no-trim", "--trim", "pattern"]);
+        let cleared = parse(&["--sort", "path", "--no-sort", "pattern"]);
+
+        assert!(!negated.case_insensitive);
+        assert!(reasserted.trim);
+        assert_eq!(None, cleared.sort);
+    }
+
+    #[test]
+    fn an_equals_sign_supplies_the_value_to_a_long_flag() {
+        let input = parse(&["--sort=modified", "pattern"]);
+
+        assert_eq!(Some("modified".to_owned()), input.sort);
+    }
+
+    #[test]
+    fn the_last_profile_flag_wins_and_is_removed() {
+        let mut args: VecDeque<String> = ["-i", "--profile", "logs", "--profile", "code", "foo"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+
+        assert_eq!(Some("code".to_owned()), take_profile_flag(&mut args));
+        assert_eq!(
+            vec!["-i".to_owned(), "foo".to_owned()],
+            args.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn env_opts_split_on_whitespace() {
+        assert_eq!(
+            vec!["-i".to_owned(), "--sort".to_owned(), "path".to_owned()],
+            split_opts("  -i   --sort path ")
+        );
+        assert!(split_opts("").is_empty());
+    }
+
+    #[test]
+    fn double_dash_ends_option_parsing() {
+        let input = parse(&["-i", "--", "-foo", "-bar.txt"]);
+
+        assert!(input.case_insensitive);
+        assert_eq!("-foo", input.search_pattern);
+        assert_eq!(1, input.targets.len());
+    }
+
+    #[test]
+    fn flags_may_follow_the_pattern_and_targets() {
+        let input = parse(&["pattern", "src", "-i", "-B", "2"]);
+
+        assert!(input.case_insensitive);
+        assert_eq!(2, input.before_context);
+        assert_eq!("pattern", input.search_pattern);
+        assert_eq!(1, input.targets.len());
+    }
+}
@@ -1,4 +1,6 @@
 pub(crate) mod async_line_buffer;
 mod buffer_pool;
+pub(crate) mod chunk_reader;
+pub(crate) mod transcode;
 
-pub(crate) use buffer_pool::BufferPool;
+pub(crate) use buffer_pool::{BufferPool, BufferPoolBuilder};
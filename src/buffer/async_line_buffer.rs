@@ -3,15 +3,30 @@
 use async_std::prelude::*;
 use std::collections::VecDeque;
 
+/// How large a single buffer may grow while chasing one line.
+/// A line that still doesn't fit (a multi-GB minified blob, say)
+/// skips the remainder of its input with a warning instead of
+/// exhausting memory.
+const DEFAULT_MAX_SIZE_BYTES: usize = 64 * (1 << 20);
+
 #[derive(Debug, Clone)]
 pub(crate) struct LineResult<'a> {
     line_num: usize,
     text: &'a [u8],
+
+    /// The absolute byte offset of this line's first byte within
+    /// the input, counted over the bytes the reader consumed
+    /// (i.e. post-transcoding, when a transcoding reader is used).
+    byte_offset: usize,
 }
 
 impl<'a> LineResult<'a> {
-    fn new(text: &'a [u8], line_num: usize) -> Self {
-        Self { line_num, text }
+    fn new(text: &'a [u8], line_num: usize, byte_offset: usize) -> Self {
+        Self {
+            line_num,
+            text,
+            byte_offset,
+        }
     }
 
     pub(crate) fn line_num(&self) -> usize {
@@ -21,11 +36,21 @@ impl<'a> LineResult<'a> {
     pub(crate) fn text(&self) -> &[u8] {
         &self.text
     }
+
+    pub(crate) fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+
+    /// The length of the line in bytes, terminator included.
+    pub(crate) fn len(&self) -> usize {
+        self.text.len()
+    }
 }
 
 pub(crate) struct AsyncLineBufferBuilder {
     line_break_byte: u8,
     start_size_bytes: usize,
+    max_size_bytes: usize,
 }
 
 impl AsyncLineBufferBuilder {
@@ -33,6 +58,7 @@ impl AsyncLineBufferBuilder {
         Self {
             line_break_byte: b'\n',
             start_size_bytes: 8 * (1 << 10),
+            max_size_bytes: DEFAULT_MAX_SIZE_BYTES,
         }
     }
 
@@ -46,11 +72,19 @@ impl AsyncLineBufferBuilder {
         self
     }
 
+    /// Caps how large the buffer may grow; see
+    /// `DEFAULT_MAX_SIZE_BYTES` for the behavior at the cap.
+    pub(crate) fn with_max_size_bytes(mut self, max_size_bytes: usize) -> Self {
+        self.max_size_bytes = max_size_bytes;
+        self
+    }
+
     pub(crate) fn build(self) -> AsyncLineBuffer {
         AsyncLineBuffer {
             // TODO: experiment with "reserved space" instead of pre-allocating
             buffer: vec![0u8; self.start_size_bytes],
             line_break_byte: self.line_break_byte,
+            max_size_bytes: usize::max(self.max_size_bytes, self.start_size_bytes),
             line_break_idxs: VecDeque::new(),
             start: 0,
             end: 0,
@@ -72,6 +106,9 @@ pub(crate) struct AsyncLineBuffer {
     /// The internal buffer.
     buffer: Vec<u8>,
 
+    /// The largest the internal buffer is allowed to grow.
+    max_size_bytes: usize,
+
     /// The single byte representing a newline.
     /// Since strings are utf8, we are expecting
     /// this has a unique single-byte value
@@ -111,10 +148,10 @@ impl AsyncLineBuffer {
     {
         self.ensure_capacity();
 
-        let bytes_count = reader
-            .read(self.writable_buffer_mut())
-            .await
-            .expect("Unable to read from reader.");
+        // A read error mid-stream (a vanished file, an unplugged
+        // device) ends the stream rather than panicking; the
+        // stream simply stops where the readable bytes stopped.
+        let bytes_count = reader.read(self.writable_buffer_mut()).await.unwrap_or(0);
 
         // Keep track of any newlines we inserted
         if bytes_count != 0 {
@@ -139,6 +176,37 @@ impl AsyncLineBuffer {
         bytes_count != 0
     }
 
+    /// Grows the buffer up front to fit an input of the hinted
+    /// size (clamped to the max), so the input can be consumed
+    /// in a single fill instead of a doubling series.
+    pub(crate) fn reserve_for(&mut self, size_hint: usize) {
+        let desired = usize::min(usize::max(size_hint, 1), self.max_size_bytes);
+
+        if desired > self.buffer.len() {
+            self.buffer.resize(desired, 0u8);
+        }
+    }
+
+    /// The current allocated capacity of the buffer.
+    pub(crate) fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Shrinks an (empty) buffer's allocation back down to the
+    /// given size, releasing memory a pathological input forced
+    /// it to grow.
+    pub(crate) fn shrink_to(&mut self, size: usize) {
+        debug_assert!(
+            self.start == 0 && self.end == 0,
+            "Only an empty buffer may be shrunk."
+        );
+
+        if self.buffer.len() > size {
+            self.buffer.truncate(size);
+            self.buffer.shrink_to_fit();
+        }
+    }
+
     /// Refreshes this buffer into a clean state
     /// so it can be used once again.
     pub(crate) fn refresh(&mut self) {
@@ -160,15 +228,25 @@ impl AsyncLineBuffer {
     }
 
     /// Guarantee the writable portion of the buffer has nonzero length,
-    /// expanding the buffer if necessary.
+    /// expanding the buffer if necessary (but never past the max size).
     fn ensure_capacity(&mut self) {
         if !self.writable_buffer().is_empty() {
             return;
         }
 
         let cur_factor = usize::max(1, self.buffer.len());
-        let grow_to = cur_factor * 2;
-        self.buffer.resize(grow_to, 0u8);
+        let grow_to = usize::min(cur_factor * 2, self.max_size_bytes);
+
+        if grow_to > self.buffer.len() {
+            self.buffer.resize(grow_to, 0u8);
+        }
+    }
+
+    /// True when the buffer has grown to its cap and filled it
+    /// without completing a line -- i.e. the current line can
+    /// never fit.
+    fn is_at_max_capacity(&self) -> bool {
+        self.writable_buffer().is_empty() && self.buffer.len() >= self.max_size_bytes
     }
 
     /// Retrieve a slice containing the next line,
@@ -239,6 +317,32 @@ where
     reader: R,
     lines_read: usize,
     is_line_nums_enabled: bool,
+
+    /// Set once a line has overflowed the buffer cap; the rest
+    /// of the input is skipped.
+    overflowed: bool,
+
+    /// The total bytes consumed so far; the byte offset of the
+    /// next line to be returned.
+    bytes_consumed: usize,
+
+    /// How many already-consumed lines to retain in `retained`.
+    /// Zero disables retention entirely.
+    retain_count: usize,
+
+    /// A ring of owned copies of the most recently consumed lines
+    /// (excluding the line most recently returned), oldest first,
+    /// as `(line_num, byte_offset, text)`. Owned copies are
+    /// required: the slices handed out by `read_line` are
+    /// invalidated when `roll_to_front` shifts the buffer's
+    /// contents.
+    retained: VecDeque<(usize, usize, Vec<u8>)>,
+
+    /// The line most recently returned by `read_line`, copied at
+    /// the moment it was consumed (before any later `roll_to_front`
+    /// could invalidate it). It rolls into `retained` when the
+    /// next line is read.
+    pending_retained: Option<(usize, usize, Vec<u8>)>,
 }
 
 impl<R> AsyncLineBufferReader<R>
@@ -251,6 +355,11 @@ where
             line_buffer,
             lines_read: 0,
             is_line_nums_enabled: true,
+            overflowed: false,
+            bytes_consumed: 0,
+            retain_count: 0,
+            retained: VecDeque::new(),
+            pending_retained: None,
         }
     }
 
@@ -259,6 +368,33 @@ where
         self
     }
 
+    /// Retain owned copies of the last `count` consumed lines
+    /// (not counting the line most recently returned), so a caller
+    /// can report "before" context without re-reading the input.
+    pub(crate) fn retain_lines(mut self, count: usize) -> Self {
+        self.retain_count = count;
+        self
+    }
+
+    /// Drains and returns the retained lines preceding the line
+    /// most recently returned by `read_line`, oldest first, as
+    /// `(line_num, byte_offset, text)`. The most recent line
+    /// itself is dropped from retention, since the caller is
+    /// presumably reporting it directly.
+    pub(crate) fn take_retained(&mut self) -> Vec<(usize, usize, Vec<u8>)> {
+        self.pending_retained = None;
+
+        self.retained.drain(..).collect()
+    }
+
+    /// Forgets all retained lines, including the most recent one.
+    /// Useful when a line has already been reported through some
+    /// other channel and must not resurface as "before" context.
+    pub(crate) fn clear_retained(&mut self) {
+        self.pending_retained = None;
+        self.retained.clear();
+    }
+
     pub(crate) fn inner_buf_len(&self) -> usize {
         self.line_buffer.buffer.len()
     }
@@ -267,11 +403,41 @@ where
     /// `Some(Ok(...))` if a line was read and parsed successfully.
     /// `Some(Err(...))` if a line was read but failed to parse.
     pub(crate) async fn read_line<'a>(&'a mut self) -> Option<LineResult<'a>> {
+        if self.overflowed {
+            return None;
+        }
+
+        // The previously returned line is now safely behind us;
+        // roll its copy into the retention ring.
+        if let Some(prev) = self.pending_retained.take() {
+            self.retained.push_back(prev);
+
+            while self.retained.len() > self.retain_count {
+                self.retained.pop_front();
+            }
+        }
+
         self.lines_read += 1;
         let line_num = self.lines_read;
+        let byte_offset = self.bytes_consumed;
 
         while !self.line_buffer.has_line() {
             self.line_buffer.roll_to_front();
+
+            // A line that can't fit even in a fully grown buffer
+            // is pathological (e.g. a multi-GB blob with no line
+            // breaks); skip the rest of this input rather than
+            // growing without limit.
+            if self.line_buffer.is_at_max_capacity() {
+                eprintln!(
+                    "Skipping remainder of input: line exceeds the {}-byte buffer cap.",
+                    self.line_buffer.max_size_bytes
+                );
+                self.overflowed = true;
+
+                return None;
+            }
+
             // There are currently no full lines in the buffer, so fill it up.
             let any_bytes_read = self.line_buffer.fill(&mut self.reader).await;
             if !any_bytes_read {
@@ -279,7 +445,17 @@ where
                 // we need to return it, since it will never get completed.
                 let line = self.line_buffer.consume_remaining();
 
-                return line.map(|l| LineResult::new(l, line_num));
+                if let Some(l) = &line {
+                    self.bytes_consumed += l.len();
+                }
+
+                if self.retain_count > 0 {
+                    if let Some(l) = line {
+                        self.pending_retained = Some((line_num, byte_offset, l.to_vec()));
+                    }
+                }
+
+                return line.map(|l| LineResult::new(l, line_num, byte_offset));
             }
         }
 
@@ -287,7 +463,18 @@ where
         // with at least one full line (which we consume below), or
         // else it has already been completely exhausted.
         let line = self.line_buffer.consume_line();
-        line.map(|l| LineResult::new(l, line_num))
+
+        if let Some(l) = &line {
+            self.bytes_consumed += l.len();
+        }
+
+        if self.retain_count > 0 {
+            if let Some(l) = line {
+                self.pending_retained = Some((line_num, byte_offset, l.to_vec()));
+            }
+        }
+
+        line.map(|l| LineResult::new(l, line_num, byte_offset))
     }
 
     /// Takes the line buffer from this Reader,
@@ -492,6 +679,52 @@ mod test {
         });
     }
 
+    #[test]
+    fn buffer_skips_input_when_line_exceeds_max_size() {
+        let bytes_reader = BufReader::new("0123456789 with no line break".as_bytes());
+
+        let line_buf = AsyncLineBufferBuilder::new()
+            .with_start_size_bytes(2)
+            .with_max_size_bytes(8)
+            .build();
+        let mut reader = AsyncLineBufferReader::new(bytes_reader, line_buf);
+
+        async_std::task::block_on(async {
+            let line = reader.read_line().await;
+            assert!(
+                line.is_none(),
+                "A line that cannot fit in the fully grown buffer should be skipped."
+            );
+
+            let line = reader.read_line().await;
+            assert!(line.is_none(), "The overflow should stick.");
+        });
+    }
+
+    #[test]
+    fn buffer_within_max_size_still_reads_lines() {
+        let bytes_reader = BufReader::new(
+            "tiny
+lines
+"
+            .as_bytes(),
+        );
+
+        let line_buf = AsyncLineBufferBuilder::new()
+            .with_start_size_bytes(2)
+            .with_max_size_bytes(8)
+            .build();
+        let mut reader = AsyncLineBufferReader::new(bytes_reader, line_buf);
+
+        async_std::task::block_on(async {
+            let line = reader.read_line().await;
+            assert_eq!("tiny\n".as_bytes(), line.unwrap().text());
+
+            let line = reader.read_line().await;
+            assert_eq!("lines\n".as_bytes(), line.unwrap().text());
+        });
+    }
+
     #[test]
     fn buffer_macbeth() {
         let macbeth = "
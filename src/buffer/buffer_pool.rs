@@ -1,27 +1,188 @@
 use super::async_line_buffer::{AsyncLineBuffer, AsyncLineBufferBuilder};
 use async_std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Default, Debug)]
+/// How many buffers the pool will hand out in total before
+/// `acquire` starts waiting for returns, bounding the pool's
+/// worst-case memory use to this many buffers at their cap.
+const DEFAULT_MAX_BUFFERS: usize = 64;
+
+/// How many buffers to preallocate when none is configured.
+const DEFAULT_PREALLOC_COUNT: usize = 4;
+
+/// Builds a `BufferPool`, with knobs for the preallocated buffer
+/// count, the initial per-buffer size, and whether buffers shrink
+/// back to that size when returned.
+pub(crate) struct BufferPoolBuilder {
+    line_break_byte: u8,
+    prealloc_count: usize,
+    start_size_bytes: Option<usize>,
+    shrink_on_return: bool,
+}
+
+impl BufferPoolBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            line_break_byte: b'\n',
+            prealloc_count: DEFAULT_PREALLOC_COUNT,
+            start_size_bytes: None,
+            shrink_on_return: false,
+        }
+    }
+
+    /// Split records on the given byte instead of `\n`.
+    pub(crate) fn line_break_byte(mut self, line_break_byte: u8) -> Self {
+        self.line_break_byte = line_break_byte;
+        self
+    }
+
+    /// Preallocate this many buffers (`--buffer-count`).
+    pub(crate) fn prealloc_count(mut self, count: Option<usize>) -> Self {
+        if let Some(count) = count {
+            self.prealloc_count = count;
+        }
+
+        self
+    }
+
+    /// Start each buffer at this many bytes (`--buffer-size`).
+    pub(crate) fn start_size_bytes(mut self, size: Option<usize>) -> Self {
+        self.start_size_bytes = size;
+        self
+    }
+
+    /// Shrink grown buffers back to their starting size when they
+    /// return to the pool (`--buffer-shrink`), trading refill cost
+    /// for a smaller steady-state footprint.
+    pub(crate) fn shrink_on_return(mut self, enabled: bool) -> Self {
+        self.shrink_on_return = enabled;
+        self
+    }
+
+    pub(crate) fn build(self) -> BufferPool {
+        let pool = Mutex::new(
+            (0..self.prealloc_count)
+                .map(|_| Self::new_buffer(self.line_break_byte, self.start_size_bytes))
+                .collect(),
+        );
+
+        BufferPool {
+            pool,
+            line_break_byte: self.line_break_byte,
+            start_size_bytes: self.start_size_bytes,
+            shrink_on_return: self.shrink_on_return,
+            max_buffers: usize::max(DEFAULT_MAX_BUFFERS, self.prealloc_count),
+            created: AtomicUsize::new(self.prealloc_count),
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn new_buffer(line_break_byte: u8, start_size_bytes: Option<usize>) -> AsyncLineBuffer {
+        let mut builder = AsyncLineBufferBuilder::new().with_line_break_byte(line_break_byte);
+
+        if let Some(size) = start_size_bytes {
+            builder = builder.with_start_size_bytes(size);
+        }
+
+        builder.build()
+    }
+}
+
+#[derive(Debug)]
 pub(crate) struct BufferPool {
     pool: Mutex<Vec<AsyncLineBuffer>>,
+
+    /// The byte every buffer in this pool splits records on;
+    /// `\n` unless overridden (`--null-data`/`--line-terminator`).
+    line_break_byte: u8,
+
+    /// The configured initial buffer size, when overridden.
+    start_size_bytes: Option<usize>,
+
+    /// Shrink grown buffers back down when they are returned.
+    shrink_on_return: bool,
+
+    /// The pool's budget: how many buffers may be alive at once.
+    max_buffers: usize,
+
+    /// How many buffers have been created so far.
+    created: AtomicUsize,
+
+    /// The capacity, in bytes, of every buffer currently on loan,
+    /// as measured when each was handed out.
+    live_bytes: AtomicUsize,
+
+    /// The largest `live_bytes` observed so far -- the pool's
+    /// (approximate) peak aggregate memory use. Growth while a
+    /// buffer is out is only seen when it returns.
+    peak_bytes: AtomicUsize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BufferPool {
-    /// Get a buffer, either recycling an old one, or
-    /// generating a fresh one.
-    pub(crate) async fn acquire(&self) -> AsyncLineBuffer {
-        self.try_get_existing()
-            .await
-            .unwrap_or_else(Self::generate_new)
+    /// Get a buffer: recycle an old one, generate a fresh one if
+    /// the budget allows, or wait for another task to return one.
+    ///
+    /// `size_hint` is the expected input length (typically the
+    /// file's size from metadata); the buffer is pre-grown to fit
+    /// it, so small files are read in exactly one fill while huge
+    /// ones stay clamped to the buffer's cap.
+    pub(crate) async fn acquire(&self, size_hint: Option<usize>) -> AsyncLineBuffer {
+        let mut buffer = loop {
+            if let Some(buffer) = self.try_get_existing().await {
+                break buffer;
+            }
+
+            if self.created.fetch_add(1, Ordering::SeqCst) < self.max_buffers {
+                break self.generate_new();
+            }
+
+            // Over budget: undo the reservation and wait for a
+            // buffer to come back to the pool.
+            self.created.fetch_sub(1, Ordering::SeqCst);
+            async_std::task::yield_now().await;
+        };
+
+        if let Some(size_hint) = size_hint {
+            buffer.reserve_for(size_hint);
+        }
+
+        let live = self
+            .live_bytes
+            .fetch_add(buffer.capacity(), Ordering::Relaxed)
+            + buffer.capacity();
+        self.peak_bytes.fetch_max(live, Ordering::Relaxed);
+
+        buffer
     }
 
     pub(crate) fn new() -> BufferPool {
-        let pool = Mutex::new((0..4).map(|_| Self::generate_new()).collect());
-
-        Self { pool }
+        BufferPoolBuilder::new().build()
     }
 
-    pub(crate) async fn return_to_pool(&self, buf: AsyncLineBuffer) {
+    pub(crate) async fn return_to_pool(&self, mut buf: AsyncLineBuffer) {
+        // The buffer may have grown while it was out; fold its
+        // final size into the peak before releasing its share.
+        let live = self.live_bytes.load(Ordering::Relaxed);
+        self.peak_bytes.fetch_max(live, Ordering::Relaxed);
+
+        let _ = self
+            .live_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                Some(live.saturating_sub(buf.capacity()))
+            });
+
+        if self.shrink_on_return {
+            buf.refresh();
+            buf.shrink_to(self.start_size_bytes.unwrap_or_else(default_start_size));
+        }
+
         self.pool.lock().await.push(buf);
     }
 
@@ -29,8 +190,13 @@ impl BufferPool {
         self.pool.lock().await.len()
     }
 
-    fn generate_new() -> AsyncLineBuffer {
-        AsyncLineBufferBuilder::new().build()
+    /// The pool's (approximate) peak aggregate memory, in bytes.
+    pub(crate) fn peak_bytes(&self) -> usize {
+        self.peak_bytes.load(Ordering::Relaxed)
+    }
+
+    fn generate_new(&self) -> AsyncLineBuffer {
+        BufferPoolBuilder::new_buffer(self.line_break_byte, self.start_size_bytes)
     }
 
     async fn try_get_existing(&self) -> Option<AsyncLineBuffer> {
@@ -43,3 +209,8 @@ impl BufferPool {
         })
     }
 }
+
+/// The default starting size of a fresh line buffer.
+fn default_start_size() -> usize {
+    AsyncLineBufferBuilder::new().build().capacity()
+}
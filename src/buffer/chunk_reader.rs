@@ -0,0 +1,241 @@
+use async_std::prelude::*;
+
+/// How many bytes each chunk holds, carried-over overlap included.
+const DEFAULT_CHUNK_SIZE_BYTES: usize = 1 << 20;
+
+/// How many trailing bytes of one chunk reappear at the front of
+/// the next, so a match spanning a chunk boundary (up to this
+/// long) is still seen whole.
+const DEFAULT_OVERLAP_BYTES: usize = 64 * (1 << 10);
+
+/// Reads an input as a series of large, overlapping chunks rather
+/// than lines, for consumers (like multiline search) that want to
+/// run a matcher over a wide window without buffering the whole
+/// input. Each chunk repeats the tail of the previous one, so a
+/// match no longer than the overlap is always seen unsplit in
+/// some chunk.
+#[derive(Debug)]
+pub(crate) struct AsyncChunkReader<R>
+where
+    R: async_std::io::Read + std::marker::Unpin,
+{
+    reader: R,
+    buffer: Vec<u8>,
+    chunk_size_bytes: usize,
+    overlap_bytes: usize,
+
+    /// The absolute byte offset of the current chunk's first byte.
+    chunk_offset: usize,
+
+    /// The (1-based) number of the line containing the current
+    /// chunk's first byte.
+    first_line_num: usize,
+
+    /// How many bytes at the front of the current chunk were
+    /// carried over from the previous one.
+    carried: usize,
+
+    is_first_chunk: bool,
+
+    /// Set once the underlying reader reports end of input.
+    exhausted: bool,
+}
+
+impl<R> AsyncChunkReader<R>
+where
+    R: async_std::io::Read + std::marker::Unpin,
+{
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            chunk_size_bytes: DEFAULT_CHUNK_SIZE_BYTES,
+            overlap_bytes: DEFAULT_OVERLAP_BYTES,
+            chunk_offset: 0,
+            first_line_num: 1,
+            carried: 0,
+            is_first_chunk: true,
+            exhausted: false,
+        }
+    }
+
+    /// Yield chunks of (at most) the given size.
+    pub(crate) fn chunk_size_bytes(mut self, size: usize) -> Self {
+        self.chunk_size_bytes = size;
+        self
+    }
+
+    /// Repeat this many trailing bytes of each chunk at the front
+    /// of the next.
+    pub(crate) fn overlap_bytes(mut self, overlap: usize) -> Self {
+        self.overlap_bytes = overlap;
+        self
+    }
+
+    /// The next chunk, or `None` once the input holds no bytes
+    /// beyond those already seen.
+    pub(crate) async fn read_chunk(&mut self) -> Option<Chunk<'_>> {
+        if self.exhausted {
+            return None;
+        }
+
+        // Equal (or inverted) sizes could never make forward
+        // progress: each chunk would be entirely carried over.
+        assert!(
+            self.chunk_size_bytes > self.overlap_bytes,
+            "Chunk size must exceed the overlap."
+        );
+
+        if self.is_first_chunk {
+            self.is_first_chunk = false;
+        } else {
+            // Retire everything except the overlap tail, advancing
+            // the offset and line bookkeeping past the dropped bytes.
+            let drop_len = self.buffer.len().saturating_sub(self.overlap_bytes);
+
+            self.first_line_num += count_line_breaks(&self.buffer[..drop_len]);
+            self.chunk_offset += drop_len;
+
+            self.buffer.copy_within(drop_len.., 0);
+            self.buffer.truncate(self.buffer.len() - drop_len);
+        }
+
+        self.carried = self.buffer.len();
+
+        // Fill the remainder of the chunk from the reader.
+        let mut filled = self.buffer.len();
+        self.buffer
+            .resize(usize::max(self.chunk_size_bytes, filled), 0u8);
+
+        while filled < self.buffer.len() {
+            // A read error mid-stream ends the stream, exactly as
+            // on the line-wise path.
+            let bytes_count = self
+                .reader
+                .read(&mut self.buffer[filled..])
+                .await
+                .unwrap_or(0);
+
+            if bytes_count == 0 {
+                self.exhausted = true;
+                break;
+            }
+
+            filled += bytes_count;
+        }
+
+        self.buffer.truncate(filled);
+
+        if filled == self.carried {
+            // Nothing beyond the carried-over bytes: every byte
+            // here was already part of the previous chunk.
+            return None;
+        }
+
+        Some(Chunk {
+            bytes: &self.buffer,
+            byte_offset: self.chunk_offset,
+            first_line_num: self.first_line_num,
+            fresh_from: self.carried,
+        })
+    }
+}
+
+/// One overlapping window of the input.
+#[derive(Debug)]
+pub(crate) struct Chunk<'a> {
+    bytes: &'a [u8],
+    byte_offset: usize,
+    first_line_num: usize,
+    fresh_from: usize,
+}
+
+impl<'a> Chunk<'a> {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// The absolute byte offset of this chunk's first byte.
+    pub(crate) fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+
+    /// The index where bytes not seen in any previous chunk begin;
+    /// a match ending before this was already reported against the
+    /// previous chunk.
+    pub(crate) fn fresh_from(&self) -> usize {
+        self.fresh_from
+    }
+
+    /// Recovers the (1-based) line number containing the byte at
+    /// the given index within this chunk.
+    pub(crate) fn line_num_at(&self, idx: usize) -> usize {
+        self.first_line_num + count_line_breaks(&self.bytes[..idx])
+    }
+}
+
+fn count_line_breaks(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == b'\n').count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_input_arrives_as_one_chunk() {
+        async_std::task::block_on(async {
+            let input = b"line one\nline two\n";
+            let mut chunks = AsyncChunkReader::new(&input[..]);
+
+            let chunk = chunks.read_chunk().await.expect("Expected one chunk.");
+            assert_eq!(input, chunk.bytes());
+            assert_eq!(0, chunk.byte_offset());
+            assert_eq!(0, chunk.fresh_from());
+            assert_eq!(1, chunk.line_num_at(0));
+            assert_eq!(2, chunk.line_num_at(input.len() - 1));
+
+            assert!(chunks.read_chunk().await.is_none());
+        });
+    }
+
+    #[test]
+    fn chunks_overlap_and_report_offsets() {
+        async_std::task::block_on(async {
+            let input = b"abcdefghijkl";
+            let mut chunks = AsyncChunkReader::new(&input[..])
+                .chunk_size_bytes(8)
+                .overlap_bytes(4);
+
+            let first: Vec<u8> = chunks.read_chunk().await.unwrap().bytes().to_vec();
+            assert_eq!(b"abcdefgh", &first[..]);
+
+            let second = chunks.read_chunk().await.unwrap();
+            assert_eq!(b"efghijkl", second.bytes());
+            assert_eq!(4, second.byte_offset());
+            assert_eq!(4, second.fresh_from());
+
+            assert!(chunks.read_chunk().await.is_none());
+        });
+    }
+
+    #[test]
+    fn line_numbers_recover_across_chunks() {
+        async_std::task::block_on(async {
+            let input = b"a\nb\nc\nd\ne\nf\n";
+            let mut chunks = AsyncChunkReader::new(&input[..])
+                .chunk_size_bytes(6)
+                .overlap_bytes(2);
+
+            let first = chunks.read_chunk().await.unwrap();
+            assert_eq!(1, first.line_num_at(0));
+            assert_eq!(3, first.line_num_at(4));
+
+            let second = chunks.read_chunk().await.unwrap();
+            // The second chunk starts at line 3 ("c\n").
+            assert_eq!(b"c\nd\ne\n", second.bytes());
+            assert_eq!(3, second.line_num_at(0));
+            assert_eq!(5, second.line_num_at(4));
+        });
+    }
+}
@@ -0,0 +1,368 @@
+//! A transcoding reader layer for UTF-16 input.
+//!
+//! Windows tooling (and .NET in particular) commonly writes logs
+//! as UTF-16 with a byte-order mark. Those files are full of NUL
+//! bytes, so without help the binary check would skip them. The
+//! reader here sniffs the first two bytes: on a UTF-16 BOM it
+//! transparently transcodes the stream to UTF-8 before line
+//! splitting and matching; anything else passes through untouched.
+
+use crate::error::{Error, Result};
+use async_std::io::Read;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// How many bytes to pull from the inner reader per fill.
+const RAW_CHUNK_SIZE: usize = 8 * (1 << 10);
+
+/// The source encoding, determined from the stream's first bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    /// Not yet determined; fewer than two bytes seen so far.
+    Undetected,
+
+    /// No BOM: bytes are passed through unchanged.
+    Passthrough,
+
+    Utf16Le,
+    Utf16Be,
+
+    /// Each byte is its own Unicode scalar value (ISO-8859-1).
+    Latin1,
+}
+
+/// An explicitly requested source encoding (`--encoding`), for
+/// corpora where BOM-based auto-detection is wrong or absent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ForcedEncoding {
+    Utf8,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl ForcedEncoding {
+    /// Resolves an `--encoding` argument to an encoding.
+    /// `Err` with the list of known names on an unknown one.
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "utf-8" | "utf8" => Ok(ForcedEncoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(ForcedEncoding::Latin1),
+            "utf-16le" | "utf16le" => Ok(ForcedEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(ForcedEncoding::Utf16Be),
+            _ => Err(Error::InvalidFlagValue {
+                flag: "--encoding",
+                value: name.to_owned(),
+                expected: "utf-8, latin1, utf-16le, or utf-16be".to_owned(),
+            }),
+        }
+    }
+}
+
+/// Wraps a reader, transcoding UTF-16 (detected by BOM) to UTF-8.
+#[derive(Debug)]
+pub(crate) struct TranscodingReader<R>
+where
+    R: Read + Unpin,
+{
+    inner: R,
+    encoding: Encoding,
+
+    /// Raw bytes read but not yet decodable: the undetected
+    /// prefix, a half code unit, or a held-back high surrogate.
+    pending: Vec<u8>,
+
+    /// Decoded output not yet handed to the caller.
+    decoded: Vec<u8>,
+
+    /// How much of `decoded` the caller has consumed.
+    decoded_pos: usize,
+
+    /// The inner reader reached end-of-stream.
+    inner_done: bool,
+
+    /// A leading BOM has been looked for (and stripped) already.
+    bom_handled: bool,
+}
+
+impl<R> TranscodingReader<R>
+where
+    R: Read + Unpin,
+{
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            encoding: Encoding::Undetected,
+            pending: Vec::new(),
+            decoded: Vec::new(),
+            decoded_pos: 0,
+            inner_done: false,
+            bom_handled: false,
+        }
+    }
+
+    /// A reader decoding from the given encoding unconditionally,
+    /// instead of sniffing for a BOM (`--encoding`). A leading
+    /// BOM matching a forced UTF-16 encoding is still stripped.
+    pub(crate) fn forced(inner: R, encoding: ForcedEncoding) -> Self {
+        let mut reader = Self::new(inner);
+
+        reader.encoding = match encoding {
+            ForcedEncoding::Utf8 => Encoding::Passthrough,
+            ForcedEncoding::Latin1 => Encoding::Latin1,
+            ForcedEncoding::Utf16Le => Encoding::Utf16Le,
+            ForcedEncoding::Utf16Be => Encoding::Utf16Be,
+        };
+
+        reader
+    }
+
+    /// Folds freshly read raw bytes into the decoded buffer,
+    /// detecting the encoding first if necessary.
+    fn process(&mut self, raw: &[u8]) {
+        self.pending.extend_from_slice(raw);
+
+        if self.encoding == Encoding::Undetected {
+            if self.pending.len() < 2 {
+                // Can't sniff a BOM yet; wait for more bytes
+                // (or end-of-stream).
+                return;
+            }
+
+            self.encoding = match (self.pending[0], self.pending[1]) {
+                (0xff, 0xfe) => Encoding::Utf16Le,
+                (0xfe, 0xff) => Encoding::Utf16Be,
+                _ => Encoding::Passthrough,
+            };
+
+            // The BOM itself is consumed, not reported.
+            if self.encoding != Encoding::Passthrough {
+                self.pending.drain(..2);
+            }
+
+            self.bom_handled = true;
+        }
+
+        // A forced UTF-16 stream may still open with a BOM,
+        // which belongs to the encoding, not the content.
+        if !self.bom_handled {
+            if let Some(bom) = match self.encoding {
+                Encoding::Utf16Le => Some([0xff, 0xfe]),
+                Encoding::Utf16Be => Some([0xfe, 0xff]),
+                _ => None,
+            } {
+                if self.pending.len() < 2 {
+                    return;
+                }
+
+                if self.pending[..2] == bom {
+                    self.pending.drain(..2);
+                }
+            }
+
+            self.bom_handled = true;
+        }
+
+        match self.encoding {
+            Encoding::Passthrough => {
+                self.decoded.append(&mut self.pending);
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => self.decode_pending_utf16(),
+            Encoding::Latin1 => {
+                let mut utf8 = [0u8; 4];
+                for &byte in &self.pending {
+                    let c = char::from(byte);
+                    self.decoded
+                        .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                }
+                self.pending.clear();
+            }
+            Encoding::Undetected => unreachable!(),
+        }
+    }
+
+    /// Decodes every complete code unit in `pending` to UTF-8,
+    /// holding back a trailing half unit or high surrogate whose
+    /// other half may arrive with the next read.
+    fn decode_pending_utf16(&mut self) {
+        let mut units: Vec<u16> = self
+            .pending
+            .chunks_exact(2)
+            .map(|pair| {
+                if self.encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                }
+            })
+            .collect();
+
+        let mut held_bytes = self.pending.len() % 2;
+
+        // A trailing high surrogate is the first half of a pair;
+        // hold its bytes until its partner arrives.
+        if let Some(&last) = units.last() {
+            if (0xd800..=0xdbff).contains(&last) {
+                units.pop();
+                held_bytes += 2;
+            }
+        }
+
+        self.pending.drain(..self.pending.len() - held_bytes);
+
+        let mut utf8 = [0u8; 4];
+        for decoded in char::decode_utf16(units.into_iter()) {
+            let c = decoded.unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.decoded
+                .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+        }
+    }
+
+    /// Flushes whatever `pending` still holds at end-of-stream:
+    /// a short undetected prefix passes through, while a
+    /// truncated code unit or unpaired surrogate becomes the
+    /// replacement character.
+    fn finish(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        if self.encoding == Encoding::Undetected || self.encoding == Encoding::Passthrough {
+            self.decoded.append(&mut self.pending);
+            return;
+        }
+
+        self.pending.clear();
+
+        let mut utf8 = [0u8; 4];
+        self.decoded.extend_from_slice(
+            char::REPLACEMENT_CHARACTER
+                .encode_utf8(&mut utf8)
+                .as_bytes(),
+        );
+    }
+}
+
+impl<R> Read for TranscodingReader<R>
+where
+    R: Read + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            // Serve already-decoded output first.
+            if this.decoded_pos < this.decoded.len() {
+                let available = &this.decoded[this.decoded_pos..];
+                let len = usize::min(available.len(), buf.len());
+
+                buf[..len].copy_from_slice(&available[..len]);
+                this.decoded_pos += len;
+
+                if this.decoded_pos == this.decoded.len() {
+                    this.decoded.clear();
+                    this.decoded_pos = 0;
+                }
+
+                return Poll::Ready(Ok(len));
+            }
+
+            if this.inner_done {
+                return Poll::Ready(Ok(0));
+            }
+
+            let mut raw = [0u8; RAW_CHUNK_SIZE];
+
+            match Pin::new(&mut this.inner).poll_read(cx, &mut raw) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(0)) => {
+                    this.inner_done = true;
+                    this.finish();
+                }
+                Poll::Ready(Ok(n)) => this.process(&raw[..n]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_std::prelude::*;
+
+    fn read_all<R: Read + Unpin>(mut reader: TranscodingReader<R>) -> Vec<u8> {
+        async_std::task::block_on(async {
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).await.unwrap();
+            out
+        })
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xff, 0xfe];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn input_without_bom_passes_through() {
+        let reader = TranscodingReader::new("caf\u{e9} latin1".as_bytes());
+
+        assert_eq!("caf\u{e9} latin1".as_bytes(), &read_all(reader)[..]);
+    }
+
+    #[test]
+    fn utf16le_is_transcoded_and_bom_dropped() {
+        let bytes = utf16le("hello\nworld\n");
+        let reader = TranscodingReader::new(&bytes[..]);
+
+        assert_eq!(b"hello\nworld\n".to_vec(), read_all(reader));
+    }
+
+    #[test]
+    fn utf16be_is_transcoded() {
+        let mut bytes = vec![0xfe, 0xff];
+        for unit in "hi".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        let reader = TranscodingReader::new(&bytes[..]);
+
+        assert_eq!(b"hi".to_vec(), read_all(reader));
+    }
+
+    #[test]
+    fn surrogate_pairs_survive_transcoding() {
+        // One code point outside the BMP, encoded as a
+        // surrogate pair.
+        let bytes = utf16le("a\u{1f600}b");
+        let reader = TranscodingReader::new(&bytes[..]);
+
+        assert_eq!("a\u{1f600}b".as_bytes(), &read_all(reader)[..]);
+    }
+
+    #[test]
+    fn forced_latin1_is_decoded() {
+        let reader = TranscodingReader::forced(&b"caf\xe9"[..], ForcedEncoding::Latin1);
+
+        assert_eq!("caf\u{e9}".as_bytes(), &read_all(reader)[..]);
+    }
+
+    #[test]
+    fn forced_utf16le_works_without_a_bom() {
+        let mut bytes = Vec::new();
+        for unit in "no bom".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        let reader = TranscodingReader::forced(&bytes[..], ForcedEncoding::Utf16Le);
+
+        assert_eq!(b"no bom".to_vec(), read_all(reader));
+    }
+}
@@ -0,0 +1,49 @@
+//! Cooperative cancellation, shared by the crawler, the searcher
+//! workers, and the printer. Anything holding a clone of a token
+//! can ask every other holder to stop promptly: `-q` cancels on
+//! the first match, `--timeout` cancels when the clock runs out,
+//! the printer cancels when its output dies, and Ctrl-C cancels
+//! everything at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A cheaply clonable token that lets one part of the search
+/// request that all other in-flight work stop promptly.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// True once `cancel` was called -- or once the process was
+    /// interrupted (Ctrl-C), which cancels every token at once.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || crate::interrupt::was_interrupted()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_same_cancellation() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+
+        assert!(!clone.is_cancelled());
+
+        token.cancel();
+
+        assert!(clone.is_cancelled());
+    }
+}
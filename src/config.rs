@@ -0,0 +1,146 @@
+//! The config file: default flags applied to every run, plus
+//! named profiles for whole workflows (`--profile logs` might
+//! enable `--text --sort modified`, say, while `--profile code`
+//! sticks to source types). The file lives at `~/.toygreprc`
+//! unless `TOYGREP_CONFIG` points somewhere else, and looks like:
+//!
+//! ```text
+//! # always-on defaults
+//! --sort path
+//!
+//! [profile.logs]
+//! --text
+//! --sort modified
+//! ```
+//!
+//! Each non-comment line holds one or more flag tokens, split on
+//! whitespace. The command line always overrides the file.
+
+use std::collections::HashMap;
+
+/// Points at an alternate config file, mostly for tests and
+/// per-project setups.
+const CONFIG_ENV_VAR: &str = "TOYGREP_CONFIG";
+
+/// The config file's name under the home directory.
+const CONFIG_FILE_NAME: &str = ".toygreprc";
+
+/// The parsed config file: the flags outside any section, and the
+/// flags under each `[profile.NAME]` heading.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    defaults: Vec<String>,
+    profiles: HashMap<String, Vec<String>>,
+}
+
+impl Config {
+    /// Loads the config file, or an empty config when there is
+    /// none (no config file is the common case, not an error).
+    pub(crate) fn load() -> Self {
+        config_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    fn parse(contents: &str) -> Self {
+        enum Section {
+            Defaults,
+            Profile(String),
+            Unrecognized,
+        }
+
+        let mut config = Self::default();
+        let mut section = Section::Defaults;
+
+        for line in contents.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                // A section other than `[profile.NAME]` is skipped
+                // wholesale, so a future key doesn't break old
+                // binaries.
+                section = match header.strip_prefix("profile.") {
+                    Some(name) => Section::Profile(name.to_owned()),
+                    None => Section::Unrecognized,
+                };
+
+                continue;
+            }
+
+            let tokens = line.split_whitespace().map(str::to_owned);
+
+            match &section {
+                Section::Defaults => config.defaults.extend(tokens),
+                Section::Profile(name) => config
+                    .profiles
+                    .entry(name.clone())
+                    .or_default()
+                    .extend(tokens),
+                Section::Unrecognized => {}
+            }
+        }
+
+        config
+    }
+
+    /// The flags outside any profile, applied to every run.
+    pub(crate) fn defaults(&self) -> &[String] {
+        &self.defaults
+    }
+
+    /// The flags under `[profile.NAME]`, or `None` when the file
+    /// has no such profile.
+    pub(crate) fn profile(&self, name: &str) -> Option<&[String]> {
+        self.profiles.get(name).map(Vec::as_slice)
+    }
+}
+
+fn config_path() -> Option<std::path::PathBuf> {
+    if let Ok(path) = std::env::var(CONFIG_ENV_VAR) {
+        return Some(path.into());
+    }
+
+    std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn defaults_and_profiles_parse_into_flag_tokens() {
+        let config = Config::parse(
+            "# a comment\n\
+             --sort path\n\
+             \n\
+             [profile.logs]\n\
+             --text --sort modified\n\
+             --timeout 30\n\
+             \n\
+             [profile.code]\n\
+             -t rs\n",
+        );
+
+        let tokens = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(tokens(&["--sort", "path"]), config.defaults());
+        assert_eq!(
+            Some(tokens(&["--text", "--sort", "modified", "--timeout", "30"]).as_slice()),
+            config.profile("logs")
+        );
+        assert_eq!(None, config.profile("missing"));
+    }
+
+    #[test]
+    fn unknown_sections_are_ignored() {
+        let config = Config::parse("[colors]\nmatch:fg:yellow\n[profile.a]\n-i\n");
+
+        assert!(config.defaults().is_empty());
+        assert_eq!(Some(["-i".to_owned()].as_slice()), config.profile("a"));
+    }
+}
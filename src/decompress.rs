@@ -0,0 +1,191 @@
+//! Pluggable decompression for `-z/--search-zip`.
+//!
+//! Decoders are looked up in a registry keyed by file extension,
+//! with a magic-byte check so a mislabeled file is skipped rather
+//! than fed to the wrong decoder. A new format implements
+//! `DecoderFactory` and gets one entry in `DECODER_FACTORIES`;
+//! the searcher itself never changes.
+
+use std::io;
+use std::path::Path;
+
+/// A decompression format the searcher can see through.
+pub(crate) trait DecoderFactory: Send + Sync {
+    /// The file extension this decoder handles (without the dot).
+    fn extension(&self) -> &'static str;
+
+    /// The magic bytes expected at the start of the stream.
+    fn magic(&self) -> &'static [u8];
+
+    /// Decompresses the full stream.
+    fn decode(&self, compressed: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Whether the stream starts with this format's magic bytes.
+    fn sniff(&self, compressed: &[u8]) -> bool {
+        compressed.starts_with(self.magic())
+    }
+}
+
+/// Every known format, one entry per decoder.
+static DECODER_FACTORIES: &[&dyn DecoderFactory] =
+    &[&GzipFactory, &ZstdFactory, &XzFactory, &Bzip2Factory];
+
+/// The decoder registered for the given path's extension, if any.
+pub(crate) fn decoder_for(path: &Path) -> Option<&'static dyn DecoderFactory> {
+    let extension = path.extension()?.to_str()?;
+
+    DECODER_FACTORIES
+        .iter()
+        .find(|factory| factory.extension() == extension)
+        .copied()
+}
+
+/// Gzip, decoded in-process via flate2.
+struct GzipFactory;
+
+impl DecoderFactory for GzipFactory {
+    fn extension(&self) -> &'static str {
+        "gz"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0x1f, 0x8b]
+    }
+
+    fn decode(&self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        use std::io::Read as _;
+
+        let mut decompressed = Vec::new();
+
+        flate2::read::GzDecoder::new(compressed).read_to_end(&mut decompressed)?;
+
+        Ok(decompressed)
+    }
+}
+
+/// Zstandard, decoded by the external `zstd` binary.
+struct ZstdFactory;
+
+impl DecoderFactory for ZstdFactory {
+    fn extension(&self) -> &'static str {
+        "zst"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0x28, 0xb5, 0x2f, 0xfd]
+    }
+
+    fn decode(&self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        decode_via_command("zstd", &["-d", "-c", "-q"], compressed)
+    }
+}
+
+/// Xz, decoded by the external `xz` binary.
+struct XzFactory;
+
+impl DecoderFactory for XzFactory {
+    fn extension(&self) -> &'static str {
+        "xz"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]
+    }
+
+    fn decode(&self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        decode_via_command("xz", &["-d", "-c", "-q"], compressed)
+    }
+}
+
+/// Bzip2, decoded by the external `bzip2` binary.
+struct Bzip2Factory;
+
+impl DecoderFactory for Bzip2Factory {
+    fn extension(&self) -> &'static str {
+        "bz2"
+    }
+
+    fn magic(&self) -> &'static [u8] {
+        b"BZh"
+    }
+
+    fn decode(&self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        decode_via_command("bzip2", &["-d", "-c", "-q"], compressed)
+    }
+}
+
+/// Runs an external decompressor, feeding it the stream on stdin
+/// and collecting its stdout (the approach Ripgrep takes for
+/// formats without a suitable in-process decoder). The stdin
+/// write happens on its own thread so a large output can't
+/// deadlock both pipes.
+fn decode_via_command(command: &str, args: &[&str], compressed: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Write as _;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .expect("Child process stdin was not piped.");
+
+    let input = compressed.to_vec();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{} exited with {}", command, output.status),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decoder_is_found_by_extension() {
+        assert!(decoder_for(Path::new("logs/app.log.gz")).is_some());
+        assert!(decoder_for(Path::new("data.zst")).is_some());
+        assert!(decoder_for(Path::new("plain.txt")).is_none());
+        assert!(decoder_for(Path::new("no_extension")).is_none());
+    }
+
+    #[test]
+    fn sniff_rejects_wrong_magic() {
+        let decoder = decoder_for(Path::new("fake.gz")).unwrap();
+
+        assert!(!decoder.sniff(b"not actually gzip"));
+    }
+
+    #[test]
+    fn gzip_roundtrips() {
+        use std::io::Write as _;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"hello\nworld\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let decoder = decoder_for(Path::new("greeting.gz")).unwrap();
+
+        assert!(decoder.sniff(&compressed));
+        assert_eq!(
+            b"hello\nworld\n".to_vec(),
+            decoder.decode(&compressed).unwrap()
+        );
+    }
+}
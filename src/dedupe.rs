@@ -0,0 +1,168 @@
+//! Duplicate-file suppression. With `-L` a symlink can lead the
+//! walk back to a file it already reported, and overlapping
+//! targets (`dir` and `dir/sub` on one command line) reach the
+//! same files twice; either way the same physical file would be
+//! searched -- and printed -- more than once. A deduper shared
+//! across the whole search remembers every file it has seen and
+//! lets each one through exactly once.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// On Unix a file's identity is its (device, inode) pair, which
+/// survives any number of paths and symlinks pointing at it.
+#[cfg(unix)]
+type FileKey = (u64, u64);
+
+/// Elsewhere (notably Windows) there is no inode to compare, so
+/// the canonicalized path stands in for the file's identity.
+#[cfg(not(unix))]
+type FileKey = std::path::PathBuf;
+
+/// A cheaply clonable set of every physical file seen so far;
+/// clones share the same underlying set.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FileDeduper {
+    seen: Arc<Mutex<HashSet<FileKey>>>,
+}
+
+impl FileDeduper {
+    /// True the first time the physical file behind `path` is
+    /// seen, false on every later sighting. A file whose identity
+    /// cannot be determined is let through rather than dropped.
+    #[cfg(unix)]
+    pub(crate) fn first_visit(&self, _path: &std::path::Path, meta: &std::fs::Metadata) -> bool {
+        match crate::walker::file_id(meta) {
+            Some(id) => self
+                .seen
+                .lock()
+                .expect("Unable to acquire lock.")
+                .insert(id),
+            None => true,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn first_visit(&self, path: &std::path::Path, _meta: &std::fs::Metadata) -> bool {
+        match path.canonicalize() {
+            Ok(canonical) => self
+                .seen
+                .lock()
+                .expect("Unable to acquire lock.")
+                .insert(canonical),
+            Err(_) => true,
+        }
+    }
+}
+
+/// A cheaply clonable registry of the size and content hash of
+/// every file searched so far, behind `--dedupe-contents`: a
+/// file byte-identical to one already searched (a vendored copy,
+/// say) is skipped, no matter where it lives. Clones share the
+/// same registry.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ContentDeduper {
+    seen: Arc<Mutex<HashMap<(u64, u64), String>>>,
+}
+
+impl ContentDeduper {
+    /// Hashes the whole file and records it. Returns the path of
+    /// the previously-seen file with the same size and hash, if
+    /// there is one; a file that cannot be read is let through so
+    /// the search proper can report the error.
+    pub(crate) async fn duplicate_of(&self, path: &std::path::Path, size: u64) -> Option<String> {
+        use async_std::io::ReadExt;
+
+        let mut file = async_std::fs::File::open(path).await.ok()?;
+        let mut chunk = vec![0u8; HASH_CHUNK_BYTES];
+        let mut hash = FNV_OFFSET_BASIS;
+
+        loop {
+            let bytes_read = file.read(&mut chunk).await.ok()?;
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            hash = fnv1a(hash, &chunk[..bytes_read]);
+        }
+
+        let mut seen = self.seen.lock().expect("Unable to acquire lock.");
+
+        match seen.entry((size, hash)) {
+            std::collections::hash_map::Entry::Occupied(original) => Some(original.get().clone()),
+            std::collections::hash_map::Entry::Vacant(slot) => {
+                slot.insert(path.display().to_string());
+
+                None
+            }
+        }
+    }
+}
+
+/// How many bytes the hashing pass reads per buffer fill.
+const HASH_CHUNK_BYTES: usize = 64 * 1024;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Folds `bytes` into a running FNV-1a hash; fast, dependency-free,
+/// and plenty collision-resistant once paired with the file size.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_same_file_is_let_through_exactly_once() {
+        let deduper = FileDeduper::default();
+
+        let path = std::env::temp_dir().join("toygrep_dedupe_test.txt");
+        std::fs::write(&path, "contents").expect("Unable to write test file.");
+        let meta = std::fs::metadata(&path).expect("Unable to stat test file.");
+
+        assert!(deduper.first_visit(&path, &meta));
+        assert!(!deduper.first_visit(&path, &meta));
+
+        // Clones share the same set, so the clone's sighting is
+        // also a repeat.
+        assert!(!deduper.clone().first_visit(&path, &meta));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn identical_contents_are_reported_as_duplicates() {
+        let deduper = ContentDeduper::default();
+
+        let original = std::env::temp_dir().join("toygrep_content_dedupe_a.txt");
+        let copy = std::env::temp_dir().join("toygrep_content_dedupe_b.txt");
+        let different = std::env::temp_dir().join("toygrep_content_dedupe_c.txt");
+        std::fs::write(&original, "same contents").expect("Unable to write test file.");
+        std::fs::write(&copy, "same contents").expect("Unable to write test file.");
+        std::fs::write(&different, "other contents").expect("Unable to write test file.");
+
+        let size = 13;
+
+        async_std::task::block_on(async {
+            assert_eq!(None, deduper.duplicate_of(&original, size).await);
+            assert_eq!(
+                Some(original.display().to_string()),
+                deduper.duplicate_of(&copy, size).await
+            );
+            assert_eq!(None, deduper.duplicate_of(&different, 14).await);
+        });
+
+        std::fs::remove_file(&original).ok();
+        std::fs::remove_file(&copy).ok();
+        std::fs::remove_file(&different).ok();
+    }
+}
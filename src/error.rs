@@ -1,7 +1,105 @@
+use std::fmt;
+
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub(crate) enum Error {
-    Utf8PrintFail(String),
     TargetsNotFound(Vec<String>),
+
+    /// The user-supplied pattern failed to compile.
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+
+    /// A user-supplied `--colors` spec was malformed.
+    InvalidColorSpec {
+        spec: String,
+        reason: String,
+    },
+
+    /// A flag's value named something unknown
+    /// (`--encoding`, `--sort`, `-t`, ...).
+    InvalidFlagValue {
+        flag: &'static str,
+        value: String,
+        expected: String,
+    },
+
+    /// An I/O operation on a specific path failed.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    /// The filesystem walk could not proceed from a path.
+    WalkError {
+        path: String,
+        reason: String,
+    },
+
+    /// Writing formatted results to the output stream failed.
+    PrintError(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TargetsNotFound(targets) => {
+                write!(f, "invalid targets specified: {:?}", targets)
+            }
+            Error::InvalidPattern { pattern, reason } => {
+                write!(f, "invalid pattern '{}': {}", pattern, reason)
+            }
+            Error::InvalidColorSpec { spec, reason } => {
+                write!(f, "invalid color spec '{}': {}", spec, reason)
+            }
+            Error::InvalidFlagValue {
+                flag,
+                value,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "invalid {} value '{}' (expected {})",
+                    flag, value, expected
+                )
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::WalkError { path, reason } => write!(f, "couldn't walk {}: {}", path, reason),
+            Error::PrintError(source) => write!(f, "error writing output: {}", source),
+        }
+    }
+}
+
+impl Error {
+    /// True for a write failure caused by the reader of our output
+    /// going away (e.g. `toygrep ... | head`) -- a normal way for a
+    /// run to end, not worth a message.
+    pub(crate) fn is_broken_pipe(&self) -> bool {
+        match self {
+            Error::Io { source, .. } | Error::PrintError(source) => {
+                source.kind() == std::io::ErrorKind::BrokenPipe
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::PrintError(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A bare `io::Error` nearly always comes from a `write!` into the
+/// output stream, so `?` on those maps here; errors with a path in
+/// hand should build `Error::Io` instead.
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::PrintError(source)
+    }
 }
@@ -0,0 +1,169 @@
+//! The `--explain` mode: for each given path, replays the
+//! walker's filtering decisions one by one — VCS directories,
+//! ignore rules (with the file and line that decided), depth
+//! limits, the type filter — and reports whether the path would
+//! be searched and, if not, exactly which rule excluded it.
+//! Invaluable when a file mysteriously fails to show up in
+//! results and the suspect is three `.gitignore`s up.
+
+use crate::arg_parse::UserInput;
+use crate::ignore::{self, IgnoreStack};
+use crate::types::TypeFilter;
+use std::path::Path;
+
+/// Explains every path argument, returning the process exit code
+/// (2 when any path could not be examined at all).
+pub(crate) fn run(user_input: &UserInput, type_filter: &TypeFilter) -> i32 {
+    let root = match std::env::current_dir() {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("toygrep: unable to access the current directory: {}", e);
+            return 2;
+        }
+    };
+
+    let mut failed = false;
+
+    for target in &user_input.targets {
+        let path: std::path::PathBuf = match target {
+            crate::target::Target::Stdin => {
+                println!("-: searched (stdin is never filtered)");
+                continue;
+            }
+            crate::target::Target::Path(path) => path.clone().into(),
+        };
+
+        match explain_path(&root, &path, user_input, type_filter) {
+            Ok(verdict) => println!("{}: {}", path.display(), verdict),
+            Err(e) => {
+                eprintln!("toygrep: {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        2
+    } else {
+        0
+    }
+}
+
+/// Replays the walk from `root` down to `path`, stopping at the
+/// first rule that excludes it.
+fn explain_path(
+    root: &Path,
+    path: &Path,
+    user_input: &UserInput,
+    type_filter: &TypeFilter,
+) -> std::io::Result<String> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    };
+
+    let meta = std::fs::symlink_metadata(&absolute)?;
+
+    if meta.file_type().is_symlink() && !user_input.follow_symlinks {
+        return Ok("skipped (symlink; searched with --follow)".to_owned());
+    }
+
+    // A path outside the tree below the current directory has no
+    // walk to replay; only the file-level filters apply to it.
+    let relative = absolute.strip_prefix(root).unwrap_or(&absolute);
+
+    let mut ignores = if user_input.no_ignore {
+        IgnoreStack::empty()
+    } else {
+        IgnoreStack::empty().descend(root)
+    };
+
+    let mut current = root.to_path_buf();
+    let components: Vec<_> = relative.components().collect();
+
+    for (index, component) in components.iter().enumerate() {
+        let depth = index + 1;
+        let is_last = depth == components.len();
+        current = current.join(component);
+
+        if !is_last {
+            // The walker's directory-level rules, in its order.
+            if let Some(verdict) =
+                explain_directory(&current, depth, &ignores, user_input, component)
+            {
+                return Ok(verdict);
+            }
+
+            if !user_input.no_ignore {
+                ignores = ignores.descend(&current);
+            }
+
+            continue;
+        }
+
+        if let Some((true, source)) = ignores.explain(&current, meta.is_dir()) {
+            return Ok(format!("skipped (ignored by {})", source));
+        }
+
+        if depth < user_input.min_depth.unwrap_or(0) {
+            return Ok(format!(
+                "skipped (shallower than --min-depth {})",
+                user_input.min_depth.unwrap_or(0)
+            ));
+        }
+
+        if !type_filter.matches(&current) {
+            return Ok("skipped (excluded by the type filter)".to_owned());
+        }
+    }
+
+    Ok(explain_searched(&current, &ignores, &meta))
+}
+
+/// The directory-level exclusions for one ancestor of the path,
+/// or `None` when the walk would descend through it.
+fn explain_directory(
+    current: &Path,
+    depth: usize,
+    ignores: &IgnoreStack,
+    user_input: &UserInput,
+    component: &std::path::Component<'_>,
+) -> Option<String> {
+    let name = component.as_os_str().to_string_lossy();
+
+    if !user_input.no_ignore_vcs && ignore::is_vcs_dir(&name) {
+        return Some(format!(
+            "skipped (inside the VCS directory '{}'; searched with --no-ignore-vcs)",
+            name
+        ));
+    }
+
+    if let Some((true, source)) = ignores.explain(current, true) {
+        return Some(format!(
+            "skipped (parent directory '{}' ignored by {})",
+            name, source
+        ));
+    }
+
+    if let Some(max) = user_input.max_depth {
+        if depth >= max {
+            return Some(format!(
+                "skipped (parent directory '{}' is at --max-depth {})",
+                name, max
+            ));
+        }
+    }
+
+    None
+}
+
+/// The positive verdict, mentioning a `!pattern` re-inclusion
+/// when one was what saved the path.
+fn explain_searched(path: &Path, ignores: &IgnoreStack, meta: &std::fs::Metadata) -> String {
+    if let Some((false, source)) = ignores.explain(path, meta.is_dir()) {
+        return format!("searched (re-included by {})", source);
+    }
+
+    "searched".to_owned()
+}
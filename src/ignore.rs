@@ -0,0 +1,319 @@
+//! Ignore-file handling for directory traversal.
+//!
+//! Each directory may contain `.gitignore`, `.ignore`, and
+//! `.toygrepignore` files (in increasing precedence order) whose
+//! patterns hide matching children from the search. Rules follow
+//! the familiar gitignore shape: one glob per line, `#` comments,
+//! `!pattern` negation, a trailing `/` to match directories only,
+//! and an embedded `/` to anchor the pattern to the directory
+//! containing the ignore file.
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The ignore files consulted in each directory, listed in
+/// increasing precedence order: a pattern in a later file
+/// overrides one in an earlier file.
+const IGNORE_FILENAMES: &[&str] = &[".gitignore", ".ignore", ".toygrepignore"];
+
+/// Version-control bookkeeping directories, skipped during
+/// traversal by default (see `--no-ignore-vcs`).
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// Whether the given directory name belongs to a version-control
+/// system and should be skipped by default.
+pub(crate) fn is_vcs_dir(name: &str) -> bool {
+    VCS_DIRS.contains(&name)
+}
+
+/// One parsed ignore pattern.
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+
+    /// True for `!pattern` rules, which re-include a path
+    /// that an earlier rule excluded.
+    negated: bool,
+
+    /// True for patterns with a trailing `/`,
+    /// which match directories only.
+    dir_only: bool,
+
+    /// Where the rule came from, e.g. `.gitignore:3 'target/'`,
+    /// reported by `--explain`.
+    source: String,
+}
+
+/// The parsed rules from one directory's ignore files.
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// Parses rules from the given lines, in order.
+    /// Malformed or empty lines are skipped.
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Self {
+        let rules = lines
+            .enumerate()
+            .filter_map(|(index, line)| parse_line(line, &format!("line {}", index + 1)))
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Reads and parses every ignore file present in `dir`.
+    pub(crate) fn load(dir: &Path) -> Self {
+        let mut rules = Vec::new();
+
+        for filename in IGNORE_FILENAMES {
+            if let Ok(content) = std::fs::read_to_string(dir.join(filename)) {
+                rules.extend(content.lines().enumerate().filter_map(|(index, line)| {
+                    parse_line(line, &format!("{}:{}", filename, index + 1))
+                }));
+            }
+        }
+
+        Self { rules }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether these rules have an opinion on the given path
+    /// (relative to the directory the rules were loaded from):
+    /// `Some(true)` to ignore it, `Some(false)` to re-include it,
+    /// `None` if no rule matches. The last matching rule wins.
+    fn matched(&self, relative_path: &str, is_dir: bool) -> Option<bool> {
+        self.matched_rule(relative_path, is_dir)
+            .map(|(ignored, _)| ignored)
+    }
+
+    /// Like `matched`, but also names the deciding rule's source,
+    /// for `--explain`.
+    fn matched_rule(&self, relative_path: &str, is_dir: bool) -> Option<(bool, &str)> {
+        let mut decision = None;
+
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+
+            if rule.regex.is_match(relative_path) {
+                decision = Some((!rule.negated, rule.source.as_str()));
+            }
+        }
+
+        decision
+    }
+}
+
+/// The chain of ignore rules inherited from ancestor directories,
+/// consulted outermost-first so that rules in a nested directory
+/// override those above it. Cloning is cheap; levels are shared.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IgnoreStack {
+    levels: Vec<Arc<(PathBuf, IgnoreRules)>>,
+}
+
+impl IgnoreStack {
+    /// A stack with no rules at all, which ignores nothing.
+    pub(crate) fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Returns this stack extended with the rules found in `dir`'s
+    /// ignore files, if it has any.
+    pub(crate) fn descend(&self, dir: &Path) -> Self {
+        let rules = IgnoreRules::load(dir);
+
+        if rules.is_empty() {
+            return self.clone();
+        }
+
+        let mut levels = self.levels.clone();
+        levels.push(Arc::new((dir.to_path_buf(), rules)));
+
+        Self { levels }
+    }
+
+    /// Whether any level of the stack ignores the given path.
+    /// Deeper levels override shallower ones.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut decision = false;
+
+        for level in &self.levels {
+            let (base, rules) = level.as_ref();
+
+            let relative = match path.strip_prefix(base) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+
+            if let Some(matched) = rules.matched(&relative.to_string_lossy(), is_dir) {
+                decision = matched;
+            }
+        }
+
+        decision
+    }
+
+    /// Like `is_ignored`, but reports which rule decided (the
+    /// ignore file's path, line, and pattern), for `--explain`.
+    /// `None` means no rule at any level had an opinion.
+    pub(crate) fn explain(&self, path: &Path, is_dir: bool) -> Option<(bool, String)> {
+        let mut decision = None;
+
+        for level in &self.levels {
+            let (base, rules) = level.as_ref();
+
+            let relative = match path.strip_prefix(base) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+
+            if let Some((ignored, source)) = rules.matched_rule(&relative.to_string_lossy(), is_dir)
+            {
+                decision = Some((ignored, format!("{}/{}", base.display(), source)));
+            }
+        }
+
+        decision
+    }
+}
+
+fn parse_line(line: &str, source: &str) -> Option<Rule> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+
+    // A pattern containing a slash is anchored to the directory
+    // holding the ignore file; otherwise it matches a name at
+    // any depth below it.
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+    let glob = glob_to_regex(pattern);
+
+    let full = if anchored {
+        format!("^{}$", glob)
+    } else {
+        format!("(?:^|/){}$", glob)
+    };
+
+    Regex::new(&full).ok().map(|regex| Rule {
+        regex,
+        negated,
+        dir_only,
+        source: format!("{} '{}'", source, line),
+    })
+}
+
+/// Translates one ignore glob into a regex: `**` crosses directory
+/// separators, `*` and `?` do not, and everything else is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::with_capacity(glob.len() * 2);
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    out.push_str(".*");
+                } else {
+                    out.push_str("[^/]*");
+                }
+            }
+            '?' => out.push_str("[^/]"),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules(lines: &[&str]) -> IgnoreRules {
+        IgnoreRules::parse(lines.iter().copied())
+    }
+
+    #[test]
+    fn plain_name_matches_at_any_depth() {
+        let rules = rules(&["target"]);
+
+        assert_eq!(Some(true), rules.matched("target", true));
+        assert_eq!(Some(true), rules.matched("nested/target", true));
+        assert_eq!(None, rules.matched("target2", true));
+    }
+
+    #[test]
+    fn negation_wins_when_it_comes_last() {
+        let rules = rules(&["*.log", "!keep.log"]);
+
+        assert_eq!(Some(true), rules.matched("debug.log", false));
+        assert_eq!(Some(false), rules.matched("keep.log", false));
+    }
+
+    #[test]
+    fn later_rule_takes_precedence() {
+        let rules = rules(&["!special.tmp", "*.tmp"]);
+
+        // The blanket exclusion comes after the negation,
+        // so it wins.
+        assert_eq!(Some(true), rules.matched("special.tmp", false));
+    }
+
+    #[test]
+    fn trailing_slash_matches_directories_only() {
+        let rules = rules(&["build/"]);
+
+        assert_eq!(Some(true), rules.matched("build", true));
+        assert_eq!(None, rules.matched("build", false));
+    }
+
+    #[test]
+    fn slash_anchors_to_the_rules_directory() {
+        let rules = rules(&["src/*.bak"]);
+
+        assert_eq!(Some(true), rules.matched("src/old.bak", false));
+        assert_eq!(None, rules.matched("other/src/old.bak", false));
+    }
+
+    #[test]
+    fn comments_and_blanks_are_skipped() {
+        let rules = rules(&["# a comment", "", "   "]);
+
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn deeper_stack_level_overrides_shallower() {
+        let outer = Arc::new((PathBuf::from("/repo"), rules(&["*.log"])));
+        let inner = Arc::new((PathBuf::from("/repo/sub"), rules(&["!keep.log"])));
+
+        let stack = IgnoreStack {
+            levels: vec![outer, inner],
+        };
+
+        assert!(stack.is_ignored(Path::new("/repo/other.log"), false));
+        assert!(!stack.is_ignored(Path::new("/repo/sub/keep.log"), false));
+    }
+}
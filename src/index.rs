@@ -0,0 +1,515 @@
+//! The `toygrep index` subcommand and its on-disk trigram index,
+//! in the codesearch lineage: every searchable file under a tree
+//! is read once and each three-byte window of its contents is
+//! recorded in a posting list. A later search whose pattern
+//! contains a plain literal of three bytes or more intersects the
+//! literal's trigram postings and reads only the files that could
+//! possibly match — over a big monorepo, usually a tiny fraction
+//! of the tree.
+//!
+//! The index lives in `.toygrep-index` at the indexed root.
+//! Trigrams are stored (and queried) ASCII-lowercased, so a
+//! case-insensitive search needs no separate index; the real
+//! search of each candidate file still decides every match, so
+//! the index can only over-approximate, never miss — for the
+//! files it knows about. Files created after `toygrep index` ran
+//! are invisible until the index is rebuilt.
+//!
+//! The format, all integers LEB128 varints:
+//!
+//! ```text
+//! "TGIX" <version:u8>
+//! <file_count> then per file: <path_len> <path bytes> <size> <mtime>
+//! <trigram_count> then per trigram:
+//!     <3 bytes> <posting_count> <file ids, delta-encoded>
+//! ```
+
+use crate::arg_parse::UserInput;
+use crate::target::Target;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The index file's name under the indexed root.
+pub(crate) const INDEX_FILE_NAME: &str = ".toygrep-index";
+
+const MAGIC: &[u8] = b"TGIX";
+const VERSION: u8 = 1;
+
+/// How much of a file's head is checked for a NUL byte before
+/// indexing it, mirroring the searcher's binary heuristic.
+const BINARY_CHECK_LEN_BYTES: usize = 512;
+
+/// The trigram index for one tree: the indexed files and, for
+/// each trigram, the ids of the files containing it.
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    files: Vec<IndexedFile>,
+    postings: HashMap<[u8; 3], Vec<u32>>,
+}
+
+/// One indexed file, with enough metadata to notice staleness.
+#[derive(Debug, PartialEq)]
+struct IndexedFile {
+    /// The file's path relative to the indexed root, so the index
+    /// survives the tree being moved.
+    path: String,
+    size: u64,
+    mtime: u64,
+}
+
+/// Runs `toygrep index [PATH]`, returning the process exit code.
+pub(crate) async fn run(args: &[String]) -> i32 {
+    let root = match args {
+        [] => PathBuf::from("."),
+        [path] => PathBuf::from(path),
+        _ => {
+            eprintln!("toygrep: usage: toygrep index [PATH]");
+            return 2;
+        }
+    };
+
+    if !root.is_dir() {
+        eprintln!("toygrep: {}: not a directory", root.display());
+        return 2;
+    }
+
+    let files = crate::replace::collect_files(&[root.clone().into()]).await;
+
+    let mut index = Index::default();
+    let mut bytes_indexed = 0_u64;
+
+    for path in &files {
+        let std_path: PathBuf = path.clone().into();
+
+        let relative = std_path
+            .strip_prefix(&root)
+            .unwrap_or(&std_path)
+            .to_string_lossy()
+            .into_owned();
+
+        let meta = match std::fs::metadata(&std_path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                eprintln!("toygrep: {}: {}", std_path.display(), e);
+                continue;
+            }
+        };
+
+        let contents = match std::fs::read(&std_path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("toygrep: {}: {}", std_path.display(), e);
+                continue;
+            }
+        };
+
+        if contents[..usize::min(contents.len(), BINARY_CHECK_LEN_BYTES)].contains(&0) {
+            continue;
+        }
+
+        bytes_indexed += contents.len() as u64;
+        index.add(relative, &meta, &contents);
+    }
+
+    let index_path = root.join(INDEX_FILE_NAME);
+
+    if let Err(e) = index.save(&index_path) {
+        eprintln!("toygrep: {}: {}", index_path.display(), e);
+        return 2;
+    }
+
+    println!(
+        "indexed {} files ({} bytes, {} trigrams) into {}",
+        index.files.len(),
+        bytes_indexed,
+        index.postings.len(),
+        index_path.display()
+    );
+
+    0
+}
+
+impl Index {
+    /// Adds one file's contents to the index.
+    fn add(&mut self, path: String, meta: &std::fs::Metadata, contents: &[u8]) {
+        let file_id = self.files.len() as u32;
+
+        self.files.push(IndexedFile {
+            path,
+            size: meta.len(),
+            mtime: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs()),
+        });
+
+        for window in contents.windows(3) {
+            let trigram = lowercased([window[0], window[1], window[2]]);
+            let postings = self.postings.entry(trigram).or_default();
+
+            // Windows arrive in file order, so a repeat trigram in
+            // the same file is always the list's current tail.
+            if postings.last() != Some(&file_id) {
+                postings.push(file_id);
+            }
+        }
+    }
+
+    /// The indexed files that could contain the literal: those
+    /// whose contents held every one of its trigrams. `None` when
+    /// the literal is too short to have a trigram, in which case
+    /// the index cannot prune anything.
+    pub(crate) fn candidates(&self, literal: &[u8]) -> Option<Vec<&str>> {
+        if literal.len() < 3 {
+            return None;
+        }
+
+        let mut ids: Option<Vec<u32>> = None;
+
+        for window in literal.windows(3) {
+            let trigram = lowercased([window[0], window[1], window[2]]);
+            let postings: &[u32] = self.postings.get(&trigram).map_or(&[], |p| p);
+
+            ids = Some(match ids {
+                None => postings.to_vec(),
+                Some(ids) => intersect(&ids, postings),
+            });
+
+            if ids.as_ref().is_some_and(Vec::is_empty) {
+                break;
+            }
+        }
+
+        Some(
+            ids.unwrap_or_default()
+                .into_iter()
+                .map(|id| self.files[id as usize].path.as_str())
+                .collect(),
+        )
+    }
+
+    /// Writes the index to the given path.
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut out = Vec::new();
+
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+
+        write_varint(&mut out, self.files.len() as u64);
+
+        for file in &self.files {
+            write_varint(&mut out, file.path.len() as u64);
+            out.extend_from_slice(file.path.as_bytes());
+            write_varint(&mut out, file.size);
+            write_varint(&mut out, file.mtime);
+        }
+
+        // A sorted trigram section makes the output reproducible.
+        let mut trigrams: Vec<_> = self.postings.keys().copied().collect();
+        trigrams.sort_unstable();
+
+        write_varint(&mut out, trigrams.len() as u64);
+
+        for trigram in trigrams {
+            out.extend_from_slice(&trigram);
+
+            let postings = &self.postings[&trigram];
+            write_varint(&mut out, postings.len() as u64);
+
+            let mut previous = 0;
+
+            for &id in postings {
+                write_varint(&mut out, u64::from(id - previous));
+                previous = id;
+            }
+        }
+
+        std::fs::File::create(path)?.write_all(&out)
+    }
+
+    /// Reads an index back from the given path.
+    pub(crate) fn load(path: &Path) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::decode(&bytes).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed index file")
+        })
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC || reader.take(1)? != [VERSION] {
+            return None;
+        }
+
+        let file_count = reader.varint()?;
+        let mut files = Vec::with_capacity(file_count as usize);
+
+        for _ in 0..file_count {
+            let path_len = reader.varint()? as usize;
+            let path = String::from_utf8(reader.take(path_len)?.to_vec()).ok()?;
+            let size = reader.varint()?;
+            let mtime = reader.varint()?;
+
+            files.push(IndexedFile { path, size, mtime });
+        }
+
+        let trigram_count = reader.varint()?;
+        let mut postings = HashMap::with_capacity(trigram_count as usize);
+
+        for _ in 0..trigram_count {
+            let trigram = reader.take(3)?;
+            let trigram = [trigram[0], trigram[1], trigram[2]];
+
+            let posting_count = reader.varint()?;
+            let mut ids = Vec::with_capacity(posting_count as usize);
+            let mut previous = 0_u32;
+
+            for _ in 0..posting_count {
+                let delta = reader.varint()?;
+
+                if delta > u64::from(u32::MAX) {
+                    return None;
+                }
+
+                previous = previous.checked_add(delta as u32)?;
+                ids.push(previous);
+            }
+
+            postings.insert(trigram, ids);
+        }
+
+        Some(Self { files, postings })
+    }
+}
+
+/// Narrows directory targets through their trees' indexes, when
+/// the search can use one: the pattern must contain a plain
+/// literal, and no traversal-filter flag may be in effect (an
+/// explicit file target bypasses those, so narrowing would too).
+/// `None` leaves the target list untouched.
+pub(crate) fn narrow_targets(user_input: &UserInput) -> Option<Vec<Target>> {
+    let literal = query_literal(user_input)?;
+
+    let traversal_filters_active = !user_input.types.is_empty()
+        || !user_input.type_nots.is_empty()
+        || user_input.path_regex.is_some()
+        || user_input.max_depth.is_some()
+        || user_input.min_depth.is_some()
+        || user_input.files_only;
+
+    if traversal_filters_active {
+        return None;
+    }
+
+    let mut narrowed = Vec::new();
+    let mut used_index = false;
+
+    for target in &user_input.targets {
+        let root: PathBuf = match target {
+            Target::Path(path) => path.clone().into(),
+            Target::Stdin => {
+                narrowed.push(Target::Stdin);
+                continue;
+            }
+        };
+
+        let index_path = root.join(INDEX_FILE_NAME);
+
+        if !root.is_dir() || !index_path.is_file() {
+            narrowed.push(target.clone());
+            continue;
+        }
+
+        let index = match Index::load(&index_path) {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("toygrep: {}: {}", index_path.display(), e);
+                narrowed.push(target.clone());
+                continue;
+            }
+        };
+
+        match index.candidates(literal.as_bytes()) {
+            // Too short a literal to prune; search the tree as if
+            // there were no index.
+            None => narrowed.push(target.clone()),
+            Some(candidates) => {
+                used_index = true;
+
+                // A candidate deleted since indexing would only
+                // produce a read error; skip it quietly.
+                narrowed.extend(
+                    candidates
+                        .into_iter()
+                        .map(|relative| root.join(relative))
+                        .filter(|path| path.is_file())
+                        .map(|path| Target::for_path(path.into())),
+                );
+            }
+        }
+    }
+
+    if used_index {
+        Some(narrowed)
+    } else {
+        None
+    }
+}
+
+/// The pattern as a literal the index can break into trigrams:
+/// the fixed string itself, or a regex with no metacharacters in
+/// it. Anything fancier gives up on pruning rather than risk a
+/// wrong trigram set.
+fn query_literal(user_input: &UserInput) -> Option<String> {
+    if !user_input.patterns.is_empty() || !user_input.all_of.is_empty() {
+        return None;
+    }
+
+    let pattern = &user_input.search_pattern;
+
+    if pattern.is_empty() {
+        return None;
+    }
+
+    if user_input.fixed_strings || !pattern.bytes().any(|b| br".*+?()[]{}|^$\".contains(&b)) {
+        Some(pattern.clone())
+    } else {
+        None
+    }
+}
+
+fn lowercased(trigram: [u8; 3]) -> [u8; 3] {
+    [
+        trigram[0].to_ascii_lowercase(),
+        trigram[1].to_ascii_lowercase(),
+        trigram[2].to_ascii_lowercase(),
+    ]
+}
+
+/// The ids present in both sorted lists.
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+/// A cursor over the serialized index.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos + len)?;
+        self.pos += len;
+
+        Some(slice)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0_u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn meta() -> std::fs::Metadata {
+        std::fs::metadata(std::env::temp_dir()).unwrap()
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+
+            let mut reader = Reader {
+                bytes: &out,
+                pos: 0,
+            };
+
+            assert_eq!(Some(value), reader.varint());
+            assert_eq!(out.len(), reader.pos);
+        }
+    }
+
+    #[test]
+    fn candidates_need_every_trigram_of_the_literal() {
+        let mut index = Index::default();
+
+        index.add("a.txt".to_owned(), &meta(), b"the quick brown fox");
+        index.add("b.txt".to_owned(), &meta(), b"the slow brown dog");
+        index.add("c.txt".to_owned(), &meta(), b"unrelated");
+
+        assert_eq!(Some(vec!["a.txt", "b.txt"]), index.candidates(b"brown"));
+        assert_eq!(Some(vec!["a.txt"]), index.candidates(b"QUICK"));
+        assert_eq!(Some(Vec::new()), index.candidates(b"missing"));
+
+        // Too short to prune with.
+        assert_eq!(None, index.candidates(b"ox"));
+    }
+
+    #[test]
+    fn an_index_survives_a_save_and_load() {
+        let mut index = Index::default();
+
+        index.add("src/lib.rs".to_owned(), &meta(), b"pub fn f() {}");
+        index.add("README.md".to_owned(), &meta(), b"# toygrep");
+
+        let path = std::env::temp_dir().join(format!("toygrep-index-{}", std::process::id()));
+
+        index.save(&path).unwrap();
+        let loaded = Index::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(index.files, loaded.files);
+        assert_eq!(index.postings, loaded.postings);
+        assert_eq!(Some(vec!["src/lib.rs"]), loaded.candidates(b"pub fn"));
+    }
+}
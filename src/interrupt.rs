@@ -0,0 +1,28 @@
+//! Ctrl-C handling: a SIGINT flips a global flag that the cancel
+//! tokens observe, so in-flight searches wind down cooperatively
+//! and the printer still flushes whatever it had buffered (grouped
+//! results, summaries) before the process exits with status 130.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the Ctrl-C handler. The handler only flips the flag;
+/// the graceful wind-down happens on the ordinary code paths that
+/// poll `was_interrupted`.
+pub(crate) fn install() {
+    // Failure to install (e.g. an exotic platform) just means
+    // Ctrl-C kills the process the default way.
+    let _ = ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Whether a Ctrl-C has arrived since `install`.
+pub(crate) fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// The conventional exit status for a run ended by SIGINT
+/// (128 + the signal number).
+pub(crate) const INTERRUPT_EXIT_CODE: i32 = 130;
@@ -14,20 +14,41 @@
     future_incompatible
 )]
 
+mod archive;
 mod arg_parse;
 mod buffer;
+mod cancel;
+mod config;
+mod decompress;
+mod dedupe;
 mod error;
+mod explain;
+mod ignore;
+mod index;
+mod interrupt;
 mod matcher;
 mod print;
+mod repl;
+mod replace;
 mod search;
+mod serve;
 mod target;
 mod time_log;
+mod timing;
+mod types;
+mod ui;
+mod walker;
+mod walker_worker;
 
+use crate::arg_parse::ColorMode;
+use crate::cancel::CancelToken;
 use crate::error::Error;
-use crate::print::Printer;
+use crate::print::{BufferMode, ColorConfig, Printer};
 use crate::search::stats::ReadStats;
-use crate::search::SearcherBuilder;
+use crate::search::{ContextLines, SearcherBuilder};
 use crate::time_log::TimeLog;
+use crate::timing::TimingCollector;
+use crate::types::TypeFilter;
 use matcher::DummyMatcher;
 use matcher::RegexMatcherBuilder;
 use std::clone::Clone;
@@ -35,59 +56,310 @@ use std::time::Instant;
 
 #[async_std::main]
 async fn main() {
-    let user_input = arg_parse::capture_input(std::env::args());
+    // `toygrep replace ...` is a separate write path with its own
+    // small flag set; everything else goes through the searcher.
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("replace") {
+        std::process::exit(replace::run(&args[2..]).await);
+    }
+
+    if args.get(1).map(String::as_str) == Some("index") {
+        std::process::exit(index::run(&args[2..]).await);
+    }
+
+    let mut user_input = arg_parse::capture_input(args.into_iter());
+
+    // A directory target indexed by `toygrep index` narrows to
+    // the index's candidate files when the pattern allows it.
+    if let Some(narrowed) = index::narrow_targets(&user_input) {
+        user_input.targets = narrowed;
+    }
+
+    if user_input.serve {
+        std::process::exit(serve::run().await);
+    }
+
+    // From here on a Ctrl-C winds the search down cooperatively
+    // (and still flushes buffered output) instead of killing us.
+    interrupt::install();
 
     let mut time_log = TimeLog::new(Instant::now());
 
-    if user_input.search_pattern.is_empty() {
+    // One collector shared by the searcher and the printer, so
+    // the `--stats` phase breakdown covers both sides.
+    let timing = TimingCollector::new();
+
+    if user_input.search_pattern.is_empty()
+        && user_input.patterns.is_empty()
+        && user_input.all_of.is_empty()
+        && !user_input.files_only
+        && !user_input.repl
+        && !user_input.serve
+        && !user_input.explain
+    {
         arg_parse::print_help();
         return;
     }
 
     let matcher = RegexMatcherBuilder::new()
         .for_pattern(&user_input.search_pattern)
-        .case_insensitive(user_input.case_insensitive)
+        .for_patterns(&user_input.patterns)
+        .all_of(&user_input.all_of)
+        .none_of(&user_input.none_of)
+        .case_insensitive(user_input.is_case_insensitive())
         .match_whole_word(user_input.whole_word)
-        .build();
+        .match_whole_line(user_input.whole_line)
+        .fixed_string(user_input.fixed_strings)
+        .engine(user_input.engine)
+        .build()
+        .unwrap_or_else(|e| {
+            if let Error::InvalidPattern { pattern, reason } = e {
+                eprintln!("toygrep: invalid pattern '{}': {}", pattern, reason);
+            } else {
+                eprintln!("toygrep: {}", e);
+            }
+
+            // Like grep, exit status 2 signals an error
+            // (as opposed to 1 for "no matches found").
+            std::process::exit(2);
+        });
     // let matcher = DummyMatcher;
 
+    // Invalid flag values exit 2 with a one-line message, like
+    // every other usage error.
+    let flag_error = |e: Error| -> ! {
+        eprintln!("toygrep: {}", e);
+        std::process::exit(2);
+    };
+
+    let type_filter = TypeFilter::from_names(&user_input.types, &user_input.type_nots)
+        .unwrap_or_else(|e| flag_error(e));
+
+    if user_input.explain {
+        std::process::exit(explain::run(&user_input, &type_filter));
+    }
+
+    // The `--path-regex` matcher runs against paths during the
+    // walk, independent of the content pattern's flags.
+    let path_matcher = user_input.path_regex.as_deref().map(|pattern| {
+        RegexMatcherBuilder::new()
+            .for_pattern(pattern)
+            .build()
+            .unwrap_or_else(|e| {
+                if let Error::InvalidPattern { pattern, reason } = e {
+                    eprintln!("toygrep: invalid path regex '{}': {}", pattern, reason);
+                } else {
+                    eprintln!("toygrep: {:?}", e);
+                }
+
+                std::process::exit(2);
+            })
+    });
+
+    let sort_key = user_input
+        .sort
+        .as_deref()
+        .map(|name| search::SortKey::from_name(name).unwrap_or_else(|e| flag_error(e)));
+    let encoding = user_input.encoding.as_deref().map(|name| {
+        buffer::transcode::ForcedEncoding::from_name(name).unwrap_or_else(|e| flag_error(e))
+    });
+
+    // `ColorChoice::Auto` on its own still emits escape sequences
+    // into pipes, so only use it when stdout really is a terminal.
+    let color_choice = match user_input.color {
+        ColorMode::Always => termcolor::ColorChoice::Always,
+        ColorMode::Never => termcolor::ColorChoice::Never,
+        ColorMode::Auto => {
+            if atty::is(atty::Stream::Stdout) {
+                termcolor::ColorChoice::Auto
+            } else {
+                termcolor::ColorChoice::Never
+            }
+        }
+    };
+
+    let color_config = ColorConfig::from_specs(&user_input.color_specs).unwrap_or_else(|e| {
+        eprintln!("toygrep: {}", e);
+        std::process::exit(2);
+    });
+
+    if user_input.repl {
+        let code = repl::run(
+            &user_input,
+            type_filter,
+            color_choice,
+            color_config,
+            encoding,
+        )
+        .await;
+
+        std::process::exit(code);
+    }
+
+    // One token shared by the crawler, the searchers, and the
+    // printer: whoever cancels first (`-q`'s match, `--timeout`,
+    // a dead output, Ctrl-C) stops the rest promptly.
+    let cancel_token = CancelToken::new();
+
+    if let Some(secs) = user_input.timeout {
+        let token = cancel_token.clone();
+
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_secs(secs as u64));
+            token.cancel();
+        });
+    }
+
     let print_builder = {
         let first_target = user_input.targets.first();
 
         let print_immediately =
             user_input.targets.len() == 1 && first_target.unwrap().is_file().await;
 
-        let group_by_target = user_input.targets.len() > 1
-            || (first_target.is_some() && first_target.unwrap().is_dir().await);
+        // `-H`/`-h` override the inference from the target list.
+        let group_by_target = match user_input.filename {
+            Some(with_filename) => with_filename,
+            None => {
+                user_input.targets.len() > 1
+                    || (first_target.is_some() && first_target.unwrap().is_dir().await)
+            }
+        };
+
+        let buffer_mode = if user_input.block_buffered {
+            BufferMode::Block
+        } else {
+            BufferMode::Line
+        };
 
         Printer::new()
             .with_matcher(matcher.clone())
+            .buffer_mode(buffer_mode)
             .group_by_target(group_by_target)
             .print_immediately(print_immediately)
+            .context_separators(user_input.before_context + user_input.after_context > 0)
+            .count_only(user_input.count_only)
+            .files_with_matches_only(user_input.files_with_matches)
+            .json_output(user_input.json)
+            .markdown_output(user_input.markdown)
+            .quickfix(user_input.quickfix.clone())
+            .hyperlink_format(user_input.hyperlink_format.clone())
+            .align(user_input.align)
+            .trim(user_input.trim)
+            .group_by_dir(user_input.group_by_dir)
+            .timing(timing.clone())
+            .cancel_token(cancel_token.clone())
+            .sequenced(user_input.ordered)
+            .max_columns(user_input.max_columns)
+            .byte_offset(user_input.byte_offset)
+            .heading_match_counts(user_input.heading_counts)
+            .context_separator(user_input.context_separator.clone())
+            .suppress_context_separator(user_input.no_context_separator)
+            .group_separator(user_input.group_separator.clone())
+            .suppress_group_separator(user_input.no_group_separator)
+            .field_match_separator(user_input.field_match_separator.clone())
+            .field_context_separator(user_input.field_context_separator.clone())
+            .color_choice(color_choice)
+            .color_config(color_config)
+            .replace_template(
+                user_input
+                    .replace_template
+                    .as_ref()
+                    .map(|t| t.as_bytes().to_vec()),
+            )
+    };
+
+    // The configuration every output mode shares; each branch
+    // below adds only its own deltas on top.
+    let setup = SearcherSetup {
+        context_lines: ContextLines {
+            before: user_input.before_context,
+            after: user_input.after_context,
+        },
+        cancel_token: cancel_token.clone(),
+        type_filter,
+        path_matcher,
+        encoding,
+        sort_key,
+        timing: timing.clone(),
     };
 
     // Perform the search, walking the filesystem, detecting matches,
     // and sending them to the printer (note, even after `search` has
     // terminated, the printer thread is likely still processing
     // the results sent to it).
+    let mut print_time_log = None;
     let status = {
-        // TODO: consider using dyn instead of branching
-        if user_input.quiet {
+        if user_input.tui {
+            // The browser wants the whole result set up front, so
+            // the search runs against a collecting sink and the
+            // terminal UI opens once it finishes.
+            let printer = ui::CollectingSender::new();
+            let searcher = setup
+                .configure(SearcherBuilder::new(matcher, printer.clone()), &user_input)
+                .build();
+            let result = run_search(&searcher, &user_input).await;
+
+            drop(searcher);
+
+            if let Err(e) = ui::browse(printer.into_messages()) {
+                eprintln!("toygrep: {}", e);
+                std::process::exit(2);
+            }
+
+            result
+        } else if user_input.stats_only {
+            // Results go to the null printer, so the timings and
+            // counters measure matching and IO alone.
+            let printer = print_builder.make_null();
+            let searcher = setup
+                .configure(SearcherBuilder::new(matcher, printer), &user_input)
+                .build();
+            run_search(&searcher, &user_input).await
+        } else if user_input.quiet {
+            // In quiet mode, only the exit status matters:
+            // cancel everything the moment any line matches.
             let printer = print_builder.make_null();
-            let searcher = SearcherBuilder::new(matcher, printer).build();
-            searcher.search(&user_input.targets).await
+            let searcher = setup
+                .configure(SearcherBuilder::new(matcher, printer), &user_input)
+                .quit_after_first_match(true)
+                .list_files_only(user_input.files_only)
+                .build();
+            let status = run_search(&searcher, &user_input).await;
+
+            // A Ctrl-C also cancels the shared token, so it has
+            // to be checked first: an interrupted run reports the
+            // interrupt, not a match.
+            if interrupt::was_interrupted() {
+                std::process::exit(interrupt::INTERRUPT_EXIT_CODE);
+            }
+
+            // Like grep -q: status 0 if anything matched, 1
+            // otherwise. Matching is judged from the search's own
+            // counters -- the cancel token also trips on
+            // `--timeout`, which must not claim a match.
+            let matched = matches!(&status, Ok(stats) if stats.lines_matched_count > 0);
+            std::process::exit(if matched { 0 } else { 1 })
         } else if user_input.synchronous_printer {
             let printer = print_builder.build_blocking();
-            let searcher = SearcherBuilder::new(matcher, printer).build();
-            searcher.search(&user_input.targets).await
+            let searcher = setup
+                .configure(SearcherBuilder::new(matcher, printer), &user_input)
+                .stop_after_first_match(user_input.files_with_matches)
+                .list_files_only(user_input.files_only)
+                .build();
+            run_search(&searcher, &user_input).await
         } else {
             let (printer, join_handle) = print_builder.spawn_threaded();
-            let searcher = SearcherBuilder::new(matcher, printer).build();
-            let result = searcher.search(&user_input.targets).await;
+            let searcher = setup
+                .configure(SearcherBuilder::new(matcher, printer), &user_input)
+                .stop_after_first_match(user_input.files_with_matches)
+                .list_files_only(user_input.files_only)
+                .build();
+            let result = run_search(&searcher, &user_input).await;
 
             drop(searcher);
 
-            join_handle.join().expect("Couldn't join printing thread.");
+            print_time_log = Some(join_handle.join().expect("Couldn't join printing thread."));
 
             result
         }
@@ -95,70 +367,352 @@ async fn main() {
 
     time_log.log_search_duration();
 
-    // The printer thread stays alive as long as any channel senders exist.
-    // At this point, we've queued up all our searches, so now we must wait
-    // for them to complete, send the results to the printer, and drop their
-    // respective senders.
-    // let print_time_log = printer_handle
-    //     .join()
-    //     .expect("Failed to join the printer thread.");
-    // time_log.print_duration = print_time_log.print_duration;
-    // time_log.printer_spawn_to_print = print_time_log.printer_spawn_to_print;
-    // time_log.first_result_to_first_print = print_time_log.first_result_to_first_print;
+    // The printer thread has been joined by now (in the threaded
+    // branch), so its timings can be merged into the report.
+    if let Some(print_time_log) = print_time_log {
+        time_log.print_duration = print_time_log.print_duration;
+        time_log.printer_spawn_to_print = print_time_log.printer_spawn_to_print;
+        time_log.first_result_to_first_print = print_time_log.first_result_to_first_print;
+    }
+
+    // Files and directories that could not be read were skipped
+    // during the search; report them once at the end, unless
+    // suppressed.
+    if let Ok(stats) = &status {
+        if !user_input.no_messages {
+            for (path, reason) in &stats.read_errors {
+                eprintln!("toygrep: {}: {}", path, reason);
+            }
+        }
+    }
+
+    // Content-dedupe skips are diagnostics, not errors: only
+    // `--verbose` runs hear about them.
+    if let Ok(stats) = &status {
+        if user_input.verbose {
+            for (duplicate, original) in &stats.content_duplicates {
+                eprintln!("toygrep: {}: duplicate of {}", duplicate, original);
+            }
+        }
+    }
 
     if let Err(Error::TargetsNotFound(targets)) = &status {
         eprintln!("\nInvalid targets specified: {:?}", targets);
+    } else if let Err(e) = &status {
+        eprintln!("toygrep: {}", e);
     }
 
     time_log.log_start_die_duration();
-    if user_input.stats && status.is_ok() {
-        let stats = status.unwrap();
-        println!("{}", format_stats(&stats, &time_log));
+    if let (Some(count), Ok(stats)) = (user_input.stats_files, &status) {
+        println!("{}", format_slowest_files(stats, count));
+    }
+
+    if user_input.stats_by_type {
+        if let Ok(stats) = &status {
+            println!("{}", format_stats_by_type(stats));
+        }
+    }
+
+    if (user_input.stats || user_input.stats_json || user_input.stats_only) && status.is_ok() {
+        let report = StatsReport::new(status.as_ref().unwrap(), &time_log);
+
+        if user_input.stats_json {
+            println!("{}", report.to_json());
+        } else {
+            println!("{}", report.to_text());
+            println!("\n{}", timing.format_breakdown());
+        }
+    }
+
+    // Everything above still ran on an interrupt, so whatever was
+    // found (and buffered) before the Ctrl-C has been flushed.
+    if interrupt::was_interrupted() {
+        std::process::exit(interrupt::INTERRUPT_EXIT_CODE);
+    }
+
+    // Like grep, exit status 2 signals an error (bad targets,
+    // failed reads of an explicit target, ...).
+    if status.is_err() {
+        std::process::exit(2);
     }
 }
 
-fn format_stats(read_stats: &ReadStats, time_log: &TimeLog) -> String {
-    format!(
-        "\n{} total files visited
-{} skipped (non-utf8) files
-{} total bytes checked for non-utf8 detection
+/// The searcher configuration pieces computed once in `main` and
+/// shared by every output mode, so each mode's branch states only
+/// its own deltas (quiet's early cancel, `-l`'s stop-per-file,
+/// ...) instead of repeating the whole builder chain.
+struct SearcherSetup {
+    context_lines: ContextLines,
+    cancel_token: CancelToken,
+    type_filter: TypeFilter,
+    path_matcher: Option<matcher::AnyMatcher>,
+    encoding: Option<buffer::transcode::ForcedEncoding>,
+    sort_key: Option<search::SortKey>,
+    timing: TimingCollector,
+}
+
+impl SearcherSetup {
+    /// Applies the shared configuration to the given builder.
+    fn configure<M, P>(
+        &self,
+        builder: SearcherBuilder<M, P>,
+        user_input: &arg_parse::UserInput,
+    ) -> SearcherBuilder<M, P>
+    where
+        M: matcher::Matcher + Sync,
+        P: print::PrinterSender + Sync,
+    {
+        builder
+            .context_lines(self.context_lines)
+            .max_match_count(user_input.max_count)
+            .multiline(user_input.multiline)
+            .cancel_token(self.cancel_token.clone())
+            .process_ignore_files(!user_input.no_ignore)
+            .type_filter(self.type_filter.clone())
+            .path_matcher(self.path_matcher.clone())
+            .max_depth(user_input.max_depth)
+            .min_depth(user_input.min_depth)
+            .follow_symlinks(user_input.follow_symlinks)
+            .skip_vcs_dirs(!user_input.no_ignore_vcs)
+            .thread_count(user_input.threads)
+            .max_open_files(user_input.max_open_files)
+            .search_compressed(user_input.search_zip)
+            .search_archives(user_input.search_archives)
+            .dedupe_contents(user_input.dedupe_contents)
+            .force_text(user_input.text)
+            .encoding(self.encoding)
+            .line_terminator(user_input.line_terminator.unwrap_or(b'\n'))
+            .buffer_count(user_input.buffer_count)
+            .buffer_size(user_input.buffer_size)
+            .buffer_shrink(user_input.buffer_shrink)
+            .stats_files(user_input.stats_files)
+            .stats_by_type(user_input.stats_by_type)
+            .timing(self.timing.clone())
+            .sort_by(self.sort_key)
+            .sort_reverse(user_input.sort_reverse)
+    }
+}
+
+/// The stats report assembled at the end of a run, combining the
+/// searcher's `ReadStats` counters with the `TimeLog` phase
+/// timings; renderable as text (`--stats`) or JSON
+/// (`--stats-json`).
+struct StatsReport {
+    total_files_visited: usize,
+    directories_visited: usize,
+    skipped_files_binary: usize,
+    files_skipped_by_ignore: usize,
+    total_bytes_searched: usize,
+    binary_bytes_checked: usize,
+    lines_matched_count: usize,
+    lines_matched_bytes: usize,
+    max_buffer_size: usize,
+    buffers_created: usize,
+    peak_pool_bytes: usize,
+    text_allocations: usize,
+    read_errors: usize,
+    files_deduped: usize,
+    content_duplicates: usize,
+    filesystem_walk_secs: f32,
+    start_die_secs: Option<f32>,
+    search_secs: Option<f32>,
+    printer_spawn_to_print_secs: Option<f32>,
+    first_result_to_first_print_secs: Option<f32>,
+    print_secs: Option<f32>,
+}
+
+impl StatsReport {
+    fn new(read_stats: &ReadStats, time_log: &TimeLog) -> Self {
+        let secs = |duration: Option<std::time::Duration>| duration.map(|d| d.as_secs_f32());
+
+        Self {
+            total_files_visited: read_stats.total_files_visited,
+            directories_visited: read_stats.directories_visited,
+            skipped_files_binary: read_stats.skipped_files_binary,
+            files_skipped_by_ignore: read_stats.files_skipped_by_ignore,
+            total_bytes_searched: read_stats.total_bytes_searched,
+            binary_bytes_checked: read_stats.binary_bytes_checked,
+            lines_matched_count: read_stats.lines_matched_count,
+            lines_matched_bytes: read_stats.lines_matched_bytes,
+            max_buffer_size: read_stats.max_buffer_size,
+            buffers_created: read_stats.buffers_created,
+            peak_pool_bytes: read_stats.peak_pool_bytes,
+            text_allocations: read_stats.text_allocations,
+            read_errors: read_stats.read_errors.len(),
+            files_deduped: read_stats.files_deduped,
+            content_duplicates: read_stats.content_duplicates.len(),
+            filesystem_walk_secs: read_stats.filesystem_walk_dur.as_secs_f32(),
+            start_die_secs: secs(time_log.start_die_duration),
+            search_secs: secs(time_log.search_duration),
+            printer_spawn_to_print_secs: secs(time_log.printer_spawn_to_print),
+            first_result_to_first_print_secs: secs(time_log.first_result_to_first_print),
+            print_secs: secs(time_log.print_duration),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let measured = |secs: Option<f32>| {
+            secs.map(|s| s.to_string())
+                .unwrap_or_else(|| "(not measured)".into())
+        };
+
+        format!(
+            "\n{} total files visited
+{} directories visited
+{} skipped binary files
+{} files skipped by ignore rules
+{} total bytes searched
+{} total bytes checked for binary detection
 {} matching lines found
 {} total bytes in matching lines
 {max_buf_size} maximum buffer size (bytes)
 {buffers_created} buffers created
+{peak_pool_bytes} peak buffer pool bytes
+{text_allocations} print payload allocations
+{read_errors} files or directories unreadable
+{files_deduped} duplicate files skipped
+{content_duplicates} duplicate-content files skipped
 {startstop} seconds start-to-stop
 {filesystem} seconds recursing through filesystem
 {search} seconds searching
 {printidle} seconds until first result arrives at printer 
 {printprint} seconds between first result arriving and first printing
 {printing} seconds printing",
-        read_stats.total_files_visited,
-        read_stats.skipped_files_non_utf8,
-        read_stats.non_utf8_bytes_checked,
-        read_stats.lines_matched_count,
-        read_stats.lines_matched_bytes,
-        startstop = time_log
-            .start_die_duration
-            .map(|d| d.as_secs_f32().to_string())
-            .unwrap_or_else(|| "(not measured)".into()),
-        filesystem = read_stats.filesystem_walk_dur.as_secs_f32(),
-        search = time_log
-            .search_duration
-            .map(|d| d.as_secs_f32().to_string())
-            .unwrap_or_else(|| "(not measured)".into()),
-        printidle = time_log
-            .printer_spawn_to_print
-            .map(|d| d.as_secs_f32().to_string())
-            .unwrap_or_else(|| "(not measured)".into()),
-        printprint = time_log
-            .first_result_to_first_print
-            .map(|d| d.as_secs_f32().to_string())
-            .unwrap_or_else(|| "(not measured)".into()),
-        printing = time_log
-            .print_duration
-            .map(|d| d.as_secs_f32().to_string())
-            .unwrap_or_else(|| "(not measured)".into()),
-        max_buf_size = read_stats.max_buffer_size,
-        buffers_created = read_stats.buffers_created,
-    )
+            self.total_files_visited,
+            self.directories_visited,
+            self.skipped_files_binary,
+            self.files_skipped_by_ignore,
+            self.total_bytes_searched,
+            self.binary_bytes_checked,
+            self.lines_matched_count,
+            self.lines_matched_bytes,
+            startstop = measured(self.start_die_secs),
+            filesystem = self.filesystem_walk_secs,
+            search = measured(self.search_secs),
+            printidle = measured(self.printer_spawn_to_print_secs),
+            printprint = measured(self.first_result_to_first_print_secs),
+            printing = measured(self.print_secs),
+            max_buf_size = self.max_buffer_size,
+            buffers_created = self.buffers_created,
+            peak_pool_bytes = self.peak_pool_bytes,
+            text_allocations = self.text_allocations,
+            read_errors = self.read_errors,
+            files_deduped = self.files_deduped,
+            content_duplicates = self.content_duplicates,
+        )
+    }
+
+    /// One JSON object; durations not measured in this run are
+    /// emitted as `null`.
+    fn to_json(&self) -> String {
+        let json_secs =
+            |secs: Option<f32>| secs.map(|s| s.to_string()).unwrap_or_else(|| "null".into());
+
+        format!(
+            concat!(
+                "{{",
+                r#""total_files_visited":{},"#,
+                r#""directories_visited":{},"#,
+                r#""skipped_files_binary":{},"#,
+                r#""files_skipped_by_ignore":{},"#,
+                r#""total_bytes_searched":{},"#,
+                r#""binary_bytes_checked":{},"#,
+                r#""lines_matched_count":{},"#,
+                r#""lines_matched_bytes":{},"#,
+                r#""max_buffer_size":{},"#,
+                r#""buffers_created":{},"#,
+                r#""peak_pool_bytes":{},"#,
+                r#""text_allocations":{},"#,
+                r#""read_errors":{},"#,
+                r#""files_deduped":{},"#,
+                r#""content_duplicates":{},"#,
+                r#""filesystem_walk_secs":{},"#,
+                r#""start_die_secs":{},"#,
+                r#""search_secs":{},"#,
+                r#""printer_spawn_to_print_secs":{},"#,
+                r#""first_result_to_first_print_secs":{},"#,
+                r#""print_secs":{}"#,
+                "}}",
+            ),
+            self.total_files_visited,
+            self.directories_visited,
+            self.skipped_files_binary,
+            self.files_skipped_by_ignore,
+            self.total_bytes_searched,
+            self.binary_bytes_checked,
+            self.lines_matched_count,
+            self.lines_matched_bytes,
+            self.max_buffer_size,
+            self.buffers_created,
+            self.peak_pool_bytes,
+            self.text_allocations,
+            self.read_errors,
+            self.files_deduped,
+            self.content_duplicates,
+            self.filesystem_walk_secs,
+            json_secs(self.start_die_secs),
+            json_secs(self.search_secs),
+            json_secs(self.printer_spawn_to_print_secs),
+            json_secs(self.first_result_to_first_print_secs),
+            json_secs(self.print_secs),
+        )
+    }
+}
+
+/// Renders the `--stats-files N` report: the N searched files
+/// that took the longest, slowest first, with their sizes.
+fn format_slowest_files(read_stats: &ReadStats, count: usize) -> String {
+    let mut timings = read_stats.file_timings.clone();
+    timings.sort_by(|a, b| b.search_dur.cmp(&a.search_dur));
+    timings.truncate(count);
+
+    let mut out = format!("\nSlowest {} files searched:", timings.len());
+
+    for timing in timings {
+        out.push_str(&format!(
+            "\n{:.6} seconds  {:>12} bytes  {}",
+            timing.search_dur.as_secs_f32(),
+            timing.bytes,
+            timing.path
+        ));
+    }
+
+    out
+}
+
+/// Renders the `--stats-by-type` report: what each file
+/// extension contributed, most matches first.
+fn format_stats_by_type(read_stats: &ReadStats) -> String {
+    let mut by_type: Vec<_> = read_stats.by_type.iter().collect();
+    by_type.sort_by(|a, b| b.1.matches.cmp(&a.1.matches).then_with(|| a.0.cmp(b.0)));
+
+    let mut out = "\nStats by file type:".to_owned();
+
+    for (extension, type_stats) in by_type {
+        out.push_str(&format!(
+            "\n{:<12} {:>6} files  {:>8} matches  {:>12} bytes",
+            extension, type_stats.files, type_stats.matches, type_stats.bytes
+        ));
+    }
+
+    out
+}
+
+/// Runs the search for the parsed invocation: either over the
+/// explicit file list from `--files-from`, or over the targets.
+async fn run_search<M, P>(
+    searcher: &search::Searcher<M, P>,
+    user_input: &arg_parse::UserInput,
+) -> Result<ReadStats, Error>
+where
+    M: matcher::Matcher + Sync + 'static,
+    P: print::PrinterSender + Sync + 'static,
+{
+    if let Some(source) = &user_input.files_from {
+        searcher
+            .search_files_from(source, user_input.files_from_nul)
+            .await
+    } else {
+        searcher.search(&user_input.targets).await
+    }
 }
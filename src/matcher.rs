@@ -1,4 +1,24 @@
-use regex::bytes::{Regex, RegexBuilder};
+use crate::error::{Error, Result};
+use aho_corasick::AhoCorasick;
+use memchr::memmem;
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+/// Which regex engine should execute the pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Engine {
+    /// Use the default engine, falling back to the fancy engine
+    /// if the pattern requires features it does not support
+    /// (lookaround, backreferences).
+    Auto,
+    Default,
+    Fancy,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::Auto
+    }
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct Match {
@@ -6,11 +26,55 @@ pub(crate) struct Match {
     pub(crate) stop: usize,
 }
 
+/// The full extent of one match plus the spans of its capture
+/// groups, all relative to the start of the searched bytes.
+#[derive(Debug, Clone)]
+pub(crate) struct Submatch {
+    pub(crate) span: Match,
+
+    /// One entry per capture group (the implicit group 0 excluded);
+    /// `None` for groups that did not participate in the match.
+    pub(crate) captures: Vec<Option<Match>>,
+}
+
 /// A trait that promises to answer a simple question:
 /// does the given slice of bytes match a specific pattern?
 pub(crate) trait Matcher: Clone + Send {
     fn is_match(&self, bytes: &[u8]) -> bool;
-    fn find_matches(&self, bytes: &[u8]) -> Vec<Match>;
+
+    /// Invokes `on_match` once per match, in increasing order of
+    /// non-overlapping spans, without allocating per call.
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match));
+
+    /// Collects every match into a `Vec`, for callers that need
+    /// the spans as a list. Prefer `for_each_match` on hot paths.
+    fn find_matches(&self, bytes: &[u8]) -> Vec<Match> {
+        let mut matches = Vec::new();
+
+        self.for_each_match(bytes, &mut |m| matches.push(m));
+
+        matches
+    }
+
+    /// Like `find_matches`, but each span also carries the byte
+    /// ranges of its capture groups, for implementations that
+    /// support captures. The default reports no captures.
+    fn find_submatches(&self, bytes: &[u8]) -> Vec<Submatch> {
+        let mut submatches = Vec::new();
+
+        self.for_each_match(bytes, &mut |m| {
+            submatches.push(Submatch {
+                span: m,
+                captures: Vec::new(),
+            })
+        });
+
+        submatches
+    }
+
+    /// Replace every match in `bytes` with the given template,
+    /// expanding capture references like `$1` and `${name}`.
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8>;
 }
 
 /// A stub of a Matcher that never finds a match.
@@ -21,36 +85,447 @@ impl Matcher for DummyMatcher {
     fn is_match(&self, _bytes: &[u8]) -> bool {
         false
     }
-    fn find_matches(&self, _bytes: &[u8]) -> Vec<Match> {
-        Vec::new()
+    fn for_each_match(&self, _bytes: &[u8], _on_match: &mut dyn FnMut(Match)) {}
+
+    fn replace_all(&self, bytes: &[u8], _template: &[u8]) -> Vec<u8> {
+        bytes.to_vec()
+    }
+}
+
+/// A literal that must appear in any haystack the full pattern
+/// can match, used to cheaply reject non-matching lines with
+/// a substring search before invoking the regex engine.
+#[derive(Debug, Clone)]
+pub(crate) struct Prefilter {
+    finder: memmem::Finder<'static>,
+}
+
+impl Prefilter {
+    /// Extracts a required literal from the given pattern, if a
+    /// sufficiently long one exists. Deliberately conservative:
+    /// any pattern with alternation, groups, or classes gets no
+    /// prefilter, since a literal inside those may not be required.
+    fn for_pattern(pattern: &str, case_insensitive: bool) -> Option<Self> {
+        let literal = extract_required_literal(pattern)?;
+
+        // The substring search is case-sensitive, so an insensitive
+        // pattern can only be prefiltered on a caseless literal.
+        if case_insensitive && literal.iter().any(u8::is_ascii_alphabetic) {
+            return None;
+        }
+
+        Some(Self {
+            finder: memmem::Finder::new(&literal).into_owned(),
+        })
+    }
+
+    fn rejects(&self, bytes: &[u8]) -> bool {
+        self.finder.find(bytes).is_none()
+    }
+}
+
+/// Finds the longest run of literal bytes that every match of the
+/// pattern must contain, or `None` if no run of useful length exists.
+fn extract_required_literal(pattern: &str) -> Option<Vec<u8>> {
+    // Alternation, groups, and classes can make any literal optional.
+    if pattern.contains(['|', '(', '[']) {
+        return None;
+    }
+
+    let mut longest = Vec::new();
+    let mut current = Vec::new();
+    let mut chars = pattern.chars().peekable();
+
+    let mut take_longest = |current: &mut Vec<u8>| {
+        if current.len() > longest.len() {
+            std::mem::swap(&mut longest, current);
+        }
+        current.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                // An escape sequence is not a literal byte we track;
+                // it also guards the char after it from this loop.
+                take_longest(&mut current);
+                chars.next();
+            }
+            '?' | '*' | '{' => {
+                // A quantifier makes the preceding char optional
+                // (or repeatable), so it cannot be required.
+                current.pop();
+                take_longest(&mut current);
+            }
+            '^' | '$' | '.' | '+' | ')' | ']' | '}' => {
+                take_longest(&mut current);
+            }
+            c => {
+                let mut buf = [0u8; 4];
+                current.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+
+    take_longest(&mut current);
+
+    // Below a few bytes, the substring search rejects too little
+    // of the haystack to be worth the extra pass.
+    if longest.len() >= 3 {
+        Some(longest)
+    } else {
+        None
     }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct RegexMatcher {
     regex: Regex,
+    prefilter: Option<Prefilter>,
 }
 
 impl Matcher for RegexMatcher {
     fn is_match(&self, bytes: &[u8]) -> bool {
+        if let Some(prefilter) = &self.prefilter {
+            if prefilter.rejects(bytes) {
+                return false;
+            }
+        }
+
         self.regex.is_match(bytes)
     }
 
-    fn find_matches(&self, bytes: &[u8]) -> Vec<Match> {
-        self.regex
-            .find_iter(bytes)
-            .map(|m| Match {
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        if let Some(prefilter) = &self.prefilter {
+            if prefilter.rejects(bytes) {
+                return;
+            }
+        }
+
+        for m in self.regex.find_iter(bytes) {
+            on_match(Match {
                 start: m.start(),
                 stop: m.end(),
+            });
+        }
+    }
+
+    fn find_submatches(&self, bytes: &[u8]) -> Vec<Submatch> {
+        if let Some(prefilter) = &self.prefilter {
+            if prefilter.rejects(bytes) {
+                return Vec::new();
+            }
+        }
+
+        self.regex
+            .captures_iter(bytes)
+            .map(|caps| {
+                let whole = caps.get(0).expect("Capture group 0 always participates.");
+
+                Submatch {
+                    span: Match {
+                        start: whole.start(),
+                        stop: whole.end(),
+                    },
+                    captures: caps
+                        .iter()
+                        .skip(1)
+                        .map(|group| {
+                            group.map(|g| Match {
+                                start: g.start(),
+                                stop: g.end(),
+                            })
+                        })
+                        .collect(),
+                }
             })
             .collect()
     }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        self.regex.replace_all(bytes, template).into_owned()
+    }
+}
+
+impl RegexMatcher {
+    fn with_prefilter(mut self, prefilter: Option<Prefilter>) -> Self {
+        self.prefilter = prefilter;
+        self
+    }
+}
+
+/// A `Matcher` backed by whichever engine the builder selected.
+#[derive(Debug, Clone)]
+pub(crate) enum AnyMatcher {
+    Default(RegexMatcher),
+    Set(RegexSetMatcher),
+    Literals(AhoCorasickMatcher),
+    Query(QueryMatcher),
+
+    #[cfg(feature = "fancy")]
+    Fancy(FancyRegexMatcher),
+}
+
+impl Matcher for AnyMatcher {
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        match self {
+            AnyMatcher::Default(m) => m.is_match(bytes),
+            AnyMatcher::Set(m) => m.is_match(bytes),
+            AnyMatcher::Literals(m) => m.is_match(bytes),
+            AnyMatcher::Query(m) => m.is_match(bytes),
+
+            #[cfg(feature = "fancy")]
+            AnyMatcher::Fancy(m) => m.is_match(bytes),
+        }
+    }
+
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        match self {
+            AnyMatcher::Default(m) => m.for_each_match(bytes, on_match),
+            AnyMatcher::Set(m) => m.for_each_match(bytes, on_match),
+            AnyMatcher::Literals(m) => m.for_each_match(bytes, on_match),
+            AnyMatcher::Query(m) => m.for_each_match(bytes, on_match),
+
+            #[cfg(feature = "fancy")]
+            AnyMatcher::Fancy(m) => m.for_each_match(bytes, on_match),
+        }
+    }
+
+    fn find_submatches(&self, bytes: &[u8]) -> Vec<Submatch> {
+        match self {
+            AnyMatcher::Default(m) => m.find_submatches(bytes),
+            AnyMatcher::Set(m) => m.find_submatches(bytes),
+            AnyMatcher::Literals(m) => m.find_submatches(bytes),
+            AnyMatcher::Query(m) => m.find_submatches(bytes),
+
+            #[cfg(feature = "fancy")]
+            AnyMatcher::Fancy(m) => m.find_submatches(bytes),
+        }
+    }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        match self {
+            AnyMatcher::Default(m) => m.replace_all(bytes, template),
+            AnyMatcher::Set(m) => m.replace_all(bytes, template),
+            AnyMatcher::Literals(m) => m.replace_all(bytes, template),
+            AnyMatcher::Query(m) => m.replace_all(bytes, template),
+
+            #[cfg(feature = "fancy")]
+            AnyMatcher::Fancy(m) => m.replace_all(bytes, template),
+        }
+    }
+}
+
+/// A `Matcher` for multiple patterns, using a `RegexSet`
+/// for the cheap per-line is-a-match check, and only running
+/// the individual regexes for span extraction on lines that match.
+/// Much faster than one giant alternation for big pattern lists.
+#[derive(Debug, Clone)]
+pub(crate) struct RegexSetMatcher {
+    set: RegexSet,
+    regexes: Vec<Regex>,
+}
+
+impl Matcher for RegexSetMatcher {
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        self.set.is_match(bytes)
+    }
+
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        // Merging the per-pattern spans into increasing order
+        // requires buffering them; the set matcher is the one
+        // implementation that cannot walk matches allocation-free.
+        let mut matches = Vec::new();
+
+        for idx in self.set.matches(bytes) {
+            for m in self.regexes[idx].find_iter(bytes) {
+                matches.push(Match {
+                    start: m.start(),
+                    stop: m.end(),
+                });
+            }
+        }
+
+        merge_spans(&mut matches);
+
+        for m in matches {
+            on_match(m);
+        }
+    }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        // Each matching pattern's replacement is applied in turn.
+        let mut replaced = bytes.to_vec();
+
+        for idx in self.set.matches(bytes) {
+            replaced = self.regexes[idx]
+                .replace_all(&replaced, template)
+                .into_owned();
+        }
+
+        replaced
+    }
+}
+
+/// A `Matcher` for a set of literal (fixed-string) patterns,
+/// backed by an Aho-Corasick automaton, which searches for every
+/// literal in a single pass. Selected by the builder when `-F` is
+/// combined with multiple patterns.
+#[derive(Debug, Clone)]
+pub(crate) struct AhoCorasickMatcher {
+    automaton: AhoCorasick,
+}
+
+impl Matcher for AhoCorasickMatcher {
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        self.automaton.is_match(bytes)
+    }
+
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        // Leftmost-longest semantics, so the spans arrive ordered
+        // and non-overlapping, just as the printer expects.
+        for m in self.automaton.find_iter(bytes) {
+            on_match(Match {
+                start: m.start(),
+                stop: m.end(),
+            });
+        }
+    }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        // Literal patterns have no captures, so every pattern
+        // is replaced by the template verbatim.
+        let replacements = vec![template; self.automaton.patterns_len()];
+
+        self.automaton.replace_all_bytes(bytes, &replacements)
+    }
+}
+
+/// A composite `Matcher` evaluating a boolean query per line:
+/// the line must match the base pattern (when present), match
+/// every `all_of` matcher, and match no `none_of` matcher.
+/// Highlight spans from the base and `all_of` matchers are merged.
+#[derive(Debug, Clone)]
+pub(crate) struct QueryMatcher {
+    base: Option<Box<AnyMatcher>>,
+    all_of: Vec<AnyMatcher>,
+    none_of: Vec<AnyMatcher>,
+}
+
+impl Matcher for QueryMatcher {
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        self.base.iter().all(|m| m.is_match(bytes))
+            && self.all_of.iter().all(|m| m.is_match(bytes))
+            && !self.none_of.iter().any(|m| m.is_match(bytes))
+    }
+
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        if !self.is_match(bytes) {
+            return;
+        }
+
+        let mut matches = Vec::new();
+
+        for matcher in self.base.iter().map(Box::as_ref).chain(&self.all_of) {
+            matcher.for_each_match(bytes, &mut |m| matches.push(m));
+        }
+
+        merge_spans(&mut matches);
+
+        for m in matches {
+            on_match(m);
+        }
+    }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        let mut replaced = bytes.to_vec();
+
+        for matcher in self.base.iter().map(Box::as_ref).chain(&self.all_of) {
+            replaced = matcher.replace_all(&replaced, template);
+        }
+
+        replaced
+    }
+}
+
+/// Sorts spans and merges any that overlap, so consumers
+/// (like the colorizing printer) see them in increasing,
+/// non-overlapping order.
+fn merge_spans(spans: &mut Vec<Match>) {
+    spans.sort_by_key(|m| (m.start, m.stop));
+
+    let mut merged: Vec<Match> = Vec::with_capacity(spans.len());
+
+    for span in spans.drain(..) {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.stop => {
+                last.stop = usize::max(last.stop, span.stop);
+            }
+            _ => merged.push(span),
+        }
+    }
+
+    *spans = merged;
+}
+
+/// A `Matcher` backed by a backtracking engine,
+/// for patterns using lookaround or backreferences,
+/// which the default engine rejects.
+#[cfg(feature = "fancy")]
+#[derive(Debug, Clone)]
+pub(crate) struct FancyRegexMatcher {
+    regex: fancy_regex::Regex,
+}
+
+#[cfg(feature = "fancy")]
+impl Matcher for FancyRegexMatcher {
+    fn is_match(&self, bytes: &[u8]) -> bool {
+        // The backtracking engine operates on str, not bytes;
+        // non-utf8 input simply does not match.
+        std::str::from_utf8(bytes)
+            .map(|text| self.regex.is_match(text).unwrap_or(false))
+            .unwrap_or(false)
+    }
+
+    fn for_each_match(&self, bytes: &[u8], on_match: &mut dyn FnMut(Match)) {
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(_) => return,
+        };
+
+        for m in self.regex.find_iter(text).flatten() {
+            on_match(Match {
+                start: m.start(),
+                stop: m.end(),
+            });
+        }
+    }
+
+    fn replace_all(&self, bytes: &[u8], template: &[u8]) -> Vec<u8> {
+        let text = match std::str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(_) => return bytes.to_vec(),
+        };
+
+        let template = String::from_utf8_lossy(template);
+
+        self.regex
+            .replace_all(text, template.as_ref())
+            .into_owned()
+            .into_bytes()
+    }
 }
 
 pub(crate) struct RegexMatcherBuilder<'a> {
     pattern: &'a str,
+    patterns: &'a [String],
+    all_of: &'a [String],
+    none_of: &'a [String],
     is_case_insensitive: bool,
     match_whole_word: bool,
+    match_whole_line: bool,
+    is_fixed_string: bool,
+    engine: Engine,
 }
 
 impl<'a> RegexMatcherBuilder<'a> {
@@ -58,7 +533,13 @@ impl<'a> RegexMatcherBuilder<'a> {
         Self {
             is_case_insensitive: true,
             match_whole_word: false,
+            match_whole_line: false,
+            is_fixed_string: false,
+            engine: Engine::Auto,
             pattern: "",
+            patterns: &[],
+            all_of: &[],
+            none_of: &[],
         }
     }
 
@@ -67,6 +548,25 @@ impl<'a> RegexMatcherBuilder<'a> {
         self
     }
 
+    /// Match any of the given patterns. When more than one is supplied,
+    /// the result is a set-based matcher rather than one big alternation.
+    pub(crate) fn for_patterns(mut self, patterns: &'a [String]) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Require every line to also match each of these patterns.
+    pub(crate) fn all_of(mut self, patterns: &'a [String]) -> Self {
+        self.all_of = patterns;
+        self
+    }
+
+    /// Exclude any line matching one of these patterns.
+    pub(crate) fn none_of(mut self, patterns: &'a [String]) -> Self {
+        self.none_of = patterns;
+        self
+    }
+
     pub(crate) fn case_insensitive(mut self, is_case_insensitive: bool) -> Self {
         self.is_case_insensitive = is_case_insensitive;
         self
@@ -77,24 +577,236 @@ impl<'a> RegexMatcherBuilder<'a> {
         self
     }
 
-    pub(crate) fn build(self) -> RegexMatcher {
-        let regex = {
-            let with_whole_word = if self.match_whole_word {
-                format_word_match(self.pattern)
+    /// Require the pattern to match an entire line,
+    /// anchoring it at both ends (the line terminator excluded).
+    pub(crate) fn match_whole_line(mut self, match_whole_line: bool) -> Self {
+        self.match_whole_line = match_whole_line;
+        self
+    }
+
+    /// Treat the pattern as a literal string instead of a regex,
+    /// so e.g. `.` and `*` match themselves.
+    pub(crate) fn fixed_string(mut self, is_fixed_string: bool) -> Self {
+        self.is_fixed_string = is_fixed_string;
+        self
+    }
+
+    /// Select which regex engine executes the pattern.
+    pub(crate) fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Applies the fixed-string, whole-word, and whole-line
+    /// options to a single raw pattern.
+    fn compose_pattern(&self, raw: &str) -> String {
+        let escaped = if self.is_fixed_string {
+            regex::escape(raw)
+        } else {
+            raw.to_owned()
+        };
+
+        if self.match_whole_line {
+            // (?m) so `$` matches just before a trailing newline.
+            format!(r"(?m)^(?:{})$", escaped)
+        } else if self.match_whole_word {
+            format_word_match(&escaped)
+        } else {
+            escaped
+        }
+    }
+
+    /// Builds the set-based multi-pattern matcher.
+    /// Always executed by the default engine; the fancy engine
+    /// has no equivalent of `RegexSet`.
+    fn build_set(&self) -> Result<AnyMatcher> {
+        // A set of pure literals gets the Aho-Corasick automaton,
+        // unless whole-word or whole-line matching requires the
+        // patterns to be wrapped in regex syntax.
+        if self.is_fixed_string && !self.match_whole_word && !self.match_whole_line {
+            return self.build_literals();
+        }
+
+        let composed: Vec<String> = self
+            .patterns
+            .iter()
+            .map(|p| self.compose_pattern(p))
+            .collect();
+
+        let set = RegexSetBuilder::new(&composed)
+            .case_insensitive(self.is_case_insensitive)
+            .build()
+            .map_err(|e| invalid_pattern(&self.patterns.join(", "), &e))?;
+
+        let regexes = composed
+            .iter()
+            .map(|p| Ok(build_default(p, self.is_case_insensitive)?.regex))
+            .collect::<Result<_>>()?;
+
+        Ok(AnyMatcher::Set(RegexSetMatcher { set, regexes }))
+    }
+
+    /// Builds the automaton-based matcher for literal pattern sets.
+    fn build_literals(&self) -> Result<AnyMatcher> {
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(self.is_case_insensitive)
+            .match_kind(aho_corasick::MatchKind::LeftmostLongest)
+            .build(self.patterns)
+            .map_err(|e| invalid_pattern(&self.patterns.join(", "), &e))?;
+
+        Ok(AnyMatcher::Literals(AhoCorasickMatcher { automaton }))
+    }
+
+    pub(crate) fn build(self) -> Result<AnyMatcher> {
+        if !self.all_of.is_empty() || !self.none_of.is_empty() {
+            return self.build_query();
+        }
+
+        if self.patterns.len() > 1 {
+            return self.build_set();
+        }
+
+        let single = self
+            .patterns
+            .first()
+            .map(String::as_str)
+            .unwrap_or(self.pattern);
+
+        self.build_single(single)
+    }
+
+    /// Builds the composite boolean-query matcher from the base
+    /// pattern(s) plus the `--all-of`/`--none-of` patterns.
+    fn build_query(&self) -> Result<AnyMatcher> {
+        let base = if self.patterns.len() > 1 {
+            Some(Box::new(self.build_set()?))
+        } else {
+            let single = self
+                .patterns
+                .first()
+                .map(String::as_str)
+                .unwrap_or(self.pattern);
+
+            if single.is_empty() {
+                None
             } else {
-                self.pattern.to_owned()
+                Some(Box::new(self.build_single(single)?))
+            }
+        };
+
+        let build_each = |patterns: &[String]| -> Result<Vec<AnyMatcher>> {
+            patterns.iter().map(|p| self.build_single(p)).collect()
+        };
+
+        Ok(AnyMatcher::Query(QueryMatcher {
+            base,
+            all_of: build_each(self.all_of)?,
+            none_of: build_each(self.none_of)?,
+        }))
+    }
+
+    /// Builds the matcher for one raw pattern, honoring the
+    /// configured options and engine selection.
+    fn build_single(&self, single: &str) -> Result<AnyMatcher> {
+        let pattern = self.compose_pattern(single);
+
+        // The prefilter literal comes from the escaped pattern, before
+        // the whole-word/whole-line wrapping adds grouping around it:
+        // a literal required by the inner pattern is still required
+        // by the wrapped one.
+        let prefilter = {
+            let escaped = if self.is_fixed_string {
+                regex::escape(single)
+            } else {
+                single.to_owned()
             };
 
-            RegexBuilder::new(&with_whole_word)
-                .case_insensitive(self.is_case_insensitive)
-                .build()
-                .unwrap_or_else(|e| panic!("{:?}", e))
+            Prefilter::for_pattern(&escaped, self.is_case_insensitive)
         };
 
-        RegexMatcher { regex }
+        match self.engine {
+            Engine::Default => Ok(AnyMatcher::Default(
+                build_default(&pattern, self.is_case_insensitive)?.with_prefilter(prefilter),
+            )),
+            #[cfg(feature = "fancy")]
+            Engine::Fancy => Ok(AnyMatcher::Fancy(build_fancy(
+                &pattern,
+                self.is_case_insensitive,
+            )?)),
+
+            #[cfg(not(feature = "fancy"))]
+            Engine::Fancy => build_fancy(&pattern, self.is_case_insensitive),
+            Engine::Auto => {
+                let default_attempt = RegexBuilder::new(&pattern)
+                    .case_insensitive(self.is_case_insensitive)
+                    .build();
+
+                match default_attempt {
+                    Ok(regex) => Ok(AnyMatcher::Default(RegexMatcher { regex, prefilter })),
+                    Err(e) => auto_fallback(&pattern, self.is_case_insensitive, &e),
+                }
+            }
+        }
     }
 }
 
+fn build_default(pattern: &str, case_insensitive: bool) -> Result<RegexMatcher> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| invalid_pattern(pattern, &e))?;
+
+    Ok(RegexMatcher {
+        regex,
+        prefilter: None,
+    })
+}
+
+fn invalid_pattern(pattern: &str, reason: &dyn std::fmt::Display) -> Error {
+    Error::InvalidPattern {
+        pattern: pattern.to_owned(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(feature = "fancy")]
+fn build_fancy(pattern: &str, case_insensitive: bool) -> Result<FancyRegexMatcher> {
+    let raw = pattern;
+
+    let pattern = if case_insensitive {
+        format!("(?i){}", pattern)
+    } else {
+        pattern.to_owned()
+    };
+
+    let regex = fancy_regex::Regex::new(&pattern).map_err(|e| invalid_pattern(raw, &e))?;
+
+    Ok(FancyRegexMatcher { regex })
+}
+
+/// Without the `fancy` feature compiled in, `--engine fancy` is
+/// an ordinary invalid flag value -- exit 2, not a panic.
+#[cfg(not(feature = "fancy"))]
+fn build_fancy(_pattern: &str, _case_insensitive: bool) -> Result<AnyMatcher> {
+    Err(Error::InvalidFlagValue {
+        flag: "--engine",
+        value: "fancy".to_owned(),
+        expected: "auto or default; this build omits the 'fancy' feature".to_owned(),
+    })
+}
+
+/// When the default engine rejects the pattern under `--engine auto`,
+/// fall back to the fancy engine, which supports more features.
+#[cfg(feature = "fancy")]
+fn auto_fallback(pattern: &str, case_insensitive: bool, _err: &regex::Error) -> Result<AnyMatcher> {
+    Ok(AnyMatcher::Fancy(build_fancy(pattern, case_insensitive)?))
+}
+
+#[cfg(not(feature = "fancy"))]
+fn auto_fallback(pattern: &str, _case_insensitive: bool, err: &regex::Error) -> Result<AnyMatcher> {
+    Err(invalid_pattern(pattern, err))
+}
+
 fn format_word_match(pattern: &str) -> String {
     format!(r"(?:(?m:^)|\W)({})(?:(?m:$)|\W)", pattern)
 }
@@ -1,14 +1,25 @@
 mod blocking_printer;
+mod color_config;
+mod json_printer;
+mod markdown_printer;
 mod null_printer;
+mod pooled_text;
 mod printer;
+mod sink;
 mod threaded_printer;
 
-use crate::error::{Error, Result};
-use crate::matcher::Matcher;
+use crate::cancel::CancelToken;
+use crate::matcher::{Matcher, Submatch};
 use crate::time_log::TimeLog;
+use crate::timing::TimingCollector;
+pub(crate) use color_config::ColorConfig;
 use crossbeam_channel::bounded;
+pub(crate) use json_printer::json_string;
+pub(crate) use pooled_text::{PooledText, TextPool};
 use printer::PrettyPrinter;
+pub(crate) use sink::{BufferMode, PrinterSink, StdoutSink};
 use std::thread;
+use termcolor::ColorChoice;
 
 /// A trait describing the ability to "send" a message to a printer.
 pub(crate) trait PrinterSender: Clone + Send {
@@ -20,22 +31,97 @@ pub(crate) trait PrinterSender: Clone + Send {
 pub(crate) struct PrintableResult {
     target_name: String,
     line_num: usize,
-    text: Vec<u8>,
+    text: PooledText,
+
+    /// The spans of each match within `text`, computed once by the
+    /// searcher so printers need not re-run the matcher per line.
+    spans: Vec<Submatch>,
+
+    /// True if this line did not itself match,
+    /// but is reported as context around a matching line.
+    is_context: bool,
+
+    /// The discovery index of this result's target, used by the
+    /// printer's sequenced mode to order per-target groups.
+    sequence: usize,
+
+    /// The absolute byte offset of this line's start within its
+    /// target, when the searcher tracked one (`-b`).
+    byte_offset: Option<usize>,
 }
 
 impl PrintableResult {
-    pub(crate) fn new(target_name: String, line_num: usize, text: Vec<u8>) -> Self {
+    pub(crate) fn new(
+        target_name: String,
+        line_num: usize,
+        text: impl Into<PooledText>,
+        spans: Vec<Submatch>,
+    ) -> Self {
         Self {
             target_name,
             line_num,
-            text,
+            text: text.into(),
+            spans,
+            is_context: false,
+            sequence: 0,
+            byte_offset: None,
         }
     }
 
-    /// Consume `self` and convert the `text` into a utf8 `String`.
-    fn text_as_string(self) -> Result<String> {
-        let target_name = self.target_name;
-        String::from_utf8(self.text).map_err(|_| Error::Utf8PrintFail(target_name))
+    /// Tags this result with the discovery index of its target.
+    pub(crate) fn with_sequence(mut self, sequence: usize) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Tags this result with the byte offset of its line's start.
+    pub(crate) fn with_byte_offset(mut self, byte_offset: usize) -> Self {
+        self.byte_offset = Some(byte_offset);
+        self
+    }
+
+    /// A result for a line that is merely context around a match.
+    pub(crate) fn context(
+        target_name: String,
+        line_num: usize,
+        text: impl Into<PooledText>,
+    ) -> Self {
+        Self {
+            target_name,
+            line_num,
+            text: text.into(),
+            spans: Vec::new(),
+            is_context: true,
+            sequence: 0,
+            byte_offset: None,
+        }
+    }
+
+    /// The path of the target this result came from.
+    pub(crate) fn target_name(&self) -> &str {
+        &self.target_name
+    }
+
+    pub(crate) fn line_num(&self) -> usize {
+        self.line_num
+    }
+
+    /// True for lines reported only as context around a match.
+    pub(crate) fn is_context(&self) -> bool {
+        self.is_context
+    }
+
+    /// The line's text, lossily decoded so a stray byte never
+    /// hides the line.
+    pub(crate) fn text_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.text).into_owned()
+    }
+
+    /// Consume `self` and convert the `text` into a `String`,
+    /// substituting replacement characters for any invalid UTF-8
+    /// so a stray byte never hides the line.
+    fn text_as_string(self) -> String {
+        String::from_utf8_lossy(&self.text).into_owned()
     }
 }
 
@@ -47,6 +133,19 @@ pub(crate) enum PrintMessage {
     /// Signals to the printer that there will be no more messages for the named target.
     EndOfReading {
         target_name: String,
+
+        /// The discovery index of the finished target.
+        sequence: usize,
+    },
+
+    /// The named target was skipped as binary, but the pattern
+    /// matched within its scanned prefix; printed as
+    /// `Binary file <path> matches`, like grep.
+    BinaryFileMatches {
+        target_name: String,
+
+        /// The discovery index of the binary target.
+        sequence: usize,
     },
 
     /// Simply a string for displaying.
@@ -58,6 +157,91 @@ struct Config {
     print_line_num: bool,
     group_by_target: bool,
     print_immediately: bool,
+
+    /// When context lines are enabled, non-contiguous
+    /// groups of lines are separated by a `--` marker.
+    print_context_separators: bool,
+
+    /// The marker printed between non-contiguous context blocks;
+    /// `None` suppresses it entirely.
+    context_separator: Option<String>,
+
+    /// The line printed before each group heading (blank by
+    /// default); `None` suppresses it entirely.
+    group_separator: Option<String>,
+
+    /// The delimiter between the line number / byte offset fields
+    /// and the text of a matching line (`:` by default).
+    field_match_separator: String,
+
+    /// The same delimiter for context lines (`-` by default).
+    field_context_separator: String,
+
+    /// Print only a per-target count of matching lines,
+    /// instead of the lines themselves.
+    count_only: bool,
+
+    /// Print only the names of targets containing matches.
+    files_with_matches_only: bool,
+
+    /// Emit results as JSON Lines events instead of human-readable text.
+    json: bool,
+
+    /// Render results as a Markdown report instead of human-readable text.
+    markdown: bool,
+
+    /// When set, matching lines are also written to this path in
+    /// Vim's quickfix (errorformat) layout.
+    quickfix_path: Option<String>,
+
+    /// When set, printed file paths are wrapped in OSC-8 terminal
+    /// hyperlinks, with `{path}` and `{line}` expanded in this
+    /// template to build each link target.
+    hyperlink_format: Option<String>,
+
+    /// Pad line numbers into a right-aligned column per file group.
+    align: bool,
+
+    /// Strip leading indentation from printed match lines.
+    trim: bool,
+
+    /// Group results under directory headings, with relative file
+    /// names beneath, instead of one group per file.
+    group_by_dir: bool,
+
+    /// The shared sink print-phase timing spans report into.
+    timing: TimingCollector,
+
+    /// Cancelled when the output dies, so in-flight searches stop
+    /// producing results nobody can see.
+    cancel_token: CancelToken,
+
+    /// Whether the output streams should emit color escape sequences.
+    color_choice: ColorChoice,
+
+    /// The colors to use for each part of the output.
+    colors: ColorConfig,
+
+    /// When present, every match in a printed line is replaced
+    /// by this template (with capture references expanded).
+    replace_template: Option<Vec<u8>>,
+
+    /// Buffer per-target groups and emit them in discovery order,
+    /// so output is identical from run to run.
+    sequenced: bool,
+
+    /// When present, lines longer than this many bytes are
+    /// truncated (or summarized) instead of printed in full.
+    max_columns: Option<usize>,
+
+    /// Print the byte offset of each reported line (`-b`).
+    print_byte_offset: bool,
+
+    /// Show each grouped heading with the file's match count,
+    /// e.g. `src/main.rs (12 matches)`. Forces per-target
+    /// buffering, since the count must be known before the
+    /// heading is written.
+    heading_match_counts: bool,
 }
 
 /// A builder for a printer sender, which may be either blocking
@@ -65,6 +249,7 @@ struct Config {
 pub(crate) struct Printer<M: Matcher> {
     config: Config,
     matcher: Option<M>,
+    buffer_mode: BufferMode,
 }
 
 impl<M: Matcher + Sync + 'static> Printer<M> {
@@ -78,9 +263,219 @@ impl<M: Matcher + Sync + 'static> Printer<M> {
                 print_line_num: true,
                 group_by_target: true,
                 print_immediately: false,
+                print_context_separators: false,
+                context_separator: Some("--".to_owned()),
+                group_separator: Some(String::new()),
+                field_match_separator: ":".to_owned(),
+                field_context_separator: "-".to_owned(),
+                count_only: false,
+                files_with_matches_only: false,
+                json: false,
+                markdown: false,
+                quickfix_path: None,
+                hyperlink_format: None,
+                align: false,
+                trim: false,
+                group_by_dir: false,
+                timing: TimingCollector::new(),
+                cancel_token: CancelToken::new(),
+                color_choice: ColorChoice::Auto,
+                colors: ColorConfig::default(),
+                replace_template: None,
+                sequenced: false,
+                max_columns: None,
+                print_byte_offset: false,
+                heading_match_counts: false,
             },
             matcher: None,
+            buffer_mode: BufferMode::Line,
+        }
+    }
+
+    /// Buffer stdout writes with this strategy
+    /// (`--line-buffered` / `--block-buffered`).
+    pub(crate) fn buffer_mode(mut self, buffer_mode: BufferMode) -> Self {
+        self.buffer_mode = buffer_mode;
+        self
+    }
+
+    pub(crate) fn context_separators(mut self, enabled: bool) -> Self {
+        self.config.print_context_separators = enabled;
+        self
+    }
+
+    /// Print this string between non-contiguous context blocks
+    /// instead of `--` (`--context-separator`); `None` keeps the
+    /// default.
+    pub(crate) fn context_separator(mut self, separator: Option<String>) -> Self {
+        if separator.is_some() {
+            self.config.context_separator = separator;
+        }
+
+        self
+    }
+
+    /// Print nothing at all between context blocks
+    /// (`--no-context-separator`).
+    pub(crate) fn suppress_context_separator(mut self, suppress: bool) -> Self {
+        if suppress {
+            self.config.context_separator = None;
+        }
+
+        self
+    }
+
+    /// Print this string before each group heading instead of a
+    /// blank line (`--group-separator`); `None` keeps the default.
+    pub(crate) fn group_separator(mut self, separator: Option<String>) -> Self {
+        if separator.is_some() {
+            self.config.group_separator = separator;
+        }
+
+        self
+    }
+
+    /// Print nothing at all before group headings
+    /// (`--no-group-separator`).
+    pub(crate) fn suppress_group_separator(mut self, suppress: bool) -> Self {
+        if suppress {
+            self.config.group_separator = None;
+        }
+
+        self
+    }
+
+    /// Delimit the fields of a matching line with this string
+    /// instead of `:` (`--field-match-separator`); `None` keeps
+    /// the default.
+    pub(crate) fn field_match_separator(mut self, separator: Option<String>) -> Self {
+        if let Some(separator) = separator {
+            self.config.field_match_separator = separator;
         }
+
+        self
+    }
+
+    /// Delimit the fields of a context line with this string
+    /// instead of `-` (`--field-context-separator`); `None` keeps
+    /// the default.
+    pub(crate) fn field_context_separator(mut self, separator: Option<String>) -> Self {
+        if let Some(separator) = separator {
+            self.config.field_context_separator = separator;
+        }
+
+        self
+    }
+
+    pub(crate) fn count_only(mut self, enabled: bool) -> Self {
+        self.config.count_only = enabled;
+        self
+    }
+
+    pub(crate) fn files_with_matches_only(mut self, enabled: bool) -> Self {
+        self.config.files_with_matches_only = enabled;
+        self
+    }
+
+    pub(crate) fn json_output(mut self, enabled: bool) -> Self {
+        self.config.json = enabled;
+        self
+    }
+
+    pub(crate) fn markdown_output(mut self, enabled: bool) -> Self {
+        self.config.markdown = enabled;
+        self
+    }
+
+    /// Tee matching lines into a quickfix file at this path
+    /// (`--quickfix`), alongside the normal output.
+    pub(crate) fn quickfix(mut self, path: Option<String>) -> Self {
+        self.config.quickfix_path = path;
+        self
+    }
+
+    /// Wrap printed file paths in OSC-8 hyperlinks built from this
+    /// template (`--hyperlink-format`).
+    pub(crate) fn hyperlink_format(mut self, template: Option<String>) -> Self {
+        self.config.hyperlink_format = template;
+        self
+    }
+
+    /// Pad each group's line numbers into an aligned column
+    /// (`--align`).
+    pub(crate) fn align(mut self, enabled: bool) -> Self {
+        self.config.align = enabled;
+        self
+    }
+
+    /// Strip leading indentation from printed match lines
+    /// (`--trim`).
+    pub(crate) fn trim(mut self, enabled: bool) -> Self {
+        self.config.trim = enabled;
+        self
+    }
+
+    /// Group results under directory headings instead of one
+    /// group per file (`--group-by dir`).
+    pub(crate) fn group_by_dir(mut self, enabled: bool) -> Self {
+        self.config.group_by_dir = enabled;
+        self
+    }
+
+    /// Report print-phase timings into the given collector, so
+    /// they can be combined with the searcher's phases.
+    /// The token the printer cancels if its output dies.
+    pub(crate) fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.config.cancel_token = token;
+        self
+    }
+
+    pub(crate) fn timing(mut self, collector: TimingCollector) -> Self {
+        self.config.timing = collector;
+        self
+    }
+
+    pub(crate) fn color_choice(mut self, choice: ColorChoice) -> Self {
+        self.config.color_choice = choice;
+        self
+    }
+
+    pub(crate) fn color_config(mut self, colors: ColorConfig) -> Self {
+        self.config.colors = colors;
+        self
+    }
+
+    pub(crate) fn replace_template(mut self, template: Option<Vec<u8>>) -> Self {
+        self.config.replace_template = template;
+        self
+    }
+
+    /// Emit per-target groups in the order their files were
+    /// discovered, buffering results as needed (`--ordered`).
+    pub(crate) fn sequenced(mut self, enabled: bool) -> Self {
+        self.config.sequenced = enabled;
+        self
+    }
+
+    /// Truncate (or summarize) lines longer than this many bytes
+    /// (`--max-columns`).
+    pub(crate) fn max_columns(mut self, max: Option<usize>) -> Self {
+        self.config.max_columns = max;
+        self
+    }
+
+    /// Print each reported line's byte offset within its target
+    /// (`-b`/`--byte-offset`).
+    pub(crate) fn byte_offset(mut self, enabled: bool) -> Self {
+        self.config.print_byte_offset = enabled;
+        self
+    }
+
+    /// Show each grouped heading with the file's match count
+    /// (`--heading-counts`).
+    pub(crate) fn heading_match_counts(mut self, enabled: bool) -> Self {
+        self.config.heading_match_counts = enabled;
+        self
     }
 
     pub(crate) fn group_by_target(mut self, should_group: bool) -> Self {
@@ -99,14 +494,31 @@ impl<M: Matcher + Sync + 'static> Printer<M> {
     }
 
     pub(crate) fn build_blocking(self) -> impl PrinterSender {
-        blocking_printer::BlockingSender::new(PrettyPrinter::new(self.matcher, self.config))
+        let sink = StdoutSink::new(self.buffer_mode);
+        self.build_blocking_with_sink(sink)
+    }
+
+    /// Like `build_blocking`, but printing into the given sink
+    /// instead of stdout.
+    pub(crate) fn build_blocking_with_sink<S: PrinterSink>(self, sink: S) -> impl PrinterSender {
+        blocking_printer::BlockingSender::new(PrettyPrinter::new(self.matcher, self.config), sink)
     }
 
     pub(crate) fn spawn_threaded(self) -> (impl PrinterSender, std::thread::JoinHandle<TimeLog>) {
+        let sink = StdoutSink::new(self.buffer_mode);
+        self.spawn_threaded_with_sink(sink)
+    }
+
+    /// Like `spawn_threaded`, but printing into the given sink
+    /// instead of stdout.
+    pub(crate) fn spawn_threaded_with_sink<S: PrinterSink>(
+        self,
+        sink: S,
+    ) -> (impl PrinterSender, std::thread::JoinHandle<TimeLog>) {
         let (sender, receiver) = bounded(128);
         let sender = crate::print::threaded_printer::Sender::new(sender);
         let mut printer =
-            crate::print::threaded_printer::Printer::new(self.matcher, receiver, self.config);
+            crate::print::threaded_printer::Printer::new(self.matcher, receiver, self.config, sink);
 
         (sender, thread::spawn(move || printer.listen()))
     }
@@ -1,23 +1,45 @@
-use super::{PrettyPrinter, PrintMessage};
+use super::{PrettyPrinter, PrintMessage, PrinterSink};
 use crate::matcher::Matcher;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
-use termcolor::{ColorChoice, StandardStream};
 
 #[derive(Clone)]
-pub(super) struct BlockingSender<M: Matcher + Send + Sync>(Arc<Mutex<PrettyPrinter<M>>>);
+pub(super) struct BlockingSender<M: Matcher + Send + Sync, S: PrinterSink> {
+    printer: Arc<Mutex<PrettyPrinter<M>>>,
+    sink: S,
 
-impl<M: Matcher + Send + Sync> BlockingSender<M> {
-    pub(super) fn new(printer: PrettyPrinter<M>) -> Self {
-        BlockingSender(Arc::new(Mutex::new(printer)))
+    /// Set after a write failure; further messages are dropped
+    /// rather than retried against a dead output.
+    output_failed: Arc<AtomicBool>,
+}
+
+impl<M: Matcher + Send + Sync, S: PrinterSink> BlockingSender<M, S> {
+    pub(super) fn new(printer: PrettyPrinter<M>, sink: S) -> Self {
+        Self {
+            printer: Arc::new(Mutex::new(printer)),
+            sink,
+            output_failed: Arc::new(AtomicBool::new(false)),
+        }
     }
 }
 
-impl<M: Matcher + Send + Sync> super::PrinterSender for BlockingSender<M> {
+impl<M: Matcher + Send + Sync, S: PrinterSink> super::PrinterSender for BlockingSender<M, S> {
     fn send(&self, message: PrintMessage) {
-        // TODO: store stdout in struct
-        let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-        let mut lock = self.0.lock().expect("Unable to acquire lock.");
-        lock.print(&mut stdout, message);
+        if self.output_failed.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut lock = self.printer.lock().expect("Unable to acquire lock.");
+        let mut writer = self.sink.open(lock.color_choice());
+
+        if let Err(e) = lock.print(&mut writer, message) {
+            if !e.is_broken_pipe() {
+                eprintln!("toygrep: {}", e);
+            }
+
+            self.output_failed.store(true, Ordering::Relaxed);
+            lock.cancel_searchers();
+        }
     }
 }
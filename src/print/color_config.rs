@@ -0,0 +1,166 @@
+use crate::error::{Error, Result};
+use termcolor::{Color, ColorSpec};
+
+/// The colors used for the various parts of printed output.
+///
+/// Built from zero or more specs of the form `KEY:ATTR:VALUE`,
+/// e.g. `match:fg:yellow`, `line:style:bold`, `path:bg:blue`.
+/// Valid keys are `match`, `line`, and `path`.
+/// The special form `KEY:none` resets that key to uncolored.
+#[derive(Debug, Clone)]
+pub(crate) struct ColorConfig {
+    matched: ColorSpec,
+    line_num: ColorSpec,
+    path: ColorSpec,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        let mut matched = ColorSpec::new();
+        matched.set_fg(Some(Color::Red));
+
+        let mut line_num = ColorSpec::new();
+        line_num.set_fg(Some(Color::Green));
+
+        Self {
+            matched,
+            line_num,
+            path: ColorSpec::new(),
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Builds a config from the given user-supplied specs,
+    /// applied in order over the defaults.
+    /// `Err` with a helpful message on a malformed spec.
+    pub(crate) fn from_specs<S: AsRef<str>>(specs: &[S]) -> Result<Self> {
+        let mut config = Self::default();
+
+        for spec in specs {
+            config.apply_spec(spec.as_ref())?;
+        }
+
+        Ok(config)
+    }
+
+    pub(super) fn matched(&self) -> &ColorSpec {
+        &self.matched
+    }
+
+    pub(super) fn line_num(&self) -> &ColorSpec {
+        &self.line_num
+    }
+
+    pub(super) fn path(&self) -> &ColorSpec {
+        &self.path
+    }
+
+    fn apply_spec(&mut self, spec: &str) -> Result<()> {
+        let bad_spec = |reason: &str| Error::InvalidColorSpec {
+            spec: spec.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        let mut pieces = spec.splitn(3, ':');
+
+        let key = pieces.next().unwrap_or_default();
+
+        let target = match key {
+            "match" => &mut self.matched,
+            "line" => &mut self.line_num,
+            "path" => &mut self.path,
+            _ => return Err(bad_spec("unknown key (expected match, line, or path)")),
+        };
+
+        let attr = pieces.next().ok_or_else(|| bad_spec("missing attribute"))?;
+
+        if attr == "none" {
+            *target = ColorSpec::new();
+            return Ok(());
+        }
+
+        let value = pieces.next().ok_or_else(|| bad_spec("missing value"))?;
+
+        match attr {
+            "fg" => {
+                target.set_fg(Some(parse_color(spec, value)?));
+            }
+            "bg" => {
+                target.set_bg(Some(parse_color(spec, value)?));
+            }
+            "style" => match value {
+                "bold" => {
+                    target.set_bold(true);
+                }
+                "nobold" => {
+                    target.set_bold(false);
+                }
+                "underline" => {
+                    target.set_underline(true);
+                }
+                "nounderline" => {
+                    target.set_underline(false);
+                }
+                "intense" => {
+                    target.set_intense(true);
+                }
+                "nointense" => {
+                    target.set_intense(false);
+                }
+                _ => return Err(bad_spec("unknown style")),
+            },
+            _ => return Err(bad_spec("unknown attribute (expected fg, bg, or style)")),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_color(spec: &str, value: &str) -> Result<Color> {
+    value.parse().map_err(|_| Error::InvalidColorSpec {
+        spec: spec.to_owned(),
+        reason: "unknown color".to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_colors_match_red_line_green() {
+        let config = ColorConfig::default();
+
+        assert_eq!(Some(&Color::Red), config.matched().fg());
+        assert_eq!(Some(&Color::Green), config.line_num().fg());
+        assert_eq!(None, config.path().fg());
+    }
+
+    #[test]
+    fn spec_overrides_match_fg() {
+        let config = ColorConfig::from_specs(&["match:fg:yellow"]).unwrap();
+
+        assert_eq!(Some(&Color::Yellow), config.matched().fg());
+    }
+
+    #[test]
+    fn spec_applies_style() {
+        let config = ColorConfig::from_specs(&["line:style:bold"]).unwrap();
+
+        assert!(config.line_num().bold());
+        assert_eq!(Some(&Color::Green), config.line_num().fg());
+    }
+
+    #[test]
+    fn none_resets_key() {
+        let config = ColorConfig::from_specs(&["match:none"]).unwrap();
+
+        assert_eq!(None, config.matched().fg());
+    }
+
+    #[test]
+    fn unknown_key_is_an_error() {
+        assert!(ColorConfig::from_specs(&["bogus:fg:red"]).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use super::{PrintMessage, PrintableResult};
+use crate::error::Result;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::time::Instant;
+
+/// Formats print messages as JSON Lines events, one object per line,
+/// suitable for editor integrations and other tooling.
+///
+/// The event stream looks like:
+/// `{"type":"begin","path":...}` when the first result for a target arrives,
+/// `{"type":"match",...}` (or `"context"`) per reported line,
+/// `{"type":"end","path":...,"matched_lines":N}` when a target completes,
+/// and a final `{"type":"summary",...}` when the whole search completes.
+pub(super) struct JsonFormatter {
+    begun_targets: HashSet<String>,
+    target_match_counts: HashMap<String, usize>,
+    total_matched_lines: usize,
+    start: Instant,
+}
+
+impl JsonFormatter {
+    pub(super) fn new() -> Self {
+        Self {
+            begun_targets: HashSet::new(),
+            target_match_counts: HashMap::new(),
+            total_matched_lines: 0,
+            start: Instant::now(),
+        }
+    }
+
+    pub(super) fn format<W: Write>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()> {
+        match message {
+            PrintMessage::Printable(printable) => {
+                if !self.begun_targets.contains(&printable.target_name) {
+                    self.begun_targets.insert(printable.target_name.clone());
+
+                    writeln!(
+                        writer,
+                        r#"{{"type":"begin","path":{}}}"#,
+                        json_string(printable.target_name.as_bytes())
+                    )?;
+                }
+
+                self.format_line(writer, &printable)?;
+            }
+            PrintMessage::EndOfReading { target_name, .. } => {
+                // Only targets that produced results get an "end" event,
+                // mirroring the "begin" above.
+                if !self.begun_targets.contains(&target_name) {
+                    return Ok(());
+                }
+
+                let matched_lines = self.target_match_counts.remove(&target_name).unwrap_or(0);
+
+                writeln!(
+                    writer,
+                    r#"{{"type":"end","path":{},"matched_lines":{}}}"#,
+                    json_string(target_name.as_bytes()),
+                    matched_lines
+                )?;
+            }
+            PrintMessage::BinaryFileMatches { target_name, .. } => {
+                writeln!(
+                    writer,
+                    r#"{{"type":"binary","path":{}}}"#,
+                    json_string(target_name.as_bytes())
+                )?;
+            }
+            PrintMessage::Display(msg) => {
+                writeln!(
+                    writer,
+                    r#"{{"type":"message","text":{}}}"#,
+                    json_string(msg.as_bytes())
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emits the final summary event. Invoked once,
+    /// after the last message has been formatted.
+    pub(super) fn format_summary<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            r#"{{"type":"summary","matched_lines":{},"elapsed_ms":{}}}"#,
+            self.total_matched_lines,
+            self.start.elapsed().as_millis()
+        )?;
+
+        Ok(())
+    }
+
+    fn format_line<W: Write>(&mut self, writer: &mut W, printable: &PrintableResult) -> Result<()> {
+        let event_type = if printable.is_context {
+            "context"
+        } else {
+            self.total_matched_lines += 1;
+            *self
+                .target_match_counts
+                .entry(printable.target_name.clone())
+                .or_default() += 1;
+
+            "match"
+        };
+
+        let submatches = if printable.is_context {
+            String::new()
+        } else {
+            let rendered: Vec<String> = printable
+                .spans
+                .iter()
+                .map(|submatch| {
+                    let m = &submatch.span;
+
+                    format!(
+                        r#"{{"start":{},"end":{},"text":{}}}"#,
+                        m.start,
+                        m.stop,
+                        json_string(&printable.text[m.start..m.stop])
+                    )
+                })
+                .collect();
+
+            rendered.join(",")
+        };
+
+        let offset = printable
+            .byte_offset
+            .map(|offset| format!(r#""absolute_offset":{},"#, offset))
+            .unwrap_or_default();
+
+        writeln!(
+            writer,
+            r#"{{"type":"{}","path":{},"line_number":{},{}"text":{},"submatches":[{}]}}"#,
+            event_type,
+            json_string(printable.target_name.as_bytes()),
+            printable.line_num,
+            offset,
+            json_string(&printable.text),
+            submatches
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Renders bytes as a JSON string literal (quotes included),
+/// escaping as required and substituting any invalid utf8.
+pub(crate) fn json_string(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_string_escapes_quotes_and_newlines() {
+        assert_eq!(r#""say \"hi\"\n""#, json_string(b"say \"hi\"\n"));
+    }
+
+    #[test]
+    fn json_string_replaces_invalid_utf8() {
+        assert_eq!("\"\u{fffd}\"", json_string(&[0xff]));
+    }
+}
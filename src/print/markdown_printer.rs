@@ -0,0 +1,166 @@
+use super::{PrintMessage, PrintableResult};
+use crate::error::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Formats print messages as a Markdown report: one heading per
+/// target followed by a fenced code block of its numbered lines,
+/// ready for pasting into issues and PR descriptions.
+///
+/// Results are buffered per target and rendered when the target's
+/// end-of-reading arrives, so each section is always contiguous
+/// even when searches complete out of order.
+pub(super) struct MarkdownFormatter {
+    target_results: HashMap<String, Vec<PrintableResult>>,
+    total_matched_lines: usize,
+    targets_with_matches: usize,
+}
+
+impl MarkdownFormatter {
+    pub(super) fn new() -> Self {
+        Self {
+            target_results: HashMap::new(),
+            total_matched_lines: 0,
+            targets_with_matches: 0,
+        }
+    }
+
+    pub(super) fn format<W: Write>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()> {
+        match message {
+            PrintMessage::Printable(printable) => {
+                self.target_results
+                    .entry(printable.target_name.clone())
+                    .or_default()
+                    .push(printable);
+            }
+            PrintMessage::EndOfReading { target_name, .. } => {
+                // Targets without results get no section,
+                // just like the grouped pretty printer.
+                if let Some(results) = self.target_results.remove(&target_name) {
+                    self.format_section(writer, &target_name, &results)?;
+                }
+            }
+            PrintMessage::BinaryFileMatches { target_name, .. } => {
+                writeln!(writer, "### `{}`\n\nBinary file matches.\n", target_name)?;
+            }
+            PrintMessage::Display(msg) => {
+                writeln!(writer, "{}", msg.trim_end())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emits the closing summary line. Invoked once,
+    /// after the last message has been formatted.
+    pub(super) fn format_summary<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            "_{} matching {} across {} {}._",
+            self.total_matched_lines,
+            if self.total_matched_lines == 1 {
+                "line"
+            } else {
+                "lines"
+            },
+            self.targets_with_matches,
+            if self.targets_with_matches == 1 {
+                "file"
+            } else {
+                "files"
+            }
+        )?;
+
+        Ok(())
+    }
+
+    fn format_section<W: Write>(
+        &mut self,
+        writer: &mut W,
+        target_name: &str,
+        results: &[PrintableResult],
+    ) -> Result<()> {
+        self.targets_with_matches += 1;
+
+        writeln!(writer, "### `{}`\n\n```text", target_name)?;
+
+        for printable in results {
+            let separator = if printable.is_context {
+                "-"
+            } else {
+                self.total_matched_lines += 1;
+                ":"
+            };
+
+            let mut text: &[u8] = &printable.text;
+            while let [head @ .., b'\n' | b'\r'] = text {
+                text = head;
+            }
+
+            writeln!(
+                writer,
+                "{}{} {}",
+                printable.line_num,
+                separator,
+                String::from_utf8_lossy(text)
+            )?;
+        }
+
+        writeln!(writer, "```\n")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn section_renders_heading_and_fenced_lines() {
+        let mut formatter = MarkdownFormatter::new();
+        let mut out = Vec::new();
+
+        formatter
+            .format(
+                &mut out,
+                PrintMessage::Printable(PrintableResult::new(
+                    "src/lib.rs".to_owned(),
+                    3,
+                    b"hello\n".to_vec(),
+                    Vec::new(),
+                )),
+            )
+            .unwrap();
+        formatter
+            .format(
+                &mut out,
+                PrintMessage::EndOfReading {
+                    target_name: "src/lib.rs".to_owned(),
+                    sequence: 0,
+                },
+            )
+            .unwrap();
+
+        let expected = "### `src/lib.rs`\n\n```text\n3: hello\n```\n\n";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn targets_without_results_get_no_section() {
+        let mut formatter = MarkdownFormatter::new();
+        let mut out = Vec::new();
+
+        formatter
+            .format(
+                &mut out,
+                PrintMessage::EndOfReading {
+                    target_name: "empty.rs".to_owned(),
+                    sequence: 0,
+                },
+            )
+            .unwrap();
+
+        assert!(out.is_empty());
+    }
+}
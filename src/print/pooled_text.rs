@@ -0,0 +1,145 @@
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How many spent buffers the pool will hold for reuse.
+const RECYCLE_CAPACITY: usize = 128;
+
+/// A pool of byte buffers shared between the searcher and the
+/// printer. Match text is written into a recycled buffer instead
+/// of a fresh allocation (a hot path on match-dense searches),
+/// and the buffer returns here once the printed result is dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct TextPool {
+    recycled: Receiver<Vec<u8>>,
+    recycler: Sender<Vec<u8>>,
+
+    /// How many payloads were freshly allocated because no
+    /// recycled buffer was available.
+    allocations: Arc<AtomicUsize>,
+}
+
+impl TextPool {
+    pub(crate) fn new() -> Self {
+        let (recycler, recycled) = bounded(RECYCLE_CAPACITY);
+
+        Self {
+            recycled,
+            recycler,
+            allocations: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// An empty buffer: a recycled one when available, otherwise
+    /// freshly allocated. Dropping it returns it to this pool.
+    pub(crate) fn acquire(&self) -> PooledText {
+        let bytes = self.recycled.try_recv().unwrap_or_else(|_| {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            Vec::new()
+        });
+
+        PooledText {
+            bytes,
+            recycler: Some(self.recycler.clone()),
+        }
+    }
+
+    /// How many print payloads were freshly allocated (rather
+    /// than recycled) so far.
+    pub(crate) fn allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for TextPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Line text bound for the printer. Usually carries a buffer on
+/// loan from a `TextPool`, returned on drop; buffers from other
+/// sources convert in via `From<Vec<u8>>` and are simply freed.
+#[derive(Debug)]
+pub(crate) struct PooledText {
+    bytes: Vec<u8>,
+
+    /// The home pool's return channel, when this buffer is on loan.
+    recycler: Option<Sender<Vec<u8>>>,
+}
+
+impl Drop for PooledText {
+    fn drop(&mut self) {
+        if let Some(recycler) = &self.recycler {
+            let mut bytes = std::mem::take(&mut self.bytes);
+            bytes.clear();
+
+            // A full return channel means buffers are being created
+            // faster than they are printed; let the excess one free.
+            let _ = recycler.try_send(bytes);
+        }
+    }
+}
+
+impl Clone for PooledText {
+    /// A clone is a plain copy with no pool affiliation.
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            recycler: None,
+        }
+    }
+}
+
+impl From<Vec<u8>> for PooledText {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            recycler: None,
+        }
+    }
+}
+
+impl Deref for PooledText {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.bytes
+    }
+}
+
+impl DerefMut for PooledText {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dropped_buffers_are_recycled() {
+        let pool = TextPool::new();
+
+        let mut text = pool.acquire();
+        text.extend_from_slice(b"hello");
+        let allocated = text.capacity();
+        drop(text);
+
+        let recycled = pool.acquire();
+        assert!(recycled.is_empty());
+        assert_eq!(allocated, recycled.capacity());
+    }
+
+    #[test]
+    fn converted_buffers_are_not_recycled() {
+        let pool = TextPool::new();
+
+        let text: PooledText = b"hello".to_vec().into();
+        drop(text);
+
+        assert_eq!(0, pool.acquire().capacity());
+    }
+}
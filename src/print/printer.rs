@@ -1,9 +1,11 @@
+use super::json_printer::JsonFormatter;
+use super::markdown_printer::MarkdownFormatter;
 use super::{Config, PrintMessage, PrintableResult};
 use crate::error::{Error, Result};
 use crate::matcher::Matcher;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
-use termcolor::{Color, ColorSpec, WriteColor};
+use termcolor::WriteColor;
 
 /// This module contains the types and logic
 /// for a printer that can group lines
@@ -20,37 +22,218 @@ pub(super) struct PrettyPrinter<M: Matcher> {
     currently_printing_file: Option<String>,
     config: Config,
     matcher: Option<M>,
+
+    /// The line number of the most recently printed line,
+    /// used to detect gaps between context groups.
+    last_line_num: Option<usize>,
+
+    /// In `--align` mode, the width line numbers of the group
+    /// currently being flushed are padded to.
+    align_width: Option<usize>,
+
+    /// The indentation written before each result line; only
+    /// non-empty in directory-grouped mode.
+    line_indent: &'static str,
+
+    /// Per-target counts of matching lines, used in count-only mode.
+    target_counts: HashMap<String, usize>,
+
+    /// Targets whose names have already been printed,
+    /// used in files-with-matches mode to print each name only once.
+    printed_targets: HashSet<String>,
+
+    /// Serializer for the JSON Lines output mode.
+    json_formatter: JsonFormatter,
+
+    /// Renderer for the Markdown report output mode.
+    markdown_formatter: MarkdownFormatter,
+
+    /// A secondary writer every matching line is teed into in
+    /// `path:line:col:text` layout, for Vim's `:cfile`.
+    quickfix_writer: Option<std::io::BufWriter<std::fs::File>>,
+
+    /// In sequenced mode, messages buffered per discovery index
+    /// until every earlier-discovered target has been flushed.
+    /// The flag records that the target's end-of-reading arrived.
+    sequenced_groups: BTreeMap<usize, (Vec<PrintMessage>, bool)>,
+
+    /// The discovery index the sequencer will flush next.
+    next_sequence: usize,
 }
 
 impl<M: Matcher> PrettyPrinter<M> {
     pub(super) fn new(matcher: Option<M>, config: Config) -> Self {
+        let quickfix_writer = config.quickfix_path.as_ref().map(|path| {
+            let file = std::fs::File::create(path).unwrap_or_else(|e| {
+                eprintln!("toygrep: couldn't create quickfix file '{}': {}", path, e);
+                std::process::exit(2);
+            });
+
+            std::io::BufWriter::new(file)
+        });
+
         Self {
             matcher,
             config,
             file_to_matches: HashMap::new(),
             currently_printing_file: None,
+            last_line_num: None,
+            align_width: None,
+            line_indent: "",
+            target_counts: HashMap::new(),
+            printed_targets: HashSet::new(),
+            json_formatter: JsonFormatter::new(),
+            markdown_formatter: MarkdownFormatter::new(),
+            quickfix_writer,
+            sequenced_groups: BTreeMap::new(),
+            next_sequence: 0,
         }
     }
 
-    pub(super) fn print<W>(&mut self, mut writer: W, message: PrintMessage)
+    /// The color choice the output stream for this printer should use.
+    pub(super) fn color_choice(&self) -> termcolor::ColorChoice {
+        self.config.color_choice
+    }
+
+    /// Asks the searchers to stop: the output is gone, so there is
+    /// nothing useful left for them to produce.
+    pub(super) fn cancel_searchers(&self) {
+        self.config.cancel_token.cancel();
+    }
+
+    pub(super) fn print<W>(&mut self, mut writer: W, message: PrintMessage) -> Result<()>
     where
         W: Write + WriteColor,
     {
+        let _print_span = self.config.timing.span(crate::timing::Phase::Print);
+
+        if self.config.sequenced {
+            return self.print_sequenced(&mut writer, message);
+        }
+
+        self.print_now(&mut writer, message)
+    }
+
+    /// Buffers the message under its target's discovery index,
+    /// then flushes any groups whose turn has come. Groups are
+    /// flushed strictly in discovery order, so the output is
+    /// identical from run to run.
+    fn print_sequenced<W>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        let sequence = match &message {
+            PrintMessage::Printable(printable) => printable.sequence,
+            PrintMessage::EndOfReading { sequence, .. } => *sequence,
+            PrintMessage::BinaryFileMatches { sequence, .. } => *sequence,
+
+            // Display messages carry no target; print them at once.
+            PrintMessage::Display(_) => {
+                return self.print_now(writer, message);
+            }
+        };
+
+        // A binary notice is the last (and only) message for its
+        // target, so it completes the group just as an
+        // end-of-reading does.
+        let is_end = matches!(
+            message,
+            PrintMessage::EndOfReading { .. } | PrintMessage::BinaryFileMatches { .. }
+        );
+
+        let group = self.sequenced_groups.entry(sequence).or_default();
+        group.0.push(message);
+
+        if is_end {
+            group.1 = true;
+            self.flush_ready_groups(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes consecutive completed groups starting at the next
+    /// expected discovery index.
+    fn flush_ready_groups<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        while let Some((_, true)) = self.sequenced_groups.get(&self.next_sequence) {
+            let (messages, _) = self.sequenced_groups.remove(&self.next_sequence).unwrap();
+
+            for message in messages {
+                self.print_now(writer, message)?;
+            }
+
+            self.next_sequence += 1;
+        }
+
+        Ok(())
+    }
+
+    fn print_now<W>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        if let PrintMessage::Printable(printable) = &message {
+            self.tee_quickfix(printable)?;
+        }
+
+        if self.config.json {
+            return self.json_formatter.format(writer, message);
+        }
+
+        if self.config.markdown {
+            return self.markdown_formatter.format(writer, message);
+        }
+
+        if self.config.count_only {
+            return self.print_count(writer, message);
+        }
+
+        if self.config.files_with_matches_only {
+            return self.print_file_with_matches(writer, message);
+        }
+
+        if self.config.group_by_dir {
+            return self.buffer_dir_grouped(writer, message);
+        }
+
         if self.config.group_by_target {
             match message {
                 PrintMessage::Display(msg) => {
                     print!("{}", msg);
                 }
                 PrintMessage::Printable(printable) => {
+                    // A heading that carries the match count, and
+                    // column alignment, can only be written once
+                    // the whole group has arrived, so every result
+                    // is buffered until its target's end-of-reading.
+                    if self.config.heading_match_counts || self.config.align {
+                        self.file_to_matches
+                            .entry(printable.target_name.to_owned())
+                            .or_default()
+                            .push(printable);
+
+                        return Ok(());
+                    }
+
                     if self.currently_printing_file == None {
                         self.currently_printing_file = Some(printable.target_name.clone());
 
-                        // Print everything we've already stored for this file:
-                        let _ = self.print_target_results(&mut writer, &printable.target_name);
+                        // Print everything we've already stored
+                        // for this file; if nothing was stored,
+                        // the heading hasn't been written yet.
+                        let printed_any =
+                            self.print_target_results(writer, &printable.target_name)?;
+
+                        if !printed_any {
+                            self.print_heading(writer, &printable.target_name, None)?;
+                        }
                     }
 
                     if Some(&printable.target_name) == self.currently_printing_file.as_ref() {
-                        let _ = self.print_line_result(&mut writer, printable);
+                        self.print_line_result(writer, printable)?;
                     } else {
                         let line_results = self
                             .file_to_matches
@@ -60,20 +243,278 @@ impl<M: Matcher> PrettyPrinter<M> {
                         line_results.push(printable);
                     }
                 }
-                PrintMessage::EndOfReading { target_name } => {
+                PrintMessage::EndOfReading { target_name, .. } => {
                     if Some(&target_name) == self.currently_printing_file.as_ref() {
                         self.currently_printing_file = None;
+                        self.last_line_num = None;
                     } else {
-                        let _ = self.print_target_results(&mut writer, &target_name);
+                        self.print_target_results(writer, &target_name)?;
                     }
                 }
+                PrintMessage::BinaryFileMatches { target_name, .. } => {
+                    Self::print_binary_notice(writer, &target_name)?;
+                }
+            }
+        } else {
+            match message {
+                PrintMessage::Printable(printable) => {
+                    self.print_line_result(writer, printable)?;
+                }
+                PrintMessage::Display(msg) => {
+                    print!("{}", msg);
+                }
+                PrintMessage::EndOfReading { .. } => {}
+                PrintMessage::BinaryFileMatches { target_name, .. } => {
+                    Self::print_binary_notice(writer, &target_name)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// In directory-grouped mode every result is buffered under
+    /// its target until the whole search completes, since a
+    /// directory's group is only known to be complete at the end.
+    fn buffer_dir_grouped<W>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        match message {
+            PrintMessage::Printable(printable) => {
+                self.file_to_matches
+                    .entry(printable.target_name.to_owned())
+                    .or_default()
+                    .push(printable);
+            }
+            PrintMessage::BinaryFileMatches { target_name, .. } => {
+                Self::print_binary_notice(writer, &target_name)?;
+            }
+            PrintMessage::Display(msg) => {
+                print!("{}", msg);
+            }
+            PrintMessage::EndOfReading { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    /// Flushes everything buffered by directory-grouped mode: one
+    /// heading per directory, each file's relative name indented
+    /// beneath it, and that file's results beneath the name.
+    fn print_dir_groups<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        let mut dir_to_targets: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for target_name in self.file_to_matches.keys() {
+            let path = std::path::Path::new(target_name);
+            let dir = path
+                .parent()
+                .map(|parent| parent.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            dir_to_targets
+                .entry(dir)
+                .or_default()
+                .push(target_name.clone());
+        }
+
+        for (dir, mut targets) in dir_to_targets {
+            targets.sort();
+
+            self.print_heading(writer, &dir, None)?;
+
+            for target_name in targets {
+                let file_name = std::path::Path::new(&target_name)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| target_name.clone());
+
+                writer.set_color(self.config.colors.path())?;
+                writeln!(writer, "  {}", file_name)?;
+                writer.reset()?;
+
+                let results = self
+                    .file_to_matches
+                    .remove(&target_name)
+                    .unwrap_or_default();
+
+                if self.config.align {
+                    self.align_width = results
+                        .iter()
+                        .map(|printable| printable.line_num.to_string().len())
+                        .max();
+                }
+
+                self.line_indent = "    ";
+                for printable in results {
+                    self.print_line_result(writer, printable)?;
+                }
+                self.line_indent = "";
+                self.align_width = None;
+                self.last_line_num = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends the printable to the quickfix file, if one was
+    /// requested. Context lines are skipped: `:cfile` wants one
+    /// entry per match.
+    fn tee_quickfix(&mut self, printable: &PrintableResult) -> Result<()> {
+        let quickfix_path = self.config.quickfix_path.clone().unwrap_or_default();
+        let writer = match &mut self.quickfix_writer {
+            Some(writer) if !printable.is_context => writer,
+            _ => return Ok(()),
+        };
+
+        // Columns are 1-based in the quickfix format.
+        let column = printable
+            .spans
+            .first()
+            .map(|submatch| submatch.span.start + 1)
+            .unwrap_or(1);
+
+        let mut text: &[u8] = &printable.text;
+        while let [head @ .., b'\n' | b'\r'] = text {
+            text = head;
+        }
+
+        writeln!(
+            writer,
+            "{}:{}:{}:{}",
+            printable.target_name,
+            printable.line_num,
+            column,
+            String::from_utf8_lossy(text)
+        )
+        .map_err(|source| Error::Io {
+            path: quickfix_path,
+            source,
+        })
+    }
+
+    /// Called once after the final message has been printed,
+    /// for output modes that end with a closing summary.
+    pub(super) fn finish<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        // A target that was skipped (e.g. binary) never reports an
+        // end-of-reading, which would stall the sequencer forever;
+        // whatever is still buffered is flushed in index order here.
+        let remaining = std::mem::take(&mut self.sequenced_groups);
+
+        for (_, (messages, _)) in remaining {
+            for message in messages {
+                self.print_now(writer, message)?;
+            }
+        }
+
+        if self.config.group_by_dir {
+            self.print_dir_groups(writer)?;
+        }
+
+        if self.config.json {
+            self.json_formatter.format_summary(writer)?;
+        }
+
+        if self.config.markdown {
+            self.markdown_formatter.format_summary(writer)?;
+        }
+
+        if let Some(quickfix) = &mut self.quickfix_writer {
+            quickfix.flush().map_err(|source| Error::Io {
+                path: self.config.quickfix_path.clone().unwrap_or_default(),
+                source,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// In count-only mode, matching lines are only tallied,
+    /// and the total is printed per-target at end of reading.
+    fn print_count<W>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        match message {
+            PrintMessage::Printable(printable) => {
+                if !printable.is_context {
+                    *self.target_counts.entry(printable.target_name).or_default() += 1;
+                }
+            }
+            PrintMessage::EndOfReading { target_name, .. } => {
+                let count = self.target_counts.remove(&target_name).unwrap_or(0);
+
+                if count == 0 {
+                    return Ok(());
+                }
+
+                if target_name.is_empty() {
+                    writeln!(writer, "{}", count)?;
+                } else {
+                    writeln!(writer, "{}:{}", target_name, count)?;
+                }
+            }
+            PrintMessage::BinaryFileMatches { .. } => {}
+            PrintMessage::Display(msg) => {
+                print!("{}", msg);
             }
-        } else if let PrintMessage::Printable(printable) = message {
-            let _ = self.print_line_result(&mut writer, printable);
         }
+
+        Ok(())
     }
 
-    fn print_target_results<W>(&mut self, writer: &mut W, name: &str) -> Result<()>
+    /// In files-with-matches mode, only the name of each target
+    /// containing at least one match is printed, exactly once.
+    fn print_file_with_matches<W>(&mut self, writer: &mut W, message: PrintMessage) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        // A binary target with a match in its prefix counts as a
+        // file with matches, like it does for grep -l.
+        let target_name = match message {
+            PrintMessage::Printable(printable) => {
+                if printable.is_context {
+                    return Ok(());
+                }
+
+                printable.target_name
+            }
+            PrintMessage::BinaryFileMatches { target_name, .. } => target_name,
+            _ => return Ok(()),
+        };
+
+        if self.printed_targets.contains(&target_name) {
+            return Ok(());
+        }
+
+        self.write_path(writer, &target_name)?;
+        writeln!(writer)?;
+        self.printed_targets.insert(target_name);
+
+        Ok(())
+    }
+
+    /// The grep-style notice for a binary file whose scanned
+    /// prefix matched the pattern.
+    fn print_binary_notice<W>(writer: &mut W, target_name: &str) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        writeln!(writer, "Binary file {} matches", target_name)?;
+
+        Ok(())
+    }
+
+    /// `Ok(true)` if the target had stored results (now printed
+    /// under their heading); `Ok(false)` if there was nothing to do.
+    fn print_target_results<W>(&mut self, writer: &mut W, name: &str) -> Result<bool>
     where
         W: Write + WriteColor,
     {
@@ -82,86 +523,288 @@ impl<M: Matcher> PrettyPrinter<M> {
 
         if matches_for_target.is_empty() {
             // Nothing to do.
-            return Ok(());
+            return Ok(false);
+        }
+
+        let match_count = if self.config.heading_match_counts {
+            Some(
+                matches_for_target
+                    .iter()
+                    .filter(|printable| !printable.is_context)
+                    .count(),
+            )
+        } else {
+            None
+        };
+
+        if self.config.align {
+            self.align_width = matches_for_target
+                .iter()
+                .map(|printable| printable.line_num.to_string().len())
+                .max();
         }
 
-        writeln!(writer, "\n{}", name).expect("Error writing to stdout.");
+        self.print_heading(writer, name, match_count)?;
         for printable in matches_for_target {
             self.print_line_result(writer, printable)?;
         }
 
+        self.align_width = None;
+
+        Ok(true)
+    }
+
+    /// Writes a file path, wrapped in an OSC-8 hyperlink when a
+    /// template was configured and the destination can render
+    /// escape sequences (the same gate color output uses, so links
+    /// never leak into pipes or files).
+    fn write_path<W>(&self, writer: &mut W, path: &str) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        let template = match &self.config.hyperlink_format {
+            Some(template) if writer.supports_color() => template,
+            _ => {
+                write!(writer, "{}", path)?;
+                return Ok(());
+            }
+        };
+
+        // Headings cover a whole file, so the link always points
+        // at line 1.
+        let uri = template.replace("{path}", path).replace("{line}", "1");
+
+        write!(writer, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", uri, path)?;
+
+        Ok(())
+    }
+
+    /// The grouped-mode heading: the target's path in its
+    /// configured color, optionally suffixed with the group's
+    /// match count (`--heading-counts`).
+    fn print_heading<W>(
+        &mut self,
+        writer: &mut W,
+        name: &str,
+        match_count: Option<usize>,
+    ) -> Result<()>
+    where
+        W: Write + WriteColor,
+    {
+        // The group separator (a blank line, unless configured
+        // otherwise) sets each group's heading apart from the
+        // results above it.
+        if let Some(separator) = &self.config.group_separator {
+            writeln!(writer, "{}", separator)?;
+        }
+
+        writer.set_color(self.config.colors.path())?;
+        self.write_path(writer, name)?;
+        writer.reset()?;
+
+        match match_count {
+            Some(1) => writeln!(writer, " (1 match)")?,
+            Some(count) => writeln!(writer, " ({} matches)", count)?,
+            None => writeln!(writer)?,
+        }
+
+        self.last_line_num = None;
+
         Ok(())
     }
 
-    fn print_line_result<W>(&self, writer: &mut W, printable: PrintableResult) -> Result<()>
+    fn print_line_result<W>(&mut self, writer: &mut W, printable: PrintableResult) -> Result<()>
     where
         W: Write + WriteColor,
     {
-        let line_num = if self.config.print_line_num {
-            format!("{}:", printable.line_num)
+        let printable = match self.config.max_columns {
+            Some(max) => Self::shorten_long_line(printable, max),
+            None => printable,
+        };
+
+        let printable = if self.config.trim {
+            Self::trim_leading_whitespace(printable)
         } else {
-            "".to_owned()
+            printable
         };
 
-        if let Some(matcher) = &self.matcher {
-            Self::print_colorized(&line_num, matcher, writer, &printable);
+        if self.config.print_context_separators {
+            if let Some(last) = self.last_line_num {
+                if printable.line_num > last + 1 {
+                    if let Some(separator) = &self.config.context_separator {
+                        writeln!(writer, "{}", separator)?;
+                    }
+                }
+            }
+
+            self.last_line_num = Some(printable.line_num);
+        }
+
+        // Context lines are separated from their line number with `-`,
+        // matching lines with `:`, like grep does -- unless the
+        // delimiters were overridden for machine consumption.
+        let separator = if printable.is_context {
+            self.config.field_context_separator.as_str()
         } else {
-            write!(writer, "{}{}", line_num, printable.text_as_string()?)
-                .expect("Error writing to stdout.");
+            self.config.field_match_separator.as_str()
+        };
+
+        let mut line_num = if self.config.print_line_num {
+            // In `--align` mode line numbers are right-aligned to
+            // the widest number in the group.
+            let width = self.align_width.unwrap_or(0);
+
+            format!(
+                "{}{:>width$}{}",
+                self.line_indent, printable.line_num, separator
+            )
+        } else {
+            self.line_indent.to_owned()
+        };
+
+        // With `-b`, the offset chunk follows the line number
+        // (`line:offset:`), like grep -n -b.
+        if self.config.print_byte_offset {
+            if let Some(byte_offset) = printable.byte_offset {
+                line_num.push_str(&format!("{}{}", byte_offset, separator));
+            }
         }
 
-        Ok(())
+        if let (Some(matcher), Some(template)) = (&self.matcher, &self.config.replace_template) {
+            if !printable.is_context {
+                let replaced = matcher.replace_all(&printable.text, template);
+
+                writer.set_color(self.config.colors.line_num())?;
+                write!(writer, "{}", line_num)?;
+                writer.reset()?;
+
+                write!(writer, "{}", String::from_utf8_lossy(&replaced))?;
+
+                return Ok(());
+            }
+        }
+
+        Self::print_colorized(&line_num, writer, &printable, &self.config.colors)
+    }
+
+    /// Strips leading spaces and tabs from the printable's text
+    /// (`--trim`), shifting its match spans left so coloring still
+    /// lands on the right bytes. Spans that sat entirely inside
+    /// the indentation collapse to empty and render nothing.
+    fn trim_leading_whitespace(mut printable: PrintableResult) -> PrintableResult {
+        let trimmed = printable
+            .text
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+
+        if trimmed == 0 {
+            return printable;
+        }
+
+        printable.text.drain(..trimmed);
+
+        for submatch in &mut printable.spans {
+            submatch.span.start = submatch.span.start.saturating_sub(trimmed);
+            submatch.span.stop = submatch.span.stop.saturating_sub(trimmed);
+        }
+
+        printable
+    }
+
+    /// Keeps very long lines (minified JS, JSONL blobs) from
+    /// dumping megabytes to the terminal: a line longer than
+    /// `max` bytes is truncated with an ellipsis -- preserving
+    /// colorization of any matches within the kept prefix -- or,
+    /// when every match starts beyond the limit, replaced with an
+    /// omission notice carrying the match count.
+    fn shorten_long_line(mut printable: PrintableResult, max: usize) -> PrintableResult {
+        let content_len = {
+            let mut text = &printable.text[..];
+
+            while let Some((b'\n' | b'\r', rest)) = text.split_last() {
+                text = rest;
+            }
+
+            text.len()
+        };
+
+        if content_len <= max {
+            return printable;
+        }
+
+        let no_visible_match = printable
+            .spans
+            .first()
+            .map_or(true, |submatch| submatch.span.start >= max);
+
+        if !printable.is_context && no_visible_match {
+            printable.text = format!(
+                "[Omitted long line with {} matches]\n",
+                printable.spans.len()
+            )
+            .into_bytes()
+            .into();
+            printable.spans.clear();
+
+            return printable;
+        }
+
+        // Truncate at a character boundary so the lossy rendering
+        // of the kept prefix never ends in a replacement character
+        // caused by the cut itself.
+        let mut cut = max;
+        while cut > 0 && printable.text[cut] & 0b1100_0000 == 0b1000_0000 {
+            cut -= 1;
+        }
+
+        printable.text.truncate(cut);
+        printable.text.extend_from_slice(b" [...]\n");
+
+        printable.spans.retain(|submatch| submatch.span.start < cut);
+        for submatch in &mut printable.spans {
+            submatch.span.stop = usize::min(submatch.span.stop, cut);
+        }
+
+        printable
     }
 
     fn print_colorized<W>(
         line_num_chunk: &str,
-        matcher: &M,
         writer: &mut W,
         printable: &PrintableResult,
-    ) where
+        colors: &super::ColorConfig,
+    ) -> Result<()>
+    where
         W: Write + WriteColor,
     {
         let text = &printable.text;
 
-        let parse_utf8 = |bytes| {
-            std::str::from_utf8(bytes)
-                .map_err(|_| Error::Utf8PrintFail(printable.target_name.to_owned()))
-        };
-
-        // First, write the line num in green.
-        writer
-            .set_color(ColorSpec::new().set_fg(Some(Color::Green)))
-            .expect("Failed setting color.");
+        // First, write the line num in its configured color.
+        writer.set_color(colors.line_num())?;
 
-        write!(writer, "{}", line_num_chunk).expect("Failed writing line num chunk.");
+        write!(writer, "{}", line_num_chunk)?;
 
         // Then, reset color to print the non-matching segment.
-        writer.reset().expect("Failed to reset stdout color.");
+        writer.reset()?;
 
         let mut start = 0;
-        for match_range in matcher.find_matches(text) {
+        for submatch in &printable.spans {
+            let match_range = &submatch.span;
             let until_match = &text[start..match_range.start];
             let during_match = &text[match_range.start..match_range.stop];
 
-            if let Ok(text) = parse_utf8(until_match) {
-                write!(writer, "{}", text).expect("Failure writing to stdout");
-            } else {
-                eprintln!("Utf8 parsing error for target: {}", printable.target_name);
-            }
+            // Invalid UTF-8 is printed lossily (as replacement
+            // characters) rather than dropped, so one stray byte
+            // can't hide a real match.
+            write!(writer, "{}", String::from_utf8_lossy(until_match))?;
 
-            // The match itself is printed in red.
-            // stdout
-            writer
-                .set_color(ColorSpec::new().set_fg(Some(Color::Red)))
-                .expect("Failed setting color.");
+            // The match itself is printed in its configured color.
+            writer.set_color(colors.matched())?;
 
-            if let Ok(text) = parse_utf8(during_match) {
-                write!(writer, "{}", text).expect("Failure writing to stdout");
-            } else {
-                eprintln!("Utf8 parsing error for target: {}", printable.target_name);
-            }
+            write!(writer, "{}", String::from_utf8_lossy(during_match))?;
 
-            writer.reset().expect("Failed to reset stdout color.");
+            writer.reset()?;
 
             start = match_range.stop;
         }
@@ -169,10 +812,8 @@ impl<M: Matcher> PrettyPrinter<M> {
         // print remainder after final match
         let remainder = &text[start..];
 
-        if let Ok(text) = parse_utf8(remainder) {
-            write!(writer, "{}", text).expect("Failure writing to stdout");
-        } else {
-            eprintln!("Utf8 parsing error for target: {}", printable.target_name);
-        }
+        write!(writer, "{}", String::from_utf8_lossy(remainder))?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,202 @@
+use std::io::Write;
+use termcolor::{BufferedStandardStream, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+/// How the stdout sink buffers its writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BufferMode {
+    /// Flush whenever a newline is written (`--line-buffered`).
+    /// This is also the default, since `std::io::Stdout` is
+    /// line-buffered on its own.
+    Line,
+
+    /// Flush only when an internal block fills up
+    /// (`--block-buffered`); much faster when a large result set
+    /// is redirected to a file.
+    Block,
+}
+
+/// The final destination printed output is written to.
+///
+/// The printers themselves only know about `Write + WriteColor`;
+/// this trait decides where such a writer comes from, so output
+/// can go to stdout (the default), into memory for tests, or to
+/// any other destination a consumer supplies.
+pub(crate) trait PrinterSink: Clone + Send + 'static {
+    type Writer: Write + WriteColor;
+
+    /// Opens the sink's writer. The threaded printer opens once
+    /// per session; the blocking printer once per message.
+    fn open(&self, color_choice: ColorChoice) -> Self::Writer;
+}
+
+/// The default sink: the process's stdout.
+#[derive(Debug, Clone)]
+pub(crate) struct StdoutSink {
+    buffer_mode: BufferMode,
+}
+
+impl StdoutSink {
+    pub(crate) fn new(buffer_mode: BufferMode) -> Self {
+        Self { buffer_mode }
+    }
+}
+
+impl PrinterSink for StdoutSink {
+    type Writer = StdoutWriter;
+
+    fn open(&self, color_choice: ColorChoice) -> StdoutWriter {
+        match self.buffer_mode {
+            BufferMode::Line => StdoutWriter::Line(StandardStream::stdout(color_choice)),
+            BufferMode::Block => StdoutWriter::Block(BufferedStandardStream::stdout(color_choice)),
+        }
+    }
+}
+
+/// The stdout writer in whichever buffering mode the sink was
+/// configured with. The block-buffered variant flushes its final
+/// block when dropped.
+pub(crate) enum StdoutWriter {
+    Line(StandardStream),
+    Block(BufferedStandardStream),
+}
+
+impl Write for StdoutWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Line(w) => w.write(buf),
+            Self::Block(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Line(w) => w.flush(),
+            Self::Block(w) => w.flush(),
+        }
+    }
+}
+
+impl WriteColor for StdoutWriter {
+    fn supports_color(&self) -> bool {
+        match self {
+            Self::Line(w) => w.supports_color(),
+            Self::Block(w) => w.supports_color(),
+        }
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> std::io::Result<()> {
+        match self {
+            Self::Line(w) => w.set_color(spec),
+            Self::Block(w) => w.set_color(spec),
+        }
+    }
+
+    fn reset(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Line(w) => w.reset(),
+            Self::Block(w) => w.reset(),
+        }
+    }
+
+    fn is_synchronous(&self) -> bool {
+        match self {
+            Self::Line(w) => w.is_synchronous(),
+            Self::Block(w) => w.is_synchronous(),
+        }
+    }
+}
+
+/// Collects everything printed into a shared in-memory buffer,
+/// with color sequences suppressed; made for tests.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VecSink {
+    bytes: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+}
+
+#[cfg(test)]
+impl VecSink {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written to this sink so far.
+    pub(crate) fn contents(&self) -> Vec<u8> {
+        self.bytes.lock().expect("Unable to acquire lock.").clone()
+    }
+}
+
+#[cfg(test)]
+impl PrinterSink for VecSink {
+    type Writer = termcolor::NoColor<SharedVecWriter>;
+
+    fn open(&self, _color_choice: ColorChoice) -> Self::Writer {
+        termcolor::NoColor::new(SharedVecWriter(self.bytes.clone()))
+    }
+}
+
+/// The `Write` half of `VecSink`: appends into the shared buffer.
+#[cfg(test)]
+pub(crate) struct SharedVecWriter(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedVecWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .expect("Unable to acquire lock.")
+            .extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::matcher::DummyMatcher;
+    use crate::print::{PrintMessage, PrintableResult, Printer, PrinterSender};
+
+    #[test]
+    fn vec_sink_collects_printed_output() {
+        let sink = VecSink::new();
+        let printer = Printer::new()
+            .with_matcher(DummyMatcher)
+            .group_by_target(false)
+            .build_blocking_with_sink(sink.clone());
+
+        printer.send(PrintMessage::Printable(PrintableResult::new(
+            "target".to_owned(),
+            1,
+            b"hello\n".to_vec(),
+            Vec::new(),
+        )));
+
+        assert_eq!(b"1:hello\n".to_vec(), sink.contents());
+    }
+
+    #[test]
+    fn vec_sink_collects_threaded_output() {
+        let sink = VecSink::new();
+        let (printer, handle) = Printer::new()
+            .with_matcher(DummyMatcher)
+            .group_by_target(false)
+            .spawn_threaded_with_sink(sink.clone());
+
+        printer.send(PrintMessage::Printable(PrintableResult::new(
+            "target".to_owned(),
+            2,
+            b"threaded\n".to_vec(),
+            Vec::new(),
+        )));
+
+        drop(printer);
+        handle.join().expect("Couldn't join printing thread.");
+
+        assert_eq!(b"2:threaded\n".to_vec(), sink.contents());
+    }
+}
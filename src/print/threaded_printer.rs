@@ -1,9 +1,8 @@
-use super::{Config, PrettyPrinter, PrintMessage, PrinterSender};
+use super::{Config, PrettyPrinter, PrintMessage, PrinterSender, PrinterSink};
 use crate::matcher::Matcher;
 use crate::time_log::TimeLog;
 use crossbeam_channel::{Receiver as ChannelReceiver, Sender as ChannelSender};
 use std::time::Instant;
-use termcolor::{ColorChoice, StandardStream};
 
 #[derive(Clone)]
 pub(crate) struct Sender {
@@ -18,45 +17,80 @@ impl Sender {
 
 impl PrinterSender for Sender {
     fn send(&self, message: PrintMessage) {
-        self.sender.send(message).expect("Failed sending message.");
+        // The printer hanging up early (it hit a write error, or
+        // the run is ending) is not the searcher's problem.
+        let _ = self.sender.send(message);
     }
 }
 
 /// A simple printer that can be spawned on a separate thread,
 /// and receive messages to print from the `Sender`.
-pub(super) struct Printer<M: Matcher> {
+pub(super) struct Printer<M: Matcher, S: PrinterSink> {
     receiver: ChannelReceiver<PrintMessage>,
     printer: PrettyPrinter<M>,
+    sink: S,
 }
 
-impl<M: Matcher> Printer<M> {
+impl<M: Matcher, S: PrinterSink> Printer<M, S> {
     pub(super) fn new(
         matcher: Option<M>,
         receiver: ChannelReceiver<PrintMessage>,
         config: Config,
+        sink: S,
     ) -> Self {
         Self {
             receiver,
             printer: PrettyPrinter::new(matcher, config),
+            sink,
         }
     }
 
     pub(super) fn listen(&mut self) -> TimeLog {
-        let stdout = StandardStream::stdout(ColorChoice::Auto);
-        let mut stdout = stdout.lock();
+        let mut stdout = self.sink.open(self.printer.color_choice());
 
         // At first, the instant represents 'spawn-to-first-print'.
         let spawn_to_print_instant = Instant::now();
         let mut time_log = TimeLog::new(spawn_to_print_instant);
-        let mut has_logged = false;
+        let mut first_result_instant = None;
+        let mut output_failed = false;
 
         while let Ok(message) = self.receiver.recv() {
-            if !has_logged {
+            if first_result_instant.is_none() {
                 time_log.log_printer_spawn_to_print();
-                has_logged = true;
+                first_result_instant = Some(Instant::now());
             }
 
-            self.printer.print(&mut stdout, message);
+            // After a write failure the remaining messages are
+            // drained unprinted, so the searchers can finish
+            // instead of blocking on a dead printer.
+            if output_failed {
+                continue;
+            }
+
+            if let Err(e) = self.printer.print(&mut stdout, message) {
+                if !e.is_broken_pipe() {
+                    eprintln!("toygrep: {}", e);
+                }
+
+                output_failed = true;
+                self.printer.cancel_searchers();
+            }
+
+            // The first print has completed by the time the first
+            // message returns from the printer.
+            if let Some(instant) = first_result_instant {
+                if time_log.first_result_to_first_print.is_none() {
+                    time_log.log_first_result_to_first_print(instant);
+                }
+            }
+        }
+
+        if !output_failed {
+            if let Err(e) = self.printer.finish(&mut stdout) {
+                if !e.is_broken_pipe() {
+                    eprintln!("toygrep: {}", e);
+                }
+            }
         }
 
         time_log.log_print_duration();
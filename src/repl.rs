@@ -0,0 +1,177 @@
+//! The exploratory pattern REPL behind `--repl`. Walking a big
+//! tree usually dwarfs the matching itself, so the REPL walks it
+//! exactly once, caches every candidate file that passes the
+//! traversal filters, and then prompts for pattern after pattern,
+//! searching only the cached set. Trying a few regexes against a
+//! large tree goes from a walk per attempt to a walk per session.
+
+use crate::arg_parse::UserInput;
+use crate::buffer::transcode::ForcedEncoding;
+use crate::cancel::CancelToken;
+use crate::matcher::RegexMatcherBuilder;
+use crate::print::{ColorConfig, Printer};
+use crate::search::{ContextLines, SearcherBuilder};
+use crate::target::Target;
+use crate::types::TypeFilter;
+use crate::walker::{Walker, WalkerConfig};
+use async_std::path::PathBuf;
+use std::io::Write;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+/// Runs the REPL to completion, returning the process exit code.
+pub(crate) async fn run(
+    user_input: &UserInput,
+    type_filter: TypeFilter,
+    color_choice: termcolor::ColorChoice,
+    color_config: ColorConfig,
+    encoding: Option<ForcedEncoding>,
+) -> i32 {
+    if !atty::is(atty::Stream::Stdin) {
+        eprintln!("toygrep: --repl needs a terminal to prompt on");
+        return 2;
+    }
+
+    let files = collect_candidates(user_input, type_filter).await;
+
+    if files.is_empty() {
+        eprintln!("toygrep: no files to search after traversal filters");
+        return 1;
+    }
+
+    println!(
+        "cached {} candidate files; enter a pattern, or a blank line to quit",
+        files.len()
+    );
+
+    let targets: Vec<Target> = files.into_iter().map(Target::for_path).collect();
+
+    loop {
+        let pattern = match prompt() {
+            Some(pattern) => pattern,
+            None => return 0,
+        };
+
+        let matcher = RegexMatcherBuilder::new()
+            .for_pattern(&pattern)
+            .case_insensitive(user_input.is_case_insensitive())
+            .match_whole_word(user_input.whole_word)
+            .match_whole_line(user_input.whole_line)
+            .fixed_string(user_input.fixed_strings)
+            .engine(user_input.engine)
+            .build();
+
+        let matcher = match matcher {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                // A bad regex just re-prompts; that's the point
+                // of an exploratory session.
+                eprintln!("toygrep: {}", e);
+                continue;
+            }
+        };
+
+        let printer = Printer::new()
+            .with_matcher(matcher.clone())
+            .group_by_target(true)
+            .color_choice(color_choice)
+            .color_config(color_config.clone())
+            .build_blocking();
+
+        let searcher = SearcherBuilder::new(matcher, printer)
+            .context_lines(ContextLines {
+                before: user_input.before_context,
+                after: user_input.after_context,
+            })
+            .max_match_count(user_input.max_count)
+            .multiline(user_input.multiline)
+            .force_text(user_input.text)
+            .encoding(encoding)
+            .line_terminator(user_input.line_terminator.unwrap_or(b'\n'))
+            .thread_count(user_input.threads)
+            .build();
+
+        match searcher.search(&targets).await {
+            Ok(stats) => println!("({} lines matched)", stats.lines_matched_count),
+            Err(e) => eprintln!("toygrep: {}", e),
+        }
+    }
+}
+
+/// Prints the prompt and reads one pattern; `None` on a blank
+/// line or end of input.
+fn prompt() -> Option<String> {
+    print!("pattern> ");
+    std::io::stdout().flush().ok()?;
+
+    let mut line = String::new();
+
+    if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+        // EOF (Ctrl-D): end the line visually before quitting.
+        println!();
+        return None;
+    }
+
+    let pattern = line.trim_end_matches(['\n', '\r'].as_ref()).to_owned();
+
+    if pattern.is_empty() {
+        None
+    } else {
+        Some(pattern)
+    }
+}
+
+/// Walks every directory target once, collecting the files that
+/// pass the traversal filters; explicit file targets are taken
+/// as-is. This is the cached set each prompted pattern searches.
+async fn collect_candidates(user_input: &UserInput, type_filter: TypeFilter) -> Vec<PathBuf> {
+    let walker_config = WalkerConfig {
+        process_ignore_files: !user_input.no_ignore,
+        type_filter,
+        path_matcher: None,
+        max_depth: user_input.max_depth,
+        min_depth: user_input.min_depth.unwrap_or(0),
+        follow_symlinks: user_input.follow_symlinks,
+        skip_vcs_dirs: !user_input.no_ignore_vcs,
+        cancel_token: CancelToken::new(),
+    };
+
+    let files = Arc::new(Mutex::new(Vec::new()));
+    let sequence_counter = Arc::new(AtomicUsize::new(0));
+
+    for target in &user_input.targets {
+        match target {
+            Target::Stdin => {
+                // Stdin belongs to the prompt in this mode.
+            }
+            Target::Path(path) => {
+                if path.is_dir().await {
+                    let on_file = {
+                        let files = files.clone();
+
+                        move |path: PathBuf, _sequence: usize| {
+                            files.lock().expect("Unable to acquire lock.").push(path);
+                        }
+                    };
+
+                    Walker::new(walker_config.clone())
+                        .worker_count(user_input.threads)
+                        .walk(path, sequence_counter.clone(), on_file)
+                        .await;
+                } else if path.is_file().await {
+                    files
+                        .lock()
+                        .expect("Unable to acquire lock.")
+                        .push(path.clone());
+                } else {
+                    eprintln!("toygrep: {}: no such file or directory", path.display());
+                }
+            }
+        }
+    }
+
+    let mut files = std::mem::take(&mut *files.lock().expect("Unable to acquire lock."));
+    files.sort();
+
+    files
+}
@@ -0,0 +1,438 @@
+//! The `toygrep replace` subcommand: the one place toygrep writes
+//! into the files it searches. Every match of a pattern is
+//! substituted with a template (capture references like `$1`
+//! expand as in `--replace`), and each changed file is rewritten
+//! atomically — the new contents go to a temporary file beside the
+//! original, which then renames over it — so a crash mid-write
+//! never leaves a half-rewritten file. Untouched bytes pass
+//! through verbatim, so the file's encoding survives the rewrite.
+
+use crate::cancel::CancelToken;
+use crate::matcher::{Matcher, RegexMatcherBuilder};
+use crate::types::TypeFilter;
+use crate::walker::{Walker, WalkerConfig};
+use async_std::path::PathBuf;
+use std::io::Write;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+/// The subcommand's own small flag set; everything after the
+/// flags is `PATTERN TEMPLATE [PATHS...]`.
+struct ReplaceInput {
+    pattern: String,
+    template: String,
+    paths: Vec<PathBuf>,
+
+    /// Keep the original of each changed file as `FILE.bak`.
+    backup: bool,
+
+    /// Report what would change without writing anything.
+    dry_run: bool,
+
+    /// Show each proposed change as a line diff and confirm it
+    /// before applying (`y`/`n`/`a`/`q`).
+    interactive: bool,
+
+    case_insensitive: bool,
+    whole_word: bool,
+    fixed_strings: bool,
+}
+
+/// Runs `toygrep replace` over the given arguments (everything
+/// after the subcommand name), returning the process exit code.
+pub(crate) async fn run(args: &[String]) -> i32 {
+    let input = match parse_args(args) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("toygrep: {}", message);
+            return 2;
+        }
+    };
+
+    // The searcher's whole-word wrapper matches (and so would
+    // replace) the delimiter characters around the word; a
+    // rewrite must keep them, so compose a zero-width `\b`
+    // version here instead of asking the builder for word mode.
+    let (pattern, fixed_strings) = if input.whole_word {
+        let escaped = if input.fixed_strings {
+            regex::escape(&input.pattern)
+        } else {
+            input.pattern.clone()
+        };
+
+        (format!(r"\b(?:{})\b", escaped), false)
+    } else {
+        (input.pattern.clone(), input.fixed_strings)
+    };
+
+    let matcher = RegexMatcherBuilder::new()
+        .for_pattern(&pattern)
+        .case_insensitive(input.case_insensitive)
+        .fixed_string(fixed_strings)
+        .build();
+
+    let matcher = match matcher {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("toygrep: {:?}", e);
+            return 2;
+        }
+    };
+
+    if input.interactive && !atty::is(atty::Stream::Stdin) {
+        eprintln!("toygrep: --interactive needs a terminal to prompt on");
+        return 2;
+    }
+
+    let files = collect_files(&input.paths).await;
+
+    let mut files_changed = 0_usize;
+    let mut lines_changed = 0_usize;
+    let mut failed = false;
+
+    // One confirmation state across every file, so `a` and `q`
+    // cover the whole run, not just the file they were typed in.
+    let mut confirm = if input.interactive {
+        Confirm::Ask
+    } else {
+        Confirm::All
+    };
+
+    for path in &files {
+        match replace_in_file(path, &matcher, &input, &mut confirm) {
+            Ok(0) => {}
+            Ok(changed) => {
+                let verb = if input.dry_run {
+                    "would change"
+                } else {
+                    "changed"
+                };
+
+                println!("{}: {} {} lines", path.display(), verb, changed);
+
+                files_changed += 1;
+                lines_changed += changed;
+            }
+            Err(e) => {
+                eprintln!("toygrep: {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+
+        if matches!(confirm, Confirm::Quit) {
+            break;
+        }
+    }
+
+    let verb = if input.dry_run {
+        "would change"
+    } else {
+        "changed"
+    };
+
+    println!(
+        "{} {} lines in {} of {} files",
+        verb,
+        lines_changed,
+        files_changed,
+        files.len()
+    );
+
+    if failed {
+        2
+    } else {
+        0
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<ReplaceInput, String> {
+    let mut input = ReplaceInput {
+        pattern: String::new(),
+        template: String::new(),
+        paths: Vec::new(),
+        backup: false,
+        dry_run: false,
+        interactive: false,
+        case_insensitive: false,
+        whole_word: false,
+        fixed_strings: false,
+    };
+
+    let mut positionals = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--backup" => input.backup = true,
+            "--dry-run" => input.dry_run = true,
+            "--interactive" => input.interactive = true,
+            "-i" | "--ignore-case" => input.case_insensitive = true,
+            "-w" | "--word-regexp" => input.whole_word = true,
+            "-F" | "--fixed-strings" => input.fixed_strings = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unrecognized replace flag: {}", flag));
+            }
+            _ => positionals.push(arg.clone()),
+        }
+    }
+
+    let mut positionals = positionals.into_iter();
+
+    input.pattern = positionals
+        .next()
+        .ok_or("usage: toygrep replace PATTERN TEMPLATE [PATHS...]")?;
+    input.template = positionals
+        .next()
+        .ok_or("usage: toygrep replace PATTERN TEMPLATE [PATHS...]")?;
+    input.paths = positionals.map(Into::into).collect();
+
+    if input.paths.is_empty() {
+        let current_dir = std::env::current_dir()
+            .map_err(|e| format!("unable to access the current directory: {}", e))?;
+
+        input.paths = vec![current_dir.into()];
+    }
+
+    Ok(input)
+}
+
+/// Expands the path arguments into concrete files, walking
+/// directories with the default traversal filters (ignore files
+/// honored, VCS internals skipped).
+pub(crate) async fn collect_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let walker_config = WalkerConfig {
+        process_ignore_files: true,
+        type_filter: TypeFilter::default(),
+        path_matcher: None,
+        max_depth: None,
+        min_depth: 0,
+        follow_symlinks: false,
+        skip_vcs_dirs: true,
+        cancel_token: CancelToken::new(),
+    };
+
+    let files = Arc::new(Mutex::new(Vec::new()));
+    let sequence_counter = Arc::new(AtomicUsize::new(0));
+
+    for path in paths {
+        if path.is_dir().await {
+            let on_file = {
+                let files = files.clone();
+
+                move |path: PathBuf, _sequence: usize| {
+                    files.lock().expect("Unable to acquire lock.").push(path);
+                }
+            };
+
+            Walker::new(walker_config.clone())
+                .walk(path, sequence_counter.clone(), on_file)
+                .await;
+        } else if path.is_file().await {
+            files
+                .lock()
+                .expect("Unable to acquire lock.")
+                .push(path.clone());
+        } else {
+            eprintln!("toygrep: {}: no such file or directory", path.display());
+        }
+    }
+
+    let mut files = std::mem::take(&mut *files.lock().expect("Unable to acquire lock."));
+    files.sort();
+
+    files
+}
+
+/// What `--interactive` has decided so far: still asking per
+/// change, applying the rest unprompted (`a`), or skipping the
+/// rest (`q`). Non-interactive runs start at `All`.
+enum Confirm {
+    Ask,
+    All,
+    Quit,
+}
+
+/// Applies the replacement to one file, returning how many lines
+/// changed. Nothing is written unless at least one line changed
+/// (and never under `--dry-run`).
+fn replace_in_file<M: Matcher>(
+    path: &PathBuf,
+    matcher: &M,
+    input: &ReplaceInput,
+    confirm: &mut Confirm,
+) -> std::io::Result<usize> {
+    let contents = std::fs::read(path)?;
+
+    // The write path won't mangle binaries; a NUL early in the
+    // file is the same heuristic the searcher uses to spot them.
+    if contents[..usize::min(contents.len(), 8192)].contains(&0) {
+        return Ok(0);
+    }
+
+    let mut rewritten = Vec::with_capacity(contents.len());
+    let mut lines_changed = 0_usize;
+
+    for (line_index, line) in split_keeping_terminator(&contents).enumerate() {
+        if matcher.is_match(line) {
+            let replaced = matcher.replace_all(line, input.template.as_bytes());
+
+            if replaced == line {
+                rewritten.extend_from_slice(line);
+                continue;
+            }
+
+            let apply = match confirm {
+                Confirm::All => true,
+                Confirm::Quit => false,
+                Confirm::Ask => {
+                    show_proposed(path, line_index + 1, line, &replaced);
+
+                    match read_choice() {
+                        'y' => true,
+                        'a' => {
+                            *confirm = Confirm::All;
+                            true
+                        }
+                        'q' => {
+                            *confirm = Confirm::Quit;
+                            false
+                        }
+                        _ => false,
+                    }
+                }
+            };
+
+            if apply {
+                lines_changed += 1;
+                rewritten.extend_from_slice(&replaced);
+            } else {
+                rewritten.extend_from_slice(line);
+            }
+        } else {
+            rewritten.extend_from_slice(line);
+        }
+    }
+
+    if lines_changed == 0 || input.dry_run {
+        return Ok(lines_changed);
+    }
+
+    if input.backup {
+        let mut backup_path = path.clone().into_os_string();
+        backup_path.push(".bak");
+        std::fs::copy(path, backup_path)?;
+    }
+
+    write_atomically(path, &rewritten)?;
+
+    Ok(lines_changed)
+}
+
+/// Shows one proposed change as a two-line diff: the current line
+/// in red, the rewritten line in green.
+fn show_proposed(path: &PathBuf, line_num: usize, old: &[u8], new: &[u8]) {
+    use termcolor::WriteColor;
+
+    let mut out = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
+    let trimmed = |bytes: &[u8]| String::from_utf8_lossy(bytes).trim_end().to_owned();
+
+    let _ = writeln!(out, "{}:{}:", path.display(), line_num);
+
+    let mut spec = termcolor::ColorSpec::new();
+
+    let _ = out.set_color(spec.set_fg(Some(termcolor::Color::Red)));
+    let _ = writeln!(out, "-{}", trimmed(old));
+    let _ = out.set_color(spec.set_fg(Some(termcolor::Color::Green)));
+    let _ = writeln!(out, "+{}", trimmed(new));
+    let _ = out.reset();
+}
+
+/// Prompts for one of `y`/`n`/`a`/`q` and reads a single
+/// keypress, raw-mode style, so no Enter is needed. Anything
+/// unrecognized counts as `n`; Esc and Ctrl-C count as `q`.
+fn read_choice() -> char {
+    use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    print!("apply? [y]es [n]o [a]ll [q]uit: ");
+    let _ = std::io::stdout().flush();
+
+    let _ = crossterm::terminal::enable_raw_mode();
+
+    let choice = loop {
+        match crossterm::event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break 'q',
+                KeyCode::Char(c) => break c.to_ascii_lowercase(),
+                KeyCode::Esc => break 'q',
+                _ => {}
+            },
+            Err(_) => break 'q',
+            _ => {}
+        }
+    };
+
+    let _ = crossterm::terminal::disable_raw_mode();
+
+    // Echo the choice; raw mode swallowed the keystroke itself.
+    println!("{}", choice);
+
+    choice
+}
+
+/// Writes the new contents to a temporary file beside the
+/// original — inheriting its permissions — then renames it over
+/// the original, so readers only ever see the old or the new file.
+fn write_atomically(path: &PathBuf, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(format!(".toygrep-tmp.{}", std::process::id()));
+
+    let permissions = std::fs::metadata(path)?.permissions();
+
+    let result = std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::set_permissions(&temp_path, permissions))
+        .and_then(|()| std::fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Splits into lines with each line's terminator attached, so
+/// rejoining the pieces reproduces the file byte for byte.
+fn split_keeping_terminator(contents: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = contents;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let end = match rest.iter().position(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None => rest.len(),
+        };
+
+        let (line, remainder) = rest.split_at(end);
+        rest = remainder;
+
+        Some(line)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_keeps_terminators_and_rejoins_byte_for_byte() {
+        let contents = b"one\ntwo\r\nthree";
+        let lines: Vec<&[u8]> = split_keeping_terminator(contents).collect();
+
+        assert_eq!(
+            vec![b"one\n".as_ref(), b"two\r\n".as_ref(), b"three".as_ref()],
+            lines
+        );
+        assert_eq!(contents.to_vec(), lines.concat());
+    }
+}
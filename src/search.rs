@@ -1,21 +1,231 @@
 use crate::buffer::async_line_buffer::{AsyncLineBufferBuilder, AsyncLineBufferReader};
-use crate::buffer::BufferPool;
+use crate::buffer::chunk_reader::AsyncChunkReader;
+use crate::buffer::transcode::{ForcedEncoding, TranscodingReader};
+use crate::buffer::{BufferPool, BufferPoolBuilder};
+use crate::cancel::CancelToken;
+use crate::dedupe::{ContentDeduper, FileDeduper};
 use crate::error::{Error, Result};
-use crate::matcher::Matcher;
-use crate::print::{PrintMessage, PrintableResult, PrinterSender};
+use crate::matcher::{Match, Matcher, Submatch};
+use crate::print::{PrintMessage, PrintableResult, PrinterSender, TextPool};
 use crate::target::Target;
+use crate::timing::{Phase, TimingCollector};
+use crate::types::TypeFilter;
+use crate::walker::{Walker, WalkerConfig};
 use async_std::fs::{self, File};
 use async_std::io::{BufReader, Read};
-use async_std::path::Path;
+use async_std::path::{Path, PathBuf};
 use async_std::prelude::*;
 use async_std::sync::Arc;
-use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
 // How many bytes must we check to be reasonably sure the input isn't binary?
+// A NUL byte in this prefix marks the file binary (the heuristic grep and
+// Ripgrep use); anything else -- including non-UTF-8 text encodings like
+// Latin-1 -- is searched normally.
 const BINARY_CHECK_LEN_BYTES: usize = 512;
 
+/// The window size used when consuming a file for multiline
+/// search, and how much of each window reappears in the next so
+/// matches spanning a window boundary are still seen whole.
+const MULTILINE_CHUNK_SIZE_BYTES: usize = 8 * (1 << 20);
+const MULTILINE_OVERLAP_BYTES: usize = 64 * (1 << 10);
+
+/// How many files the searcher may hold open at once when the
+/// process's file-descriptor limit cannot be determined.
+const FALLBACK_FD_LIMIT: usize = 256;
+
+/// How many lines of context around each match
+/// should be reported along with the match itself.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ContextLines {
+    pub(crate) before: usize,
+    pub(crate) after: usize,
+}
+
+/// Caps how many file searches run concurrently (`-j`).
+///
+/// Implemented as a bounded channel used as a semaphore: each
+/// running search parks one token in the channel's buffer, and
+/// `acquire` suspends the task once the buffer is full.
+#[derive(Debug, Clone)]
+struct ConcurrencyLimiter {
+    slots: async_std::channel::Sender<()>,
+    returns: async_std::channel::Receiver<()>,
+}
+
+impl ConcurrencyLimiter {
+    fn new(max_concurrent: usize) -> Self {
+        let (slots, returns) = async_std::channel::bounded(usize::max(max_concurrent, 1));
+
+        Self { slots, returns }
+    }
+
+    async fn acquire(&self) {
+        self.slots
+            .send(())
+            .await
+            .expect("Concurrency limiter channel closed unexpectedly.");
+    }
+
+    async fn release(&self) {
+        self.returns
+            .recv()
+            .await
+            .expect("Concurrency limiter channel closed unexpectedly.");
+    }
+}
+
+/// The file property used to order results with `--sort`/`--sortr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SortKey {
+    Path,
+    Modified,
+    Created,
+    Size,
+}
+
+impl SortKey {
+    /// Resolves a `--sort` argument to a key.
+    /// `Err` with the list of known keys on an unknown name.
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "path" => Ok(SortKey::Path),
+            "modified" => Ok(SortKey::Modified),
+            "created" => Ok(SortKey::Created),
+            "size" => Ok(SortKey::Size),
+            _ => Err(Error::InvalidFlagValue {
+                flag: "--sort",
+                value: name.to_owned(),
+                expected: "path, modified, created, or size".to_owned(),
+            }),
+        }
+    }
+}
+
+/// Per-search options threaded down to the individual reader searches.
+#[derive(Debug, Default, Clone)]
+struct SearchConfig {
+    context: ContextLines,
+
+    /// Stop reading a target once this many lines have matched.
+    max_count: Option<usize>,
+
+    /// Allow patterns to match across line boundaries,
+    /// which requires searching whole files instead of lines.
+    multiline: bool,
+
+    /// When true, the very first match anywhere cancels
+    /// all remaining searching (e.g. quiet mode).
+    cancel_on_first_match: bool,
+
+    /// Checked throughout the search to stop in-flight work early.
+    cancel_token: CancelToken,
+
+    /// Remembers every physical file searched so far, so a file
+    /// reachable twice (symlinks, overlapping targets) is searched
+    /// only once.
+    dedupe: FileDeduper,
+
+    /// Present with `--dedupe-contents`: skips files whose size
+    /// and content hash match an already-searched file.
+    dedupe_contents: Option<ContentDeduper>,
+
+    /// Honor `.gitignore`/`.ignore`/`.toygrepignore` files
+    /// encountered during directory traversal.
+    process_ignore_files: bool,
+
+    /// Restricts which files are searched by type (`-t`/`-T`).
+    type_filter: TypeFilter,
+
+    /// When set, only files whose path matches are searched
+    /// (`--path-regex`).
+    path_matcher: Option<crate::matcher::AnyMatcher>,
+
+    /// Don't descend past this many levels below the search root
+    /// (files directly in the root are at depth 1).
+    max_depth: Option<usize>,
+
+    /// Skip files shallower than this many levels below the root.
+    min_depth: usize,
+
+    /// Follow symlinked files and directories during traversal.
+    follow_symlinks: bool,
+
+    /// Skip `.git`/`.hg`/`.svn` directories during traversal.
+    skip_vcs_dirs: bool,
+
+    /// Print the name of every file that would be searched,
+    /// without opening it (`--files`).
+    list_files_only: bool,
+
+    /// Order results by this property, searching files one at a
+    /// time instead of concurrently so the order is deterministic.
+    sort: Option<SortKey>,
+
+    /// Reverse the `sort` ordering.
+    sort_reverse: bool,
+
+    /// How many traversal workers and concurrent searches to run;
+    /// `None` uses one per available core.
+    thread_count: Option<usize>,
+
+    /// Present when `thread_count` is set; gates each file search
+    /// on a free slot.
+    limiter: Option<ConcurrencyLimiter>,
+
+    /// Gates file opens so a very large tree cannot exhaust the
+    /// process's file descriptors; queued searches wait for a
+    /// slot instead of erroring.
+    fd_limiter: Option<ConcurrencyLimiter>,
+
+    /// Transparently decompress compressed files and search their
+    /// contents (`-z`), reporting matches against the archive path.
+    search_compressed: bool,
+
+    /// Descend into zip/tar/jar archives and search each member,
+    /// reporting matches against `archive!inner/path`.
+    search_archives: bool,
+
+    /// Search files the binary (NUL byte) check would skip (`-a`).
+    force_text: bool,
+
+    /// Decode inputs from this encoding instead of sniffing for
+    /// a UTF-16 BOM (`--encoding`).
+    encoding: Option<ForcedEncoding>,
+
+    /// The byte records are split on; `\n` unless overridden
+    /// (`--null-data`/`--line-terminator`).
+    line_terminator: u8,
+
+    /// Byte buffers for match text on its way to the printer,
+    /// recycled after printing to spare a per-line allocation.
+    text_pool: TextPool,
+
+    /// How many line buffers the pool preallocates (`--buffer-count`).
+    buffer_count: Option<usize>,
+
+    /// The initial size of each line buffer, in bytes (`--buffer-size`).
+    buffer_size: Option<usize>,
+
+    /// Shrink grown buffers back to their starting size when they
+    /// return to the pool (`--buffer-shrink`).
+    buffer_shrink: bool,
+
+    /// Record a per-file timing entry for every searched file
+    /// (`--stats-files N`; the N itself is applied at report time).
+    stats_files: Option<usize>,
+
+    /// Aggregate per-extension counters for every searched file
+    /// (`--stats-by-type`).
+    stats_by_type: bool,
+
+    /// The shared sink for structured phase timing spans.
+    timing: TimingCollector,
+}
+
 pub(crate) mod stats {
+    use std::collections::HashMap;
     use std::time::Duration;
 
     #[derive(Debug, Default)]
@@ -24,15 +234,15 @@ pub(crate) mod stats {
         /// Includes skipped non-utf8 files.
         pub(crate) total_files_visited: usize,
 
-        /// Count of files skipped as non-utf8.
+        /// Count of files skipped as binary (a NUL byte in the leading bytes).
         /// For stats coming from "single file level" reads, this is 1
         /// if the file was skipped or 0 if it was not.
         /// Coming from "aggregate" reads, this will be the count of all
         /// files skiped at that level of aggregation.
-        pub(crate) skipped_files_non_utf8: usize,
+        pub(crate) skipped_files_binary: usize,
 
-        /// How many bytes were checked to determine the file is or is not utf8.
-        pub(crate) non_utf8_bytes_checked: usize,
+        /// How many bytes were checked for binary (NUL byte) detection.
+        pub(crate) binary_bytes_checked: usize,
 
         /// Count of lines that matched during reading.
         pub(crate) lines_matched_count: usize,
@@ -43,6 +253,15 @@ pub(crate) mod stats {
         /// The duration of time spent recursing through the filesystem.
         pub(crate) filesystem_walk_dur: Duration,
 
+        /// Total bytes of content actually run through the matcher.
+        pub(crate) total_bytes_searched: usize,
+
+        /// Directories processed during traversal.
+        pub(crate) directories_visited: usize,
+
+        /// Files and directories skipped by ignore rules.
+        pub(crate) files_skipped_by_ignore: usize,
+
         /// The duration of time spent searching a reader.
         /// Might be an aggregated time (if the returning method searches multiple readers)
         /// or a time for only one reader (if the returning method only searches one reader).
@@ -51,19 +270,81 @@ pub(crate) mod stats {
         pub(crate) max_buffer_size: usize,
 
         pub(crate) buffers_created: usize,
+
+        /// The buffer pool's peak aggregate memory, in bytes.
+        pub(crate) peak_pool_bytes: usize,
+
+        /// Print payload buffers freshly allocated (not recycled).
+        pub(crate) text_allocations: usize,
+
+        /// One entry per searched file, recorded only when the
+        /// slowest-files report was requested (`--stats-files`).
+        pub(crate) file_timings: Vec<FileTiming>,
+
+        /// Per-extension aggregates, recorded only when the
+        /// by-type report was requested (`--stats-by-type`).
+        pub(crate) by_type: HashMap<String, TypeStats>,
+
+        /// Paths that could not be opened or walked, with the
+        /// reason, reported to stderr unless `--no-messages`.
+        pub(crate) read_errors: Vec<(String, String)>,
+
+        /// Files skipped because the same physical file (by
+        /// device and inode) was already searched.
+        pub(crate) files_deduped: usize,
+
+        /// Files skipped because their contents were byte-identical
+        /// to an already-searched file (`--dedupe-contents`), paired
+        /// with the path of the file they duplicate.
+        pub(crate) content_duplicates: Vec<(String, String)>,
+    }
+
+    /// What one file extension contributed to the search results.
+    #[derive(Debug, Default, Clone, Copy)]
+    pub(crate) struct TypeStats {
+        pub(crate) files: usize,
+        pub(crate) matches: usize,
+        pub(crate) bytes: usize,
+    }
+
+    /// How long one file took to search, and how large it was;
+    /// the raw material of the `--stats-files` report.
+    #[derive(Debug, Clone)]
+    pub(crate) struct FileTiming {
+        pub(crate) path: String,
+        pub(crate) search_dur: Duration,
+        pub(crate) bytes: usize,
     }
 
     impl ReadStats {
         pub(super) fn fold_in(&mut self, other: &ReadStats) {
             self.total_files_visited += other.total_files_visited;
-            self.skipped_files_non_utf8 += other.skipped_files_non_utf8;
-            self.non_utf8_bytes_checked += other.non_utf8_bytes_checked;
+            self.skipped_files_binary += other.skipped_files_binary;
+            self.binary_bytes_checked += other.binary_bytes_checked;
             self.lines_matched_count += other.lines_matched_count;
             self.lines_matched_bytes += other.lines_matched_bytes;
             self.filesystem_walk_dur += other.filesystem_walk_dur;
+            self.total_bytes_searched += other.total_bytes_searched;
+            self.directories_visited += other.directories_visited;
+            self.files_skipped_by_ignore += other.files_skipped_by_ignore;
             self.reader_search_dur += other.reader_search_dur;
             self.max_buffer_size = usize::max(self.max_buffer_size, other.max_buffer_size);
             self.buffers_created += other.buffers_created;
+            self.peak_pool_bytes = usize::max(self.peak_pool_bytes, other.peak_pool_bytes);
+            self.text_allocations += other.text_allocations;
+            self.file_timings.extend(other.file_timings.iter().cloned());
+
+            for (extension, other_stats) in &other.by_type {
+                let entry = self.by_type.entry(extension.clone()).or_default();
+                entry.files += other_stats.files;
+                entry.matches += other_stats.matches;
+                entry.bytes += other_stats.bytes;
+            }
+
+            self.read_errors.extend(other.read_errors.iter().cloned());
+            self.files_deduped += other.files_deduped;
+            self.content_duplicates
+                .extend(other.content_duplicates.iter().cloned());
         }
     }
 }
@@ -75,6 +356,35 @@ where
 {
     matcher: M,
     printer: P,
+    context: ContextLines,
+    max_count: Option<usize>,
+    multiline: bool,
+    cancel_on_first_match: bool,
+    cancel_token: CancelToken,
+    process_ignore_files: bool,
+    type_filter: TypeFilter,
+    path_matcher: Option<crate::matcher::AnyMatcher>,
+    max_depth: Option<usize>,
+    min_depth: usize,
+    follow_symlinks: bool,
+    skip_vcs_dirs: bool,
+    list_files_only: bool,
+    sort: Option<SortKey>,
+    sort_reverse: bool,
+    thread_count: Option<usize>,
+    max_open_files: Option<usize>,
+    search_compressed: bool,
+    search_archives: bool,
+    dedupe_contents: bool,
+    force_text: bool,
+    encoding: Option<ForcedEncoding>,
+    line_terminator: u8,
+    buffer_count: Option<usize>,
+    buffer_size: Option<usize>,
+    buffer_shrink: bool,
+    stats_files: Option<usize>,
+    stats_by_type: bool,
+    timing: TimingCollector,
 }
 
 impl<M, P> SearcherBuilder<M, P>
@@ -83,11 +393,287 @@ where
     P: PrinterSender + Sync,
 {
     pub(crate) fn new(matcher: M, printer: P) -> SearcherBuilder<M, P> {
-        Self { matcher, printer }
+        Self {
+            matcher,
+            printer,
+            context: ContextLines::default(),
+            max_count: None,
+            multiline: false,
+            cancel_on_first_match: false,
+            cancel_token: CancelToken::new(),
+            process_ignore_files: true,
+            type_filter: TypeFilter::default(),
+            path_matcher: None,
+            max_depth: None,
+            min_depth: 0,
+            follow_symlinks: false,
+            skip_vcs_dirs: true,
+            list_files_only: false,
+            sort: None,
+            sort_reverse: false,
+            thread_count: None,
+            max_open_files: None,
+            search_compressed: false,
+            search_archives: false,
+            dedupe_contents: false,
+            force_text: false,
+            encoding: None,
+            line_terminator: b'\n',
+            buffer_count: None,
+            buffer_size: None,
+            buffer_shrink: false,
+            stats_files: None,
+            stats_by_type: false,
+            timing: TimingCollector::new(),
+        }
+    }
+
+    pub(crate) fn context_lines(mut self, context: ContextLines) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Stop reading each target as soon as one matching line is found,
+    /// e.g. when only the names of matching files are wanted.
+    pub(crate) fn stop_after_first_match(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.max_count = Some(1);
+        }
+
+        self
+    }
+
+    /// Stop reading each target after `max` matching lines.
+    pub(crate) fn max_match_count(mut self, max: Option<usize>) -> Self {
+        if max.is_some() {
+            self.max_count = max;
+        }
+
+        self
+    }
+
+    /// Allow patterns to match across line boundaries.
+    pub(crate) fn multiline(mut self, enabled: bool) -> Self {
+        self.multiline = enabled;
+        self
+    }
+
+    /// Cancel the entire search as soon as any line matches,
+    /// e.g. for quiet mode, where only the exit status matters.
+    pub(crate) fn quit_after_first_match(mut self, enabled: bool) -> Self {
+        self.cancel_on_first_match = enabled;
+        self
+    }
+
+    /// Use the given token for cancellation, so the caller
+    /// can observe (or request) cancellation externally.
+    pub(crate) fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = token;
+        self
+    }
+
+    /// Honor ignore files during traversal (the default);
+    /// disabled by `--no-ignore`.
+    pub(crate) fn process_ignore_files(mut self, enabled: bool) -> Self {
+        self.process_ignore_files = enabled;
+        self
+    }
+
+    /// Restrict which files are searched by type (`-t`/`-T`).
+    pub(crate) fn type_filter(mut self, filter: TypeFilter) -> Self {
+        self.type_filter = filter;
+        self
+    }
+
+    /// Search only files whose path matches (`--path-regex`).
+    pub(crate) fn path_matcher(mut self, matcher: Option<crate::matcher::AnyMatcher>) -> Self {
+        self.path_matcher = matcher;
+        self
+    }
+
+    /// Don't descend past `max` levels below the search root.
+    pub(crate) fn max_depth(mut self, max: Option<usize>) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    /// Skip files shallower than `min` levels below the root.
+    pub(crate) fn min_depth(mut self, min: Option<usize>) -> Self {
+        self.min_depth = min.unwrap_or(0);
+        self
+    }
+
+    /// Follow symlinked files and directories during traversal.
+    pub(crate) fn follow_symlinks(mut self, enabled: bool) -> Self {
+        self.follow_symlinks = enabled;
+        self
+    }
+
+    /// Skip VCS bookkeeping directories (the default);
+    /// disabled by `--no-ignore-vcs`.
+    pub(crate) fn skip_vcs_dirs(mut self, enabled: bool) -> Self {
+        self.skip_vcs_dirs = enabled;
+        self
+    }
+
+    /// Only list the files that would be searched (`--files`),
+    /// bypassing the match loop entirely.
+    pub(crate) fn list_files_only(mut self, enabled: bool) -> Self {
+        self.list_files_only = enabled;
+        self
+    }
+
+    /// Order results by the given key, if any (`--sort`).
+    pub(crate) fn sort_by(mut self, key: Option<SortKey>) -> Self {
+        self.sort = key;
+        self
+    }
+
+    /// Reverse the `--sort` ordering (`--sortr`).
+    pub(crate) fn sort_reverse(mut self, enabled: bool) -> Self {
+        self.sort_reverse = enabled;
+        self
+    }
+
+    /// Run this many traversal workers and concurrent file
+    /// searches, instead of one per available core (`-j`).
+    pub(crate) fn thread_count(mut self, count: Option<usize>) -> Self {
+        self.thread_count = count;
+        self
+    }
+
+    /// Cap how many files may be held open at once; the default
+    /// derives from the process's file-descriptor limit.
+    pub(crate) fn max_open_files(mut self, max: Option<usize>) -> Self {
+        if max.is_some() {
+            self.max_open_files = max;
+        }
+
+        self
+    }
+
+    /// Transparently decompress compressed files and search
+    /// their contents (`-z`).
+    pub(crate) fn search_compressed(mut self, enabled: bool) -> Self {
+        self.search_compressed = enabled;
+        self
+    }
+
+    /// Descend into zip/tar/jar archives and search each member
+    /// (`--search-archives`).
+    pub(crate) fn search_archives(mut self, enabled: bool) -> Self {
+        self.search_archives = enabled;
+        self
+    }
+
+    /// Skip files byte-identical to an already-searched file
+    /// (`--dedupe-contents`).
+    pub(crate) fn dedupe_contents(mut self, enabled: bool) -> Self {
+        self.dedupe_contents = enabled;
+        self
+    }
+
+    /// Search files even when the binary check would skip them
+    /// (`-a`/`--text`).
+    pub(crate) fn force_text(mut self, enabled: bool) -> Self {
+        self.force_text = enabled;
+        self
+    }
+
+    /// Decode inputs from the given encoding instead of relying
+    /// on BOM detection (`--encoding`).
+    pub(crate) fn encoding(mut self, encoding: Option<ForcedEncoding>) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Split records on the given byte instead of `\n`
+    /// (`--null-data`/`--line-terminator`).
+    pub(crate) fn line_terminator(mut self, terminator: u8) -> Self {
+        self.line_terminator = terminator;
+        self
+    }
+
+    /// Preallocate this many line buffers in the pool
+    /// (`--buffer-count`).
+    pub(crate) fn buffer_count(mut self, count: Option<usize>) -> Self {
+        self.buffer_count = count;
+        self
+    }
+
+    /// Start each line buffer at this many bytes (`--buffer-size`).
+    pub(crate) fn buffer_size(mut self, size: Option<usize>) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Shrink grown buffers back down when they return to the pool
+    /// (`--buffer-shrink`).
+    pub(crate) fn buffer_shrink(mut self, enabled: bool) -> Self {
+        self.buffer_shrink = enabled;
+        self
+    }
+
+    pub(crate) fn stats_files(mut self, count: Option<usize>) -> Self {
+        self.stats_files = count;
+        self
+    }
+
+    pub(crate) fn stats_by_type(mut self, enabled: bool) -> Self {
+        self.stats_by_type = enabled;
+        self
+    }
+
+    /// Report phase timings into the given collector instead of a
+    /// private one, so they can be combined with the printer's.
+    pub(crate) fn timing(mut self, collector: TimingCollector) -> Self {
+        self.timing = collector;
+        self
     }
 
     pub(crate) fn build(self) -> Searcher<M, P> {
-        Searcher::new(self.matcher, self.printer)
+        let config = SearchConfig {
+            context: self.context,
+            max_count: self.max_count,
+            multiline: self.multiline,
+            cancel_on_first_match: self.cancel_on_first_match,
+            cancel_token: self.cancel_token,
+            dedupe: FileDeduper::default(),
+            process_ignore_files: self.process_ignore_files,
+            type_filter: self.type_filter,
+            path_matcher: self.path_matcher,
+            max_depth: self.max_depth,
+            min_depth: self.min_depth,
+            follow_symlinks: self.follow_symlinks,
+            skip_vcs_dirs: self.skip_vcs_dirs,
+            list_files_only: self.list_files_only,
+            sort: self.sort,
+            sort_reverse: self.sort_reverse,
+            thread_count: self.thread_count,
+            limiter: self.thread_count.map(ConcurrencyLimiter::new),
+            fd_limiter: Some(ConcurrencyLimiter::new(
+                self.max_open_files.unwrap_or_else(default_fd_limit),
+            )),
+            search_compressed: self.search_compressed,
+            search_archives: self.search_archives,
+            dedupe_contents: if self.dedupe_contents {
+                Some(ContentDeduper::default())
+            } else {
+                None
+            },
+            force_text: self.force_text,
+            encoding: self.encoding,
+            line_terminator: self.line_terminator,
+            text_pool: TextPool::new(),
+            buffer_count: self.buffer_count,
+            buffer_size: self.buffer_size,
+            buffer_shrink: self.buffer_shrink,
+            stats_files: self.stats_files,
+            stats_by_type: self.stats_by_type,
+            timing: self.timing,
+        };
+
+        Searcher::new(self.matcher, self.printer, config)
     }
 }
 
@@ -98,6 +684,7 @@ where
 {
     matcher: M,
     printer: P,
+    config: SearchConfig,
 }
 
 impl<M, P> Searcher<M, P>
@@ -105,37 +692,213 @@ where
     M: Matcher + Sync + 'static,
     P: PrinterSender + Sync + 'static,
 {
-    fn new(matcher: M, printer: P) -> Self {
-        Self { matcher, printer }
+    fn new(matcher: M, printer: P, config: SearchConfig) -> Self {
+        Self {
+            matcher,
+            printer,
+            config,
+        }
+    }
+
+    /// A buffer pool configured from this search's settings.
+    fn build_buffer_pool(&self) -> BufferPool {
+        BufferPoolBuilder::new()
+            .line_break_byte(self.config.line_terminator)
+            .prealloc_count(self.config.buffer_count)
+            .start_size_bytes(self.config.buffer_size)
+            .shrink_on_return(self.config.buffer_shrink)
+            .build()
     }
 
     pub(crate) async fn search(&'_ self, targets: &'_ [Target]) -> Result<stats::ReadStats> {
-        use async_crawl::{AsyncCrawler, Crawler};
-        // let crawler = async_crawl::singlethread_crawler::make_crawler();
-        // let crawler = async_crawl::async_scaled_crawler::make_crawler(6);
-        let crawler = async_crawl::async_recursive_crawler::make_crawler();
+        let buf_pool = Arc::new(self.build_buffer_pool());
+        let mut agg_stats = stats::ReadStats::default();
+
+        // A mistyped target must produce an error and a non-zero
+        // exit, not a silently empty (and "successful") search.
+        // The valid targets are still searched before the bad ones
+        // are reported.
+        let error_paths: Vec<String> = {
+            let mut error_paths = Vec::new();
 
-        let target = &targets[0];
+            for target in targets {
+                if let Target::Path(path) = target {
+                    if !path.exists().await {
+                        error_paths.push(format!("{}", path.display()));
+                    }
+                }
+            }
 
-        let path = match target {
-            Target::Path(pathbuf) => pathbuf,
-            Target::Stdin => panic!("Stdin not supported right now."),
+            error_paths
         };
 
-        let path: std::path::PathBuf = path.into();
+        // Every searched target gets a discovery index, so the
+        // printer's sequenced mode can reproduce discovery order.
+        let sequence_counter = Arc::new(AtomicUsize::new(0));
 
-        let buf_pool = Arc::new(BufferPool::new());
-        let printer = self.printer.clone();
-        let matcher = self.matcher.clone();
+        for target in targets {
+            let printer = self.printer.clone();
+            let matcher = self.matcher.clone();
+            let config = self.config.clone();
 
-        crawler
-            .crawl(&path, move |p| async move {
-                // dbg!("Crawling path.");
-                Searcher::search_file(&p.path(), matcher, printer, buf_pool).await;
-            })
-            .await;
+            let stats = match target {
+                Target::Stdin => {
+                    let file_rdr = match self.config.encoding {
+                        Some(encoding) => TranscodingReader::forced(
+                            BufReader::new(async_std::io::stdin()),
+                            encoding,
+                        ),
+                        None => TranscodingReader::new(BufReader::new(async_std::io::stdin())),
+                    };
+                    let mut line_buf_builder = AsyncLineBufferBuilder::new()
+                        .with_line_break_byte(self.config.line_terminator);
+
+                    if let Some(size) = self.config.buffer_size {
+                        line_buf_builder = line_buf_builder.with_start_size_bytes(size);
+                    }
+
+                    let line_buf = line_buf_builder.build();
+
+                    let mut line_rdr = AsyncLineBufferReader::new(file_rdr, line_buf)
+                        .line_nums(false)
+                        .retain_lines(self.config.context.before);
+
+                    Searcher::search_via_reader(
+                        matcher,
+                        &mut line_rdr,
+                        Some(target.display_name()),
+                        sequence_counter.fetch_add(1, Ordering::Relaxed),
+                        printer,
+                        config,
+                    )
+                    .await
+                }
+                Target::Path(path) => {
+                    if !path.exists().await {
+                        continue;
+                    }
+
+                    if path.is_dir().await {
+                        // An explicitly named directory that can't be
+                        // opened is a hard error, not a silent skip.
+                        if let Err(e) = fs::read_dir(path).await {
+                            return Err(Error::WalkError {
+                                path: path.display().to_string(),
+                                reason: e.to_string(),
+                            });
+                        }
+
+                        Searcher::search_directory(
+                            path,
+                            matcher,
+                            printer,
+                            buf_pool.clone(),
+                            config,
+                            sequence_counter.clone(),
+                        )
+                        .await
+                    } else {
+                        // An explicitly named target is read even if it is a
+                        // special file (FIFO, device, ...).
+                        Searcher::search_file(
+                            path,
+                            matcher,
+                            printer,
+                            buf_pool.clone(),
+                            config,
+                            sequence_counter.fetch_add(1, Ordering::Relaxed),
+                            true,
+                        )
+                        .await
+                    }
+                }
+            };
 
-        Ok(stats::ReadStats::default())
+            agg_stats.fold_in(&stats);
+        }
+
+        agg_stats.peak_pool_bytes = buf_pool.peak_bytes();
+        agg_stats.text_allocations = self.config.text_pool.allocations();
+
+        if !error_paths.is_empty() {
+            return Err(Error::TargetsNotFound(error_paths));
+        }
+
+        Ok(agg_stats)
+    }
+
+    /// Searches exactly the files named in the given list
+    /// (`-` reads the list from stdin), one path per line or,
+    /// with `nul_separated`, per NUL byte. No traversal or
+    /// ignore processing happens; every listed path is treated
+    /// as an explicitly named target.
+    pub(crate) async fn search_files_from(
+        &self,
+        source: &str,
+        nul_separated: bool,
+    ) -> Result<stats::ReadStats> {
+        let content = if source == "-" {
+            let mut content = Vec::new();
+
+            async_std::io::stdin()
+                .read_to_end(&mut content)
+                .await
+                .map_err(|source_err| Error::Io {
+                    path: source.to_owned(),
+                    source: source_err,
+                })?;
+
+            content
+        } else {
+            fs::read(source).await.map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Error::TargetsNotFound(vec![source.to_owned()]),
+                _ => Error::Io {
+                    path: source.to_owned(),
+                    source: e,
+                },
+            })?
+        };
+
+        let separator = if nul_separated { b'\0' } else { b'\n' };
+
+        let buf_pool = Arc::new(self.build_buffer_pool());
+        let mut agg_stats = stats::ReadStats::default();
+        let mut spawned_tasks = vec![];
+
+        let mut sequence = 0usize;
+
+        for line in content.split(|&b| b == separator) {
+            let line = trim_line_terminator(line, b'\n');
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let path = PathBuf::from(String::from_utf8_lossy(line).to_string());
+
+            let matcher = self.matcher.clone();
+            let printer = self.printer.clone();
+            let buf_pool = buf_pool.clone();
+            let config = self.config.clone();
+
+            let task = async_std::task::spawn(async move {
+                Searcher::search_file(&path, matcher, printer, buf_pool, config, sequence, true)
+                    .await
+            });
+
+            sequence += 1;
+            spawned_tasks.push(task);
+        }
+
+        for task in spawned_tasks {
+            let read_stats = task.await;
+            agg_stats.fold_in(&read_stats);
+        }
+
+        agg_stats.peak_pool_bytes = buf_pool.peak_bytes();
+        agg_stats.text_allocations = self.config.text_pool.allocations();
+
+        Ok(agg_stats)
     }
 
     /// Given some `Target`s, search them using the given `Matcher`
@@ -147,7 +910,9 @@ where
 
         let mut error_paths = Vec::new();
 
-        let buf_pool = Arc::new(BufferPool::new());
+        let buf_pool = Arc::new(self.build_buffer_pool());
+
+        let sequence_counter = Arc::new(AtomicUsize::new(0));
 
         for target in targets {
             let matcher = self.matcher.clone();
@@ -155,19 +920,58 @@ where
 
             let stats = match target {
                 Target::Stdin => {
-                    let file_rdr = BufReader::new(async_std::io::stdin());
-                    let line_buf = AsyncLineBufferBuilder::new().build();
-
-                    let mut line_rdr =
-                        AsyncLineBufferReader::new(file_rdr, line_buf).line_nums(false);
+                    let file_rdr = match self.config.encoding {
+                        Some(encoding) => TranscodingReader::forced(
+                            BufReader::new(async_std::io::stdin()),
+                            encoding,
+                        ),
+                        None => TranscodingReader::new(BufReader::new(async_std::io::stdin())),
+                    };
+                    let mut line_buf_builder = AsyncLineBufferBuilder::new()
+                        .with_line_break_byte(self.config.line_terminator);
+
+                    if let Some(size) = self.config.buffer_size {
+                        line_buf_builder = line_buf_builder.with_start_size_bytes(size);
+                    }
 
-                    Searcher::search_via_reader(matcher, &mut line_rdr, None, printer.clone()).await
+                    let line_buf = line_buf_builder.build();
+
+                    let mut line_rdr = AsyncLineBufferReader::new(file_rdr, line_buf)
+                        .line_nums(false)
+                        .retain_lines(self.config.context.before);
+
+                    Searcher::search_via_reader(
+                        matcher,
+                        &mut line_rdr,
+                        Some(target.display_name()),
+                        sequence_counter.fetch_add(1, Ordering::Relaxed),
+                        printer.clone(),
+                        self.config.clone(),
+                    )
+                    .await
                 }
                 Target::Path(path) => {
                     if path.is_file().await {
-                        Searcher::search_file(path, matcher, printer, buf_pool.clone()).await
+                        Searcher::search_file(
+                            path,
+                            matcher,
+                            printer,
+                            buf_pool.clone(),
+                            self.config.clone(),
+                            sequence_counter.fetch_add(1, Ordering::Relaxed),
+                            true,
+                        )
+                        .await
                     } else if path.is_dir().await {
-                        Searcher::search_directory(path, matcher, printer, buf_pool.clone()).await
+                        Searcher::search_directory(
+                            path,
+                            matcher,
+                            printer,
+                            buf_pool.clone(),
+                            self.config.clone(),
+                            sequence_counter.clone(),
+                        )
+                        .await
                     } else {
                         error_paths.push(format!("{}", path.display()));
                         stats::ReadStats::default()
@@ -179,6 +983,8 @@ where
         }
 
         agg_stats.buffers_created = buf_pool.pool_size().await;
+        agg_stats.peak_pool_bytes = buf_pool.peak_bytes();
+        agg_stats.text_allocations = self.config.text_pool.allocations();
 
         if error_paths.is_empty() {
             Ok(agg_stats)
@@ -191,7 +997,9 @@ where
         matcher: M,
         buffer: &mut AsyncLineBufferReader<R>,
         name: Option<String>,
+        sequence: usize,
         printer: P,
+        config: SearchConfig,
     ) -> stats::ReadStats
     where
         R: Read + std::marker::Unpin,
@@ -206,35 +1014,154 @@ where
         // This is the lowest level of granularity -- we are searching 1 file.
         stats.total_files_visited = 1;
 
+        // When nonzero, the next lines are reported as "after" context.
+        let mut after_budget = 0usize;
+
         let name = name.unwrap_or_default();
-        while let Some(line_result) = buffer.read_line().await {
-            if binary_bytes_checked < BINARY_CHECK_LEN_BYTES {
+
+        // Read and match time is accumulated locally per line and
+        // reported to the collector once, at the end of the file.
+        let mut read_nanos = 0u64;
+        let mut match_nanos = 0u64;
+
+        loop {
+            let read_start = Instant::now();
+            let line_result = match buffer.read_line().await {
+                Some(line_result) => line_result,
+                None => break,
+            };
+            read_nanos += read_start.elapsed().as_nanos() as u64;
+
+            if config.cancel_token.is_cancelled() {
+                break;
+            }
+
+            stats.total_bytes_searched += line_result.len();
+
+            if !config.force_text && binary_bytes_checked < BINARY_CHECK_LEN_BYTES {
                 binary_bytes_checked += line_result.text().len();
-                if !check_utf8(line_result.text()) {
-                    stats.non_utf8_bytes_checked = binary_bytes_checked;
-                    stats.skipped_files_non_utf8 = 1;
+                // The record terminator itself (NUL, in
+                // `--null-data` mode) must not count as binary.
+                if is_binary(trim_line_terminator(
+                    line_result.text(),
+                    config.line_terminator,
+                )) {
+                    stats.binary_bytes_checked = binary_bytes_checked;
+                    stats.skipped_files_binary = 1;
+
+                    // If the scanned prefix matched the pattern,
+                    // the file isn't dropped silently: a grep-style
+                    // notice is printed in place of its results.
+                    if stats.lines_matched_count > 0
+                        || matcher.is_match(trim_line_terminator(
+                            line_result.text(),
+                            config.line_terminator,
+                        ))
+                    {
+                        printer.send(PrintMessage::BinaryFileMatches {
+                            target_name: name,
+                            sequence,
+                        });
+                    }
+
                     return stats;
                 }
             }
 
-            if matcher.is_match(line_result.text()) {
+            // Match against the line without its terminator,
+            // so anchored patterns (`-x`, `$`) behave as expected.
+            let match_start = Instant::now();
+            let is_match = matcher.is_match(trim_line_terminator(
+                line_result.text(),
+                config.line_terminator,
+            ));
+            match_nanos += match_start.elapsed().as_nanos() as u64;
+
+            if is_match {
                 stats.lines_matched_count += 1;
-                stats.lines_matched_bytes += line_result.text().len();
+                stats.lines_matched_bytes += line_result.len();
+
+                // The spans are computed once here, so printers
+                // downstream never re-run the matcher on the line.
+                let spans = matcher.find_submatches(trim_line_terminator(
+                    line_result.text(),
+                    config.line_terminator,
+                ));
+
+                let line_num = line_result.line_num();
+                let byte_offset = line_result.byte_offset();
+
+                // The payload buffer is on loan from the pool; it
+                // comes back once the printer is done with it.
+                let mut text = config.text_pool.acquire();
+                text.extend_from_slice(line_result.text());
+                normalize_terminator(&mut text, config.line_terminator);
+
+                // The reader retained owned copies of the lines
+                // leading up to this one; report them as "before"
+                // context, then forget them so they print only once.
+                for (ctx_line_num, ctx_offset, mut ctx_text) in buffer.take_retained() {
+                    normalize_terminator(&mut ctx_text, config.line_terminator);
+
+                    printer.send(PrintMessage::Printable(
+                        PrintableResult::context(name.clone(), ctx_line_num, ctx_text)
+                            .with_sequence(sequence)
+                            .with_byte_offset(ctx_offset),
+                    ));
+                }
+
+                after_budget = config.context.after;
+
+                let printable = PrintableResult::new(name.clone(), line_num, text, spans)
+                    .with_sequence(sequence)
+                    .with_byte_offset(byte_offset);
+                printer.send(PrintMessage::Printable(printable));
+
+                if config.cancel_on_first_match {
+                    config.cancel_token.cancel();
+                    break;
+                }
+
+                if let Some(max) = config.max_count {
+                    if stats.lines_matched_count >= max {
+                        break;
+                    }
+                }
+            } else if after_budget > 0 {
+                after_budget -= 1;
+
+                let mut text = config.text_pool.acquire();
+                text.extend_from_slice(line_result.text());
+                normalize_terminator(&mut text, config.line_terminator);
+
+                let printable =
+                    PrintableResult::context(name.clone(), line_result.line_num(), text)
+                        .with_sequence(sequence)
+                        .with_byte_offset(line_result.byte_offset());
+
+                // This line printed as "after" context; it must not
+                // resurface later as retained "before" context.
+                buffer.clear_retained();
 
-                let printable = PrintableResult::new(
-                    name.clone(),
-                    line_result.line_num(),
-                    line_result.text().into(),
-                );
                 printer.send(PrintMessage::Printable(printable));
             }
         }
 
-        printer.send(PrintMessage::EndOfReading { target_name: name });
+        printer.send(PrintMessage::EndOfReading {
+            target_name: name,
+            sequence,
+        });
 
         drop(printer);
 
-        stats.non_utf8_bytes_checked = binary_bytes_checked;
+        config
+            .timing
+            .record(Phase::Read, std::time::Duration::from_nanos(read_nanos));
+        config
+            .timing
+            .record(Phase::Match, std::time::Duration::from_nanos(match_nanos));
+
+        stats.binary_bytes_checked = binary_bytes_checked;
         stats.reader_search_dur = start.elapsed();
         stats.max_buffer_size = buffer.inner_buf_len();
 
@@ -246,27 +1173,280 @@ where
         matcher: M,
         printer: P,
         buf_pool: Arc<BufferPool>,
+        config: SearchConfig,
+        sequence: usize,
+        is_explicit_target: bool,
+    ) -> stats::ReadStats {
+        let limiter = config.limiter.clone();
+        let fd_limiter = config.fd_limiter.clone();
+
+        if let Some(limiter) = &limiter {
+            limiter.acquire().await;
+        }
+
+        // The file-descriptor slot is held for the whole search,
+        // covering the `File::open` (or whole-file read) below.
+        if let Some(fd_limiter) = &fd_limiter {
+            fd_limiter.acquire().await;
+        }
+
+        let record_file_timing = config.stats_files.is_some();
+        let record_type_stats = config.stats_by_type;
+
+        let mut search_result = Searcher::search_file_limited(
+            path,
+            matcher,
+            printer,
+            buf_pool,
+            config,
+            sequence,
+            is_explicit_target,
+        )
+        .await;
+
+        if record_file_timing {
+            let bytes = fs::metadata(path)
+                .await
+                .map(|meta| meta.len() as usize)
+                .unwrap_or(0);
+
+            search_result.file_timings.push(stats::FileTiming {
+                path: path.display().to_string(),
+                search_dur: search_result.reader_search_dur,
+                bytes,
+            });
+        }
+
+        if record_type_stats {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "(none)".to_owned());
+
+            let entry = search_result.by_type.entry(extension).or_default();
+            entry.files += 1;
+            entry.matches += search_result.lines_matched_count;
+            entry.bytes += search_result.total_bytes_searched;
+        }
+
+        if let Some(fd_limiter) = &fd_limiter {
+            fd_limiter.release().await;
+        }
+
+        if let Some(limiter) = &limiter {
+            limiter.release().await;
+        }
+
+        search_result
+    }
+
+    /// The body of `search_file`, run while holding a concurrency
+    /// slot when `-j` caps how many searches run at once.
+    async fn search_file_limited(
+        path: &Path,
+        matcher: M,
+        printer: P,
+        buf_pool: Arc<BufferPool>,
+        config: SearchConfig,
+        sequence: usize,
+        is_explicit_target: bool,
     ) -> stats::ReadStats {
-        let file = {
-            let f = File::open(path).await;
+        if config.cancel_token.is_cancelled() {
+            return stats::ReadStats::default();
+        }
+
+        // Opening a special file (FIFO, socket, device) can block
+        // the task forever, so only explicitly named targets are
+        // allowed to be anything but a regular file. The same
+        // metadata also provides the buffer size hint below.
+        let meta = fs::metadata(path).await.ok();
+
+        if !is_explicit_target {
+            match &meta {
+                Some(meta) if meta.is_file() => {}
+                _ => return stats::ReadStats::default(),
+            }
+        }
+
+        // The same physical file can be reached more than once --
+        // through a followed symlink, or through overlapping
+        // targets like `dir` and `dir/sub`. Only its first
+        // sighting is searched.
+        if let Some(meta) = &meta {
+            if !config.dedupe.first_visit(path.as_ref(), meta) {
+                let mut stats = stats::ReadStats::default();
+                stats.files_deduped = 1;
 
-            if let Ok(f) = f {
-                f
+                return stats;
+            }
+        }
+
+        // With `--dedupe-contents`, a file byte-identical to one
+        // already searched (by size plus content hash) is skipped
+        // too; the hashing pass shares the chunked-read shape of
+        // the search itself.
+        if let (Some(deduper), Some(meta)) = (&config.dedupe_contents, &meta) {
+            if meta.is_file() {
+                if let Some(original) = deduper.duplicate_of(path.as_ref(), meta.len()).await {
+                    let mut stats = stats::ReadStats::default();
+                    stats
+                        .content_duplicates
+                        .push((path.display().to_string(), original));
+
+                    return stats;
+                }
+            }
+        }
+
+        // Sizing the buffer from the file's length lets a small
+        // file be read in exactly one fill, without huge files
+        // over-allocating (the hint is clamped to the buffer cap).
+        let size_hint = meta.as_ref().map(|meta| meta.len() as usize);
+
+        // In listing mode the file passed every traversal filter,
+        // which is all we wanted to know; report it unopened. The
+        // pattern (if any) runs against the path rather than the
+        // contents, which is what `--files-with-name` lists.
+        if config.list_files_only {
+            let mut stats = stats::ReadStats::default();
+            stats.total_files_visited = 1;
+
+            let name = path.display().to_string();
+
+            if matcher.is_match(name.as_bytes()) {
+                printer.send(PrintMessage::Display(format!("{}\n", name)));
+            }
+
+            return stats;
+        }
+
+        // An archive is read in full and each of its members is
+        // searched as a synthetic file. This dispatch comes first
+        // so that with both flags a `.tar.gz` is treated as an
+        // archive rather than a decompressed blob of tar headers.
+        if config.search_archives && crate::archive::is_archive(path.as_ref()) {
+            return Searcher::search_archive_file(
+                path, matcher, printer, buf_pool, config, sequence,
+            )
+            .await;
+        }
+
+        // A compressed file is inflated in full, then its contents
+        // are searched like an ordinary file's.
+        if config.search_compressed {
+            if let Some(decoder) = crate::decompress::decoder_for(path.as_ref()) {
+                return Searcher::search_compressed_file(
+                    path, matcher, printer, buf_pool, config, sequence, decoder,
+                )
+                .await;
+            }
+        }
+
+        if config.multiline {
+            return Searcher::search_file_multiline(path, matcher, printer, config, sequence).await;
+        }
+
+        let file = match File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                let mut stats = stats::ReadStats::default();
+                stats
+                    .read_errors
+                    .push((path.display().to_string(), e.to_string()));
+
+                return stats;
+            }
+        };
+
+        // The transcoding layer turns UTF-16 input (sniffed by
+        // BOM, or forced via `--encoding`) into UTF-8 before line
+        // splitting; everything else passes through untouched.
+        let rdr = match config.encoding {
+            Some(encoding) => TranscodingReader::forced(BufReader::new(file), encoding),
+            None => TranscodingReader::new(BufReader::new(file)),
+        };
+
+        let line_buf = buf_pool.acquire(size_hint).await;
+
+        let mut line_buf_rdr = AsyncLineBufferReader::new(rdr, line_buf)
+            .line_nums(true)
+            .retain_lines(config.context.before);
+
+        let target_name = Some(path.to_string_lossy().to_string());
+
+        let search_result = Searcher::search_via_reader(
+            matcher,
+            &mut line_buf_rdr,
+            target_name,
+            sequence,
+            printer,
+            config,
+        )
+        .await;
+
+        buf_pool
+            .return_to_pool(line_buf_rdr.take_line_buffer())
+            .await;
+
+        search_result
+    }
+
+    /// Decompresses the given file in full with the decoder
+    /// registered for its extension and searches the inflated
+    /// contents, reporting matches against the archive path.
+    /// A file whose magic bytes don't match its extension, or
+    /// that fails to decompress, is skipped silently, like an
+    /// unreadable one.
+    #[allow(clippy::too_many_arguments)]
+    async fn search_compressed_file(
+        path: &Path,
+        matcher: M,
+        printer: P,
+        buf_pool: Arc<BufferPool>,
+        config: SearchConfig,
+        sequence: usize,
+        decoder: &'static dyn crate::decompress::DecoderFactory,
+    ) -> stats::ReadStats {
+        let compressed = {
+            if let Ok(compressed) = fs::read(path).await {
+                compressed
             } else {
                 return stats::ReadStats::default();
             }
         };
 
-        let rdr = BufReader::new(file);
+        if !decoder.sniff(&compressed) {
+            return stats::ReadStats::default();
+        }
+
+        let decompressed = {
+            if let Ok(decompressed) = decoder.decode(&compressed) {
+                decompressed
+            } else {
+                return stats::ReadStats::default();
+            }
+        };
+
+        let size_hint = decompressed.len();
+        let rdr = async_std::io::Cursor::new(decompressed);
 
-        let line_buf = buf_pool.acquire().await;
+        let line_buf = buf_pool.acquire(Some(size_hint)).await;
 
-        let mut line_buf_rdr = AsyncLineBufferReader::new(rdr, line_buf).line_nums(true);
+        let mut line_buf_rdr = AsyncLineBufferReader::new(rdr, line_buf)
+            .line_nums(true)
+            .retain_lines(config.context.before);
 
         let target_name = Some(path.to_string_lossy().to_string());
 
-        let search_result =
-            Searcher::search_via_reader(matcher, &mut line_buf_rdr, target_name, printer).await;
+        let search_result = Searcher::search_via_reader(
+            matcher,
+            &mut line_buf_rdr,
+            target_name,
+            sequence,
+            printer,
+            config,
+        )
+        .await;
 
         buf_pool
             .return_to_pool(line_buf_rdr.take_line_buffer())
@@ -275,76 +1455,433 @@ where
         search_result
     }
 
-    /// Given a directory path, descend down the whole tree,
-    /// performing a search on every file found,
-    /// and recursively visiting descendant directories.
-    ///
-    /// Note: this is a major simplification compared to how
-    /// Ripgrep works. I believe it is possibly the biggest contributor
-    /// to why Toygrep is much slower than Ripgrep on some workloads.
-    ///
-    /// Toygrep's current appraoch is this (as seen in the code below):
-    /// An outermost loop, on one thread, descends through every directory,
-    /// and fires off an async task whenever a file is encountered.
-    ///
-    /// In comparison, Ripgrip will spawn some number of workers, and they share
-    /// a global queue of directories to visit, and descend independently of any outer loop.
-    async fn search_directory(
-        directory_path: &Path,
+    /// Reads the given archive in full and searches every member
+    /// as its own synthetic file, named `archive!inner/path`. All
+    /// members share the archive's discovery index, so grouped
+    /// output keeps an archive's results together. An unreadable
+    /// or malformed archive is skipped silently, like an
+    /// unreadable file.
+    async fn search_archive_file(
+        path: &Path,
         matcher: M,
         printer: P,
         buf_pool: Arc<BufferPool>,
+        config: SearchConfig,
+        sequence: usize,
+    ) -> stats::ReadStats {
+        let archive_bytes = {
+            if let Ok(archive_bytes) = fs::read(path).await {
+                archive_bytes
+            } else {
+                return stats::ReadStats::default();
+            }
+        };
+
+        let entries = {
+            if let Ok(entries) = crate::archive::entries(path.as_ref(), &archive_bytes) {
+                entries
+            } else {
+                return stats::ReadStats::default();
+            }
+        };
+
+        let mut stats = stats::ReadStats::default();
+
+        for entry in entries {
+            let size_hint = entry.contents.len();
+            let rdr = async_std::io::Cursor::new(entry.contents);
+
+            let line_buf = buf_pool.acquire(Some(size_hint)).await;
+
+            let mut line_buf_rdr = AsyncLineBufferReader::new(rdr, line_buf)
+                .line_nums(true)
+                .retain_lines(config.context.before);
+
+            let target_name = Some(format!("{}!{}", path.display(), entry.name));
+
+            let member_stats = Searcher::search_via_reader(
+                matcher.clone(),
+                &mut line_buf_rdr,
+                target_name,
+                sequence,
+                printer.clone(),
+                config.clone(),
+            )
+            .await;
+
+            buf_pool
+                .return_to_pool(line_buf_rdr.take_line_buffer())
+                .await;
+
+            stats.fold_in(&member_stats);
+        }
+
+        stats
+    }
+
+    /// Searches an entire file at once, so patterns may match
+    /// across line boundaries. Line numbers for each match are
+    /// recovered by counting line terminators up to the match start.
+    async fn search_file_multiline(
+        path: &Path,
+        matcher: M,
+        printer: P,
+        config: SearchConfig,
+        sequence: usize,
     ) -> stats::ReadStats {
+        let mut stats = stats::ReadStats::default();
+        stats.total_files_visited = 1;
+
         let start = Instant::now();
 
-        let mut agg_stats = stats::ReadStats::default();
+        let file = match File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                stats
+                    .read_errors
+                    .push((path.display().to_string(), e.to_string()));
 
-        let mut dir_stack = vec![];
+                return stats;
+            }
+        };
 
-        dir_stack.push(directory_path.to_path_buf());
+        // UTF-16 content (sniffed by BOM, or any encoding forced
+        // via `--encoding`) is transcoded on the fly, exactly as
+        // on the line-wise path.
+        let rdr = match config.encoding {
+            Some(encoding) => TranscodingReader::forced(BufReader::new(file), encoding),
+            None => TranscodingReader::new(BufReader::new(file)),
+        };
 
-        let mut spawned_tasks = vec![];
+        // The file is consumed as large overlapping chunks rather
+        // than one whole-file buffer, so a multi-GB target doesn't
+        // need multi-GB of memory. A match longer than the chunk
+        // overlap may be missed at a chunk boundary.
+        let mut chunks = AsyncChunkReader::new(rdr)
+            .chunk_size_bytes(MULTILINE_CHUNK_SIZE_BYTES)
+            .overlap_bytes(MULTILINE_OVERLAP_BYTES);
+
+        let name = path.to_string_lossy().to_string();
+
+        // Tracks the (absolute) end of the last reported line span,
+        // so overlapping matches are not reported twice.
+        let mut last_span_end = 0;
+
+        let mut binary_checked = false;
+
+        'chunks: while let Some(chunk) = chunks.read_chunk().await {
+            let content = chunk.bytes();
+
+            // Overlap bytes were already counted with the
+            // previous chunk.
+            stats.total_bytes_searched += content.len() - chunk.fresh_from();
+
+            if !binary_checked && !config.force_text {
+                binary_checked = true;
+
+                let check_len = usize::min(content.len(), BINARY_CHECK_LEN_BYTES);
+                stats.binary_bytes_checked = check_len;
+                if is_binary(&content[..check_len]) {
+                    stats.skipped_files_binary = 1;
+
+                    if matcher.is_match(&content[..check_len]) {
+                        printer.send(PrintMessage::BinaryFileMatches {
+                            target_name: name.clone(),
+                            sequence,
+                        });
+                    }
 
-        while let Some(dir_path) = dir_stack.pop() {
-            let mut dir_children = {
-                if let Ok(children) = fs::read_dir(dir_path).await {
-                    children
-                } else {
+                    return stats;
+                }
+            }
+
+            for found in matcher.find_matches(content) {
+                if config.cancel_token.is_cancelled() {
+                    break 'chunks;
+                }
+
+                // A match wholly inside the carried-over bytes was
+                // already reported against the previous chunk.
+                if chunk.fresh_from() > 0 && found.stop <= chunk.fresh_from() {
                     continue;
                 }
-            };
 
-            while let Some(Ok(dir_entry)) = dir_children.next().await {
-                let meta = dir_entry.metadata().await.unwrap();
+                if chunk.byte_offset() + found.start < last_span_end {
+                    continue;
+                }
 
-                if meta.is_file() {
-                    let printer = printer.clone();
-                    let matcher = matcher.clone();
-                    let buf_pool = buf_pool.clone();
+                // The span reported is the whole line(s) containing the match.
+                let span_start = match content[..found.start].iter().rposition(|&b| b == b'\n') {
+                    Some(idx) => idx + 1,
+                    None => 0,
+                };
 
-                    let task = async_std::task::spawn(async move {
-                        let dir_child_path: &Path = &dir_entry.path();
-                        Searcher::search_file(dir_child_path, matcher, printer, buf_pool).await
-                    });
+                let span_end = match content[found.stop..].iter().position(|&b| b == b'\n') {
+                    Some(idx) => found.stop + idx + 1,
+                    None => content.len(),
+                };
 
-                    spawned_tasks.push(task);
-                } else if meta.is_dir() {
-                    dir_stack.push(dir_entry.path());
+                let line_num = chunk.line_num_at(span_start);
+
+                stats.lines_matched_count += 1;
+                stats.lines_matched_bytes += span_end - span_start;
+
+                // The span is made relative to the reported line(s);
+                // captures are not recovered on this path.
+                let relative = Submatch {
+                    span: Match {
+                        start: found.start - span_start,
+                        stop: usize::min(found.stop, span_end) - span_start,
+                    },
+                    captures: Vec::new(),
+                };
+
+                let mut text = config.text_pool.acquire();
+                text.extend_from_slice(&content[span_start..span_end]);
+
+                let printable = PrintableResult::new(name.clone(), line_num, text, vec![relative])
+                    .with_sequence(sequence);
+                printer.send(PrintMessage::Printable(printable));
+
+                if config.cancel_on_first_match {
+                    config.cancel_token.cancel();
+                    break 'chunks;
                 }
+
+                if let Some(max) = config.max_count {
+                    if stats.lines_matched_count >= max {
+                        break 'chunks;
+                    }
+                }
+
+                last_span_end = chunk.byte_offset() + span_end;
             }
         }
 
+        printer.send(PrintMessage::EndOfReading {
+            target_name: name,
+            sequence,
+        });
+
+        stats.reader_search_dur = start.elapsed();
+
+        stats
+    }
+
+    /// Given a directory path, walk the whole tree below it with
+    /// a pool of parallel workers (see the `walker` module),
+    /// performing a search on every file found.
+    async fn search_directory(
+        directory_path: &Path,
+        matcher: M,
+        printer: P,
+        buf_pool: Arc<BufferPool>,
+        config: SearchConfig,
+        sequence_counter: Arc<AtomicUsize>,
+    ) -> stats::ReadStats {
+        let start = Instant::now();
+
+        let mut agg_stats = stats::ReadStats::default();
+
+        let walker_config = WalkerConfig {
+            process_ignore_files: config.process_ignore_files,
+            type_filter: config.type_filter.clone(),
+            path_matcher: config.path_matcher.clone(),
+            max_depth: config.max_depth,
+            min_depth: config.min_depth,
+            follow_symlinks: config.follow_symlinks,
+            skip_vcs_dirs: config.skip_vcs_dirs,
+            cancel_token: config.cancel_token.clone(),
+        };
+
+        // Search tasks spawned by the walker's workers as they
+        // discover files.
+        let spawned_tasks = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // When sorting, results must arrive at the printer in key
+        // order, so files are collected during the walk and
+        // searched one at a time afterwards instead of concurrently.
+        let sorted_files = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let on_file = {
+            let spawned_tasks = spawned_tasks.clone();
+            let sorted_files = sorted_files.clone();
+            let matcher = matcher.clone();
+            let printer = printer.clone();
+            let buf_pool = buf_pool.clone();
+            let config = config.clone();
+
+            move |path: PathBuf, sequence: usize| {
+                if config.sort.is_some() {
+                    sorted_files
+                        .lock()
+                        .expect("Unable to acquire lock.")
+                        .push(path);
+                    return;
+                }
+
+                let matcher = matcher.clone();
+                let printer = printer.clone();
+                let buf_pool = buf_pool.clone();
+                let config = config.clone();
+
+                let task = async_std::task::spawn(async move {
+                    Searcher::search_file(
+                        &path, matcher, printer, buf_pool, config, sequence, false,
+                    )
+                    .await
+                });
+
+                spawned_tasks
+                    .lock()
+                    .expect("Unable to acquire lock.")
+                    .push(task);
+            }
+        };
+
+        let crawl_span = config.timing.span(Phase::Crawl);
+
+        let walk_stats = Walker::new(walker_config)
+            .worker_count(config.thread_count)
+            .walk(directory_path, sequence_counter.clone(), on_file)
+            .await;
+
+        drop(crawl_span);
+
         agg_stats.filesystem_walk_dur = start.elapsed();
+        agg_stats.directories_visited = walk_stats.directories_visited;
+        agg_stats.files_skipped_by_ignore = walk_stats.files_skipped_by_ignore;
+        agg_stats.read_errors.extend(walk_stats.errors);
+
+        let spawned_tasks =
+            std::mem::take(&mut *spawned_tasks.lock().expect("Unable to acquire lock."));
 
         for task in spawned_tasks {
             let read_stats = task.await;
             agg_stats.fold_in(&read_stats);
         }
 
+        if let Some(key) = config.sort {
+            let mut sorted_files =
+                std::mem::take(&mut *sorted_files.lock().expect("Unable to acquire lock."));
+
+            sort_paths(&mut sorted_files, key, config.sort_reverse).await;
+
+            for path in &sorted_files {
+                let read_stats = Searcher::search_file(
+                    path,
+                    matcher.clone(),
+                    printer.clone(),
+                    buf_pool.clone(),
+                    config.clone(),
+                    sequence_counter.fetch_add(1, Ordering::Relaxed),
+                    false,
+                )
+                .await;
+
+                agg_stats.fold_in(&read_stats);
+            }
+        }
+
         agg_stats
     }
 }
 
-fn check_utf8(bytes: &[u8]) -> bool {
-    std::str::from_utf8(bytes).is_ok()
+/// Orders the given paths ascending by the sort key (descending
+/// for `--sortr`). Metadata-based keys use the path itself as a
+/// tiebreaker, and a file whose metadata is unavailable sorts first.
+async fn sort_paths(paths: &mut [PathBuf], key: SortKey, reverse: bool) {
+    let mut keyed: Vec<(u128, PathBuf)> = Vec::with_capacity(paths.len());
+
+    for path in paths.iter() {
+        let meta = fs::metadata(path).await.ok();
+
+        let sort_value = match (key, meta) {
+            (SortKey::Path, _) | (_, None) => 0,
+            (SortKey::Size, Some(meta)) => u128::from(meta.len()),
+            (SortKey::Modified, Some(meta)) => system_time_key(meta.modified().ok()),
+            (SortKey::Created, Some(meta)) => system_time_key(meta.created().ok()),
+        };
+
+        keyed.push((sort_value, path.clone()));
+    }
+
+    keyed.sort_by(|(a_key, a_path), (b_key, b_path)| (a_key, a_path).cmp(&(b_key, b_path)));
+
+    if reverse {
+        keyed.reverse();
+    }
+
+    for (slot, (_, path)) in paths.iter_mut().zip(keyed) {
+        *slot = path;
+    }
+}
+
+/// A conservative cap on concurrently open files, derived from
+/// the process's soft file-descriptor limit with headroom left
+/// for stdio, directory handles, and the like.
+fn default_fd_limit() -> usize {
+    soft_fd_limit().map_or(FALLBACK_FD_LIMIT, |limit| usize::max(limit / 2, 16))
+}
+
+/// The soft `ulimit -n` of the current process.
+#[cfg(target_os = "linux")]
+fn soft_fd_limit() -> Option<usize> {
+    let limits = std::fs::read_to_string("/proc/self/limits").ok()?;
+
+    let line = limits.lines().find(|l| l.starts_with("Max open files"))?;
+
+    // "Max open files  <soft>  <hard>  files"
+    line.split_whitespace().nth(3)?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn soft_fd_limit() -> Option<usize> {
+    None
+}
+
+/// Nanoseconds since the epoch for the given timestamp,
+/// or zero when the filesystem doesn't report one.
+fn system_time_key(time: Option<std::time::SystemTime>) -> u128 {
+    time.and_then(|t| t.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos())
+}
+
+/// Whether the given leading bytes mark the input as binary.
+/// Like grep, a NUL byte is the tell; a non-UTF-8 text encoding
+/// (e.g. Latin-1) never contains one, so such files stay searchable.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// Rewrites a trailing custom record terminator to `\n`, so
+/// printers echo one record per output line even in
+/// `--null-data` mode.
+fn normalize_terminator(text: &mut [u8], terminator: u8) {
+    if terminator != b'\n' {
+        if let Some(last) = text.last_mut() {
+            if *last == terminator {
+                *last = b'\n';
+            }
+        }
+    }
+}
+
+/// Strips a trailing record terminator from the given line, if
+/// present. For the default `\n` terminator this also strips a
+/// preceding `\r`, so CRLF input behaves like LF input.
+fn trim_line_terminator(line: &[u8], terminator: u8) -> &[u8] {
+    let line = match line.last() {
+        Some(&b) if b == terminator => &line[..line.len() - 1],
+        _ => line,
+    };
+
+    if terminator != b'\n' {
+        return line;
+    }
+
+    match line.last() {
+        Some(b'\r') => &line[..line.len() - 1],
+        _ => line,
+    }
 }
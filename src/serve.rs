@@ -0,0 +1,490 @@
+//! The JSON-RPC server behind `--serve`: one warm process that
+//! editor plugins can query per keystroke instead of spawning
+//! toygrep each time. Requests arrive as JSON-RPC objects, one
+//! per stdin line; match events stream back as notifications,
+//! followed by a response carrying the request's id. The walked
+//! file list is cached per path set, so repeat searches over the
+//! same tree skip the directory traversal entirely.
+//!
+//! The protocol, by example:
+//!
+//! ```text
+//! -> {"jsonrpc":"2.0","id":1,"method":"search",
+//!     "params":{"pattern":"foo","paths":["src"],
+//!               "options":{"case_insensitive":true}}}
+//! <- {"jsonrpc":"2.0","method":"match",
+//!     "params":{"id":1,"path":"src/main.rs","line_number":3,"text":"..."}}
+//! <- {"jsonrpc":"2.0","id":1,"result":{"matched_lines":1}}
+//! -> {"jsonrpc":"2.0","id":2,"method":"shutdown"}
+//! <- {"jsonrpc":"2.0","id":2,"result":null}
+//! ```
+
+use crate::matcher::RegexMatcherBuilder;
+use crate::print::json_string;
+use crate::print::PrintMessage;
+use crate::search::SearcherBuilder;
+use crate::target::Target;
+use crate::ui::CollectingSender;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Runs the server until stdin closes or a `shutdown` request
+/// arrives, returning the process exit code.
+pub(crate) async fn run() -> i32 {
+    let stdin = std::io::stdin();
+    let mut cache: HashMap<Vec<String>, Vec<Target>> = HashMap::new();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match parse(&line) {
+            Ok(request) => request,
+            Err(reason) => {
+                respond_error("null", -32700, &format!("parse error: {}", reason));
+                continue;
+            }
+        };
+
+        let id = render_id(request.get("id"));
+
+        match request.get("method").and_then(Json::as_str) {
+            Some("search") => handle_search(&id, request.get("params"), &mut cache).await,
+            Some("shutdown") => {
+                println!(r#"{{"jsonrpc":"2.0","id":{},"result":null}}"#, id);
+                return 0;
+            }
+            Some(method) => {
+                respond_error(&id, -32601, &format!("unknown method: {}", method));
+            }
+            None => {
+                respond_error(&id, -32600, "request has no method");
+            }
+        }
+    }
+
+    0
+}
+
+async fn handle_search(
+    id: &str,
+    params: Option<&Json>,
+    cache: &mut HashMap<Vec<String>, Vec<Target>>,
+) {
+    let pattern = match params.and_then(|p| p.get("pattern")).and_then(Json::as_str) {
+        Some(pattern) => pattern,
+        None => {
+            respond_error(id, -32602, "params.pattern is required");
+            return;
+        }
+    };
+
+    let paths: Vec<String> = params
+        .and_then(|p| p.get("paths"))
+        .and_then(Json::as_array)
+        .map(|paths| {
+            paths
+                .iter()
+                .filter_map(|p| p.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_else(|| vec![".".to_owned()]);
+
+    let options = params.and_then(|p| p.get("options"));
+    let option = |name: &str| {
+        options
+            .and_then(|o| o.get(name))
+            .and_then(Json::as_bool)
+            .unwrap_or(false)
+    };
+
+    let matcher = RegexMatcherBuilder::new()
+        .for_pattern(pattern)
+        .case_insensitive(option("case_insensitive"))
+        .match_whole_word(option("whole_word"))
+        .fixed_string(option("fixed_strings"))
+        .build();
+
+    let matcher = match matcher {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            respond_error(id, -32602, &format!("{:?}", e));
+            return;
+        }
+    };
+
+    // The expensive part a warm process is for: the walk happens
+    // once per path set, and every later search reuses its files.
+    let targets = match cache.get(&paths) {
+        Some(targets) => targets,
+        None => {
+            let roots: Vec<async_std::path::PathBuf> =
+                paths.iter().map(|p| p.clone().into()).collect();
+
+            let files = crate::replace::collect_files(&roots).await;
+
+            cache
+                .entry(paths.clone())
+                .or_insert(files.into_iter().map(Target::for_path).collect())
+        }
+    };
+
+    let printer = CollectingSender::new();
+
+    let searcher = SearcherBuilder::new(matcher, printer.clone()).build();
+
+    let stats = match searcher.search(targets).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            respond_error(id, -32000, &format!("{}", e));
+            return;
+        }
+    };
+
+    for message in printer.into_messages() {
+        if let PrintMessage::Printable(printable) = message {
+            if printable.is_context() {
+                continue;
+            }
+
+            println!(
+                r#"{{"jsonrpc":"2.0","method":"match","params":{{"id":{},"path":{},"line_number":{},"text":{}}}}}"#,
+                id,
+                json_string(printable.target_name().as_bytes()),
+                printable.line_num(),
+                json_string(printable.text_lossy().trim_end().as_bytes())
+            );
+        }
+    }
+
+    println!(
+        r#"{{"jsonrpc":"2.0","id":{},"result":{{"matched_lines":{}}}}}"#,
+        id, stats.lines_matched_count
+    );
+}
+
+fn respond_error(id: &str, code: i32, message: &str) {
+    println!(
+        r#"{{"jsonrpc":"2.0","id":{},"error":{{"code":{},"message":{}}}}}"#,
+        id,
+        code,
+        json_string(message.as_bytes())
+    );
+}
+
+/// The request's id rendered back out for the response: a number
+/// or string as given, `null` for anything else.
+fn render_id(id: Option<&Json>) -> String {
+    match id {
+        Some(Json::Num(n)) if n.fract() == 0.0 => format!("{}", *n as i64),
+        Some(Json::Num(n)) => format!("{}", n),
+        Some(Json::Str(s)) => json_string(s.as_bytes()),
+        _ => "null".to_owned(),
+    }
+}
+
+/// A parsed JSON value. Just enough JSON for the request side of
+/// the protocol; the response side is formatted directly.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+impl Json {
+    /// The named member, for objects.
+    fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.get(name),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Parses one JSON document, rejecting trailing garbage.
+fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.pos != parser.bytes.len() {
+        return Err(format!("trailing characters at offset {}", parser.pos));
+    }
+
+    Ok(value)
+}
+
+/// A recursive-descent parser over the raw bytes.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.peek()? {
+            b'{' => self.object(),
+            b'[' => self.array(),
+            b'"' => self.string().map(Json::Str),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'n' => self.literal("null", Json::Null),
+            b'-' | b'0'..=b'9' => self.number(),
+            other => Err(format!("unexpected character '{}'", other as char)),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut members = HashMap::new();
+
+        self.skip_whitespace();
+
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(b':')?;
+
+            members.insert(name, self.value()?);
+
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                other => return Err(format!("expected ',' or '}}', found '{}'", other as char)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.peek()? == b']' {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                other => return Err(format!("expected ',' or ']', found '{}'", other as char)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+
+        loop {
+            match self.next()? {
+                b'"' => return Ok(out),
+                b'\\' => match self.next()? {
+                    b'"' => out.push('"'),
+                    b'\\' => out.push('\\'),
+                    b'/' => out.push('/'),
+                    b'b' => out.push('\u{8}'),
+                    b'f' => out.push('\u{c}'),
+                    b'n' => out.push('\n'),
+                    b'r' => out.push('\r'),
+                    b't' => out.push('\t'),
+                    b'u' => out.push(self.unicode_escape()?),
+                    other => return Err(format!("bad escape '\\{}'", other as char)),
+                },
+                // Multi-byte utf8 passes through; the input was a
+                // &str, so the bytes are already valid.
+                other => {
+                    let start = self.pos - 1;
+                    let len = utf8_len(other);
+                    self.pos = start + len;
+
+                    out.push_str(std::str::from_utf8(&self.bytes[start..self.pos]).unwrap());
+                }
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = 0_u32;
+
+        for _ in 0..4 {
+            let digit = (self.next()? as char)
+                .to_digit(16)
+                .ok_or_else(|| "bad \\u escape".to_owned())?;
+
+            code = code * 16 + digit;
+        }
+
+        // Surrogate halves (and nothing else) fail from_u32; the
+        // replacement character is good enough for a search tool.
+        Ok(char::from_u32(code).unwrap_or('\u{fffd}'))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+
+        while let Some(&b) = self.bytes.get(self.pos) {
+            match b {
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9' => self.pos += 1,
+                _ => break,
+            }
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .unwrap()
+            .parse()
+            .map(Json::Num)
+            .map_err(|e| format!("bad number: {}", e))
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(format!("expected '{}'", word))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Result<u8, String> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| "unexpected end of input".to_owned())
+    }
+
+    fn next(&mut self) -> Result<u8, String> {
+        let b = self.peek()?;
+        self.pos += 1;
+
+        Ok(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.next()? == expected {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", expected as char))
+        }
+    }
+}
+
+/// How many bytes the utf8 sequence starting with this byte has.
+fn utf8_len(first: u8) -> usize {
+    match first {
+        b if b >= 0b1111_0000 => 4,
+        b if b >= 0b1110_0000 => 3,
+        b if b >= 0b1100_0000 => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requests_parse_into_nested_values() {
+        let request = parse(
+            r#"{"jsonrpc":"2.0","id":7,"method":"search",
+               "params":{"pattern":"a\"b","paths":["src",".."],
+                         "options":{"case_insensitive":true}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(Some("search"), request.get("method").and_then(Json::as_str));
+        assert_eq!(Some(&Json::Num(7.0)), request.get("id"));
+
+        let params = request.get("params").unwrap();
+
+        assert_eq!(Some("a\"b"), params.get("pattern").and_then(Json::as_str));
+        assert_eq!(
+            Some(2),
+            params.get("paths").and_then(Json::as_array).map(<[_]>::len)
+        );
+        assert_eq!(
+            Some(true),
+            params
+                .get("options")
+                .and_then(|o| o.get("case_insensitive"))
+                .and_then(Json::as_bool)
+        );
+    }
+
+    #[test]
+    fn malformed_input_reports_an_error() {
+        assert!(parse("{\"a\":}").is_err());
+        assert!(parse("[1,2").is_err());
+        assert!(parse("{} trailing").is_err());
+    }
+
+    #[test]
+    fn escapes_and_unicode_round_trip() {
+        let parsed = parse(r#""tab\tnl\n\u0041 é""#).unwrap();
+
+        assert_eq!(Some("tab\tnl\nA é"), parsed.as_str());
+    }
+}
@@ -1,6 +1,10 @@
 use async_std::path::PathBuf;
 
-#[derive(Debug)]
+/// The stable name used when reporting results read from stdin,
+/// which has no path of its own to display.
+pub(crate) const STDIN_DISPLAY_NAME: &str = "<stdin>";
+
+#[derive(Debug, Clone)]
 pub(crate) enum Target {
     Stdin,
     Path(PathBuf),
@@ -32,4 +36,23 @@ impl Target {
     pub(crate) fn for_path(path: PathBuf) -> Self {
         Target::Path(path)
     }
+
+    /// Builds a target from one command-line argument.
+    /// A literal `-` means "read stdin", and may appear
+    /// anywhere among ordinary file targets.
+    pub(crate) fn for_arg(arg: &str) -> Self {
+        if arg == "-" {
+            Target::Stdin
+        } else {
+            Target::Path(arg.into())
+        }
+    }
+
+    /// The name used to report results from this target.
+    pub(crate) fn display_name(&self) -> String {
+        match self {
+            Target::Stdin => STDIN_DISPLAY_NAME.to_owned(),
+            Target::Path(path) => path.to_string_lossy().to_string(),
+        }
+    }
 }
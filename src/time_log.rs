@@ -41,27 +41,32 @@ impl TimeLog {
         }
     }
 
-    pub(crate) fn log_search_duration(&mut self) {
-        assert!(self.search_duration.is_none());
+    // Each log method keeps the first value it sees; a duplicate
+    // call (possible once logging happens off multiple workers)
+    // is a no-op rather than a crash.
 
-        self.search_duration = Some(self.start_instant.elapsed());
+    pub(crate) fn log_search_duration(&mut self) {
+        let elapsed = self.start_instant.elapsed();
+        self.search_duration.get_or_insert(elapsed);
     }
 
     pub(crate) fn log_print_duration(&mut self) {
-        assert!(self.print_duration.is_none());
-
-        self.print_duration = Some(self.start_instant.elapsed());
+        let elapsed = self.start_instant.elapsed();
+        self.print_duration.get_or_insert(elapsed);
     }
 
     pub(crate) fn log_printer_spawn_to_print(&mut self) {
-        assert!(self.printer_spawn_to_print.is_none());
+        let elapsed = self.start_instant.elapsed();
+        self.printer_spawn_to_print.get_or_insert(elapsed);
+    }
 
-        self.printer_spawn_to_print = Some(self.start_instant.elapsed());
+    pub(crate) fn log_first_result_to_first_print(&mut self, first_result_instant: Instant) {
+        self.first_result_to_first_print
+            .get_or_insert(first_result_instant.elapsed());
     }
 
     pub(crate) fn log_start_die_duration(&mut self) {
-        assert!(self.start_die_duration.is_none());
-
-        self.start_die_duration = Some(self.start_instant.elapsed());
+        let elapsed = self.start_instant.elapsed();
+        self.start_die_duration.get_or_insert(elapsed);
     }
 }
@@ -0,0 +1,135 @@
+//! Structured phase timing: workers open a span for the phase
+//! they are in (crawling, reading, matching, printing), and the
+//! span's wall time folds into a shared per-phase total when it
+//! drops. Unlike `TimeLog`, which times the run as a whole, the
+//! totals here aggregate correctly across any number of
+//! concurrent workers, and are reported under `--stats`.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The phases a worker can attribute time to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    /// Walking the filesystem and filtering entries.
+    Crawl,
+
+    /// Waiting on reads from a target.
+    Read,
+
+    /// Running the matcher over content.
+    Match,
+
+    /// Formatting and writing results.
+    Print,
+}
+
+const PHASE_COUNT: usize = 4;
+
+const PHASE_NAMES: [&str; PHASE_COUNT] = ["crawl", "read", "match", "print"];
+
+#[derive(Debug, Default)]
+struct PhaseTotals {
+    nanos: AtomicU64,
+    spans: AtomicUsize,
+}
+
+/// The shared sink phase spans report into; cloning yields a
+/// handle onto the same totals, so one collector can be threaded
+/// through every worker and the printer alike.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimingCollector {
+    totals: Arc<[PhaseTotals; PHASE_COUNT]>,
+}
+
+impl TimingCollector {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a span for the given phase; its wall time is
+    /// recorded when the returned guard drops.
+    pub(crate) fn span(&self, phase: Phase) -> Span {
+        Span {
+            phase,
+            start: Instant::now(),
+            collector: self.clone(),
+        }
+    }
+
+    /// Folds an already-measured duration into a phase's total,
+    /// for call sites that accumulate locally (e.g. per line) and
+    /// report once.
+    pub(crate) fn record(&self, phase: Phase, duration: Duration) {
+        let totals = &self.totals[phase as usize];
+
+        totals
+            .nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        totals.spans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the aggregated per-phase breakdown for `--stats`.
+    /// Phase totals can exceed wall time, since workers run
+    /// concurrently.
+    pub(crate) fn format_breakdown(&self) -> String {
+        let mut out = "phase breakdown (summed across workers):".to_owned();
+
+        for (index, name) in PHASE_NAMES.iter().enumerate() {
+            let totals = &self.totals[index];
+            let duration = Duration::from_nanos(totals.nanos.load(Ordering::Relaxed));
+            let spans = totals.spans.load(Ordering::Relaxed);
+
+            out.push_str(&format!(
+                "\n  {:<6} {:>12.6} seconds across {} spans",
+                name,
+                duration.as_secs_f32(),
+                spans
+            ));
+        }
+
+        out
+    }
+}
+
+/// An open phase span; records its elapsed wall time on drop.
+#[derive(Debug)]
+pub(crate) struct Span {
+    phase: Phase,
+    start: Instant,
+    collector: TimingCollector,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.collector.record(self.phase, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spans_fold_into_their_phase_total() {
+        let collector = TimingCollector::new();
+
+        drop(collector.span(Phase::Read));
+        collector.record(Phase::Read, Duration::from_millis(5));
+
+        let breakdown = collector.format_breakdown();
+        assert!(breakdown.contains("read"));
+        assert!(breakdown.contains("across 2 spans"));
+    }
+
+    #[test]
+    fn clones_share_the_same_totals() {
+        let collector = TimingCollector::new();
+        let clone = collector.clone();
+
+        clone.record(Phase::Crawl, Duration::from_millis(1));
+
+        assert!(collector.format_breakdown().contains("across 1 spans"));
+    }
+}
@@ -0,0 +1,137 @@
+//! Built-in file type definitions for `-t/--type` filtering.
+//!
+//! Each type name maps to a set of file extensions, so
+//! `toygrep -t rust foo` searches only `*.rs` files without
+//! the user spelling out a glob.
+
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// The built-in table of type names and the extensions they cover.
+const TYPES: &[(&str, &[&str])] = &[
+    ("c", &["c", "h"]),
+    ("cpp", &["cc", "cpp", "cxx", "hh", "hpp", "hxx"]),
+    ("css", &["css", "scss"]),
+    ("go", &["go"]),
+    ("html", &["htm", "html"]),
+    ("java", &["java"]),
+    ("js", &["js", "jsx", "mjs"]),
+    ("json", &["json"]),
+    ("md", &["markdown", "md"]),
+    ("py", &["py"]),
+    ("rb", &["rb"]),
+    ("rust", &["rs"]),
+    ("sh", &["bash", "sh", "zsh"]),
+    ("toml", &["toml"]),
+    ("ts", &["ts", "tsx"]),
+    ("txt", &["txt"]),
+    ("xml", &["xml"]),
+    ("yaml", &["yaml", "yml"]),
+];
+
+/// A filter over file paths built from `--type`/`--type-not`
+/// selections. An empty filter matches every path.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TypeFilter {
+    /// Extensions a path must have, when non-empty.
+    included: Vec<&'static str>,
+
+    /// Extensions a path must not have.
+    excluded: Vec<&'static str>,
+}
+
+impl TypeFilter {
+    /// Builds a filter from the given type names.
+    /// `Err` with the list of known types on an unknown name.
+    pub(crate) fn from_names(types: &[String], type_nots: &[String]) -> Result<Self> {
+        Ok(Self {
+            included: extensions_for(types)?,
+            excluded: extensions_for(type_nots)?,
+        })
+    }
+
+    /// Whether the given path passes the filter.
+    /// Extensions are compared case-insensitively.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        if self.included.is_empty() && self.excluded.is_empty() {
+            return true;
+        }
+
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if self.excluded.iter().any(|&e| e == extension) {
+            return false;
+        }
+
+        self.included.is_empty() || self.included.iter().any(|&e| e == extension)
+    }
+}
+
+fn extensions_for(names: &[String]) -> Result<Vec<&'static str>> {
+    let mut extensions = Vec::new();
+
+    for name in names {
+        match TYPES.iter().find(|(known, _)| known == name) {
+            Some((_, found)) => extensions.extend(found.iter().copied()),
+            None => {
+                let known: Vec<&str> = TYPES.iter().map(|(name, _)| *name).collect();
+
+                return Err(Error::InvalidFlagValue {
+                    flag: "--type",
+                    value: name.clone(),
+                    expected: known.join(", "),
+                });
+            }
+        }
+    }
+
+    Ok(extensions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = TypeFilter::default();
+
+        assert!(filter.matches(Path::new("anything.xyz")));
+        assert!(filter.matches(Path::new("no_extension")));
+    }
+
+    #[test]
+    fn included_type_restricts_to_its_extensions() {
+        let filter = TypeFilter::from_names(&names(&["rust"]), &[]).unwrap();
+
+        assert!(filter.matches(Path::new("src/main.rs")));
+        assert!(!filter.matches(Path::new("notes.md")));
+    }
+
+    #[test]
+    fn excluded_type_rejects_its_extensions() {
+        let filter = TypeFilter::from_names(&[], &names(&["md"])).unwrap();
+
+        assert!(filter.matches(Path::new("src/main.rs")));
+        assert!(!filter.matches(Path::new("notes.md")));
+    }
+
+    #[test]
+    fn extension_comparison_ignores_case() {
+        let filter = TypeFilter::from_names(&names(&["md"]), &[]).unwrap();
+
+        assert!(filter.matches(Path::new("README.MD")));
+    }
+
+    #[test]
+    fn unknown_type_is_an_error() {
+        assert!(TypeFilter::from_names(&names(&["bogus"]), &[]).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! The interactive result browser behind `--tui`: the search runs
+//! to completion with its results collected instead of printed,
+//! then a full-screen terminal UI lists every match grouped under
+//! its file. Arrow keys move the selection, typing narrows the
+//! list to matches containing the typed text, and Enter opens the
+//! selected match in `$EDITOR` at the right line.
+
+use crate::error::Result;
+use crate::print::{PrintMessage, PrinterSender};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, event, execute, queue, style, terminal};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// A printer sink that files every result away for the browser
+/// instead of writing anything; clones share the same collection.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CollectingSender {
+    messages: Arc<Mutex<Vec<PrintMessage>>>,
+}
+
+impl CollectingSender {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The collected messages, once the search is done with the
+    /// other clones of this sender.
+    pub(crate) fn into_messages(self) -> Vec<PrintMessage> {
+        std::mem::take(&mut *self.messages.lock().expect("Unable to acquire lock."))
+    }
+}
+
+impl PrinterSender for CollectingSender {
+    fn send(&self, message: PrintMessage) {
+        self.messages
+            .lock()
+            .expect("Unable to acquire lock.")
+            .push(message);
+    }
+}
+
+/// One matching line, ready for display.
+struct MatchEntry {
+    path: String,
+    line_num: usize,
+    text: String,
+}
+
+/// A row of the rendered list: a file heading, or one of the
+/// matches beneath it (by index into the entry list).
+enum Row {
+    Heading(String),
+    Match(usize),
+}
+
+/// Opens the browser over the collected search results and runs
+/// it until the user quits.
+pub(crate) fn browse(messages: Vec<PrintMessage>) -> Result<()> {
+    let mut entries: Vec<MatchEntry> = messages
+        .into_iter()
+        .filter_map(|message| match message {
+            PrintMessage::Printable(printable) if !printable.is_context() => Some(MatchEntry {
+                path: printable.target_name().to_owned(),
+                line_num: printable.line_num(),
+                text: printable.text_lossy().trim_end().to_owned(),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    if entries.is_empty() {
+        println!("no matches to browse");
+        return Ok(());
+    }
+
+    // Grouping by file wants each file's matches adjacent and in
+    // line order, whatever order the concurrent search found them.
+    entries.sort_by(|a, b| a.path.cmp(&b.path).then(a.line_num.cmp(&b.line_num)));
+
+    let mut browser = Browser::new(entries);
+    let _guard = RawModeGuard::enter()?;
+
+    browser.run()
+}
+
+/// Puts the terminal into the browser's full-screen raw mode, and
+/// restores it on drop, so an error (or panic) never leaves the
+/// user's shell in a broken state.
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            std::io::stdout(),
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Browser {
+    entries: Vec<MatchEntry>,
+
+    /// Indices of the entries passing the current filter.
+    visible: Vec<usize>,
+
+    /// The typed filter; matches must contain it (in their text
+    /// or their path, case-insensitively) to stay visible.
+    filter: String,
+
+    /// The selection, as a position within `visible`.
+    selected: usize,
+
+    /// The first display row currently on screen.
+    scroll: usize,
+
+    /// A one-shot notice shown in the header, e.g. when `$EDITOR`
+    /// isn't set.
+    notice: Option<String>,
+}
+
+impl Browser {
+    fn new(entries: Vec<MatchEntry>) -> Self {
+        let visible = (0..entries.len()).collect();
+
+        Self {
+            entries,
+            visible,
+            filter: String::new(),
+            selected: 0,
+            scroll: 0,
+            notice: None,
+        }
+    }
+
+    fn run(&mut self) -> Result<()> {
+        loop {
+            self.render()?;
+
+            let event = event::read()?;
+
+            let key = match event {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                Event::Resize(..) => continue,
+                _ => continue,
+            };
+
+            if self.handle_key(key)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Applies one keypress; true means the user asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        self.notice = None;
+
+        let page = usize::max(terminal::size()?.1 as usize, 3) - 2;
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(true);
+            }
+            KeyCode::Esc => {
+                // Esc first clears the filter; pressed again (or
+                // with nothing to clear), it quits.
+                if self.filter.is_empty() {
+                    return Ok(true);
+                }
+
+                self.filter.clear();
+                self.refilter();
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.select_down(1),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+            KeyCode::PageDown => self.select_down(page),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = self.visible.len().saturating_sub(1),
+            KeyCode::Enter => self.open_selected_in_editor()?,
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) if key.modifiers.intersection(KeyModifiers::CONTROL).is_empty() => {
+                self.filter.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+
+        Ok(false)
+    }
+
+    fn select_down(&mut self, by: usize) {
+        if !self.visible.is_empty() {
+            self.selected = usize::min(self.selected + by, self.visible.len() - 1);
+        }
+    }
+
+    /// Recomputes the visible entries for the current filter,
+    /// keeping the selection in bounds.
+    fn refilter(&mut self) {
+        let filter = self.filter.to_lowercase();
+
+        self.visible = (0..self.entries.len())
+            .filter(|&i| {
+                let entry = &self.entries[i];
+
+                filter.is_empty()
+                    || entry.text.to_lowercase().contains(&filter)
+                    || entry.path.to_lowercase().contains(&filter)
+            })
+            .collect();
+
+        self.selected = usize::min(self.selected, self.visible.len().saturating_sub(1));
+        self.scroll = 0;
+    }
+
+    /// The visible entries flattened into display rows, with each
+    /// file's matches beneath one heading for it.
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut last_path: Option<&str> = None;
+
+        for (position, &entry_index) in self.visible.iter().enumerate() {
+            let entry = &self.entries[entry_index];
+
+            if last_path != Some(entry.path.as_str()) {
+                rows.push(Row::Heading(entry.path.clone()));
+                last_path = Some(entry.path.as_str());
+            }
+
+            rows.push(Row::Match(position));
+        }
+
+        rows
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let (width, height) = terminal::size()?;
+
+        // A zero-sized report (some terminal emulations) falls
+        // back to the classic 80x24 rather than a blank screen.
+        let width = if width == 0 { 80 } else { width as usize };
+        let height = if height == 0 { 24 } else { height as usize };
+        let list_height = usize::max(height, 2) - 1;
+
+        let rows = self.rows();
+
+        // Keep the selected match on screen.
+        let selected_row = rows
+            .iter()
+            .position(|row| matches!(row, Row::Match(position) if *position == self.selected))
+            .unwrap_or(0);
+
+        if selected_row < self.scroll {
+            self.scroll = selected_row;
+        } else if selected_row >= self.scroll + list_height {
+            self.scroll = selected_row + 1 - list_height;
+        }
+
+        let mut out = std::io::stdout();
+
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+
+        let files = self
+            .visible
+            .iter()
+            .map(|&i| self.entries[i].path.as_str())
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+
+        let header = match &self.notice {
+            Some(notice) => notice.clone(),
+            None => format!(
+                "{} matches in {} files    filter: {}    (type to filter, Enter opens $EDITOR, Esc quits)",
+                self.visible.len(),
+                files,
+                self.filter
+            ),
+        };
+
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            style::SetAttribute(style::Attribute::Bold),
+            style::Print(truncated(&header, width)),
+            style::SetAttribute(style::Attribute::Reset)
+        )?;
+
+        for (screen_row, row) in rows.iter().skip(self.scroll).take(list_height).enumerate() {
+            queue!(out, cursor::MoveTo(0, (screen_row + 1) as u16))?;
+
+            match row {
+                Row::Heading(path) => {
+                    queue!(
+                        out,
+                        style::SetAttribute(style::Attribute::Underlined),
+                        style::Print(truncated(path, width)),
+                        style::SetAttribute(style::Attribute::Reset)
+                    )?;
+                }
+                Row::Match(position) => {
+                    let entry = &self.entries[self.visible[*position]];
+                    let line = format!("  {}: {}", entry.line_num, entry.text);
+
+                    if *position == self.selected {
+                        queue!(
+                            out,
+                            style::SetAttribute(style::Attribute::Reverse),
+                            style::Print(truncated(&line, width)),
+                            style::SetAttribute(style::Attribute::Reset)
+                        )?;
+                    } else {
+                        queue!(out, style::Print(truncated(&line, width)))?;
+                    }
+                }
+            }
+        }
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    /// Suspends the browser and opens the selected match in
+    /// `$EDITOR` (or `$VISUAL`) at its line, the `+N file`
+    /// convention vi, nano, and emacs all understand.
+    fn open_selected_in_editor(&mut self) -> Result<()> {
+        let entry = match self.visible.get(self.selected) {
+            Some(&entry_index) => &self.entries[entry_index],
+            None => return Ok(()),
+        };
+
+        let editor = match std::env::var("EDITOR").or_else(|_| std::env::var("VISUAL")) {
+            Ok(editor) if !editor.is_empty() => editor,
+            _ => {
+                self.notice = Some("set $EDITOR to open matches".to_owned());
+                return Ok(());
+            }
+        };
+
+        execute!(
+            std::io::stdout(),
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        )?;
+        terminal::disable_raw_mode()?;
+
+        let status = std::process::Command::new(&editor)
+            .arg(format!("+{}", entry.line_num))
+            .arg(&entry.path)
+            .status();
+
+        terminal::enable_raw_mode()?;
+        execute!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+
+        if let Err(e) = status {
+            self.notice = Some(format!("couldn't run {}: {}", editor, e));
+        }
+
+        Ok(())
+    }
+}
+
+/// The line clipped to the terminal width, so a long match can't
+/// wrap and shove the rest of the list off screen.
+fn truncated(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
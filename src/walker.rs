@@ -0,0 +1,274 @@
+//! Parallel directory traversal.
+//!
+//! The walker keeps one global queue of directories still to be
+//! visited and spawns a pool of workers that check directories out
+//! of it and descend independently, pushing any subdirectories
+//! they find back onto the queue (the approach Ripgrep uses).
+//! This replaces the old single-threaded outer loop in the
+//! searcher, which left every core but one idle during the walk.
+
+use crate::cancel::CancelToken;
+use crate::ignore::IgnoreStack;
+use crate::matcher::AnyMatcher;
+use crate::types::TypeFilter;
+use crate::walker_worker::WalkerWorker;
+use async_std::fs;
+use async_std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// How many workers to use when the available parallelism
+/// cannot be determined.
+const FALLBACK_WORKER_COUNT: usize = 4;
+
+/// The traversal-affecting options, mirroring the corresponding
+/// fields of the searcher's config.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct WalkerConfig {
+    /// Honor `.gitignore`/`.ignore`/`.toygrepignore` files
+    /// encountered during the walk.
+    pub(crate) process_ignore_files: bool,
+
+    /// Restricts which files are reported by type (`-t`/`-T`).
+    pub(crate) type_filter: TypeFilter,
+
+    /// When set, only files whose path matches are reported
+    /// (`--path-regex`).
+    pub(crate) path_matcher: Option<AnyMatcher>,
+
+    /// Don't descend past this many levels below the root
+    /// (files directly in the root are at depth 1).
+    pub(crate) max_depth: Option<usize>,
+
+    /// Skip files shallower than this many levels below the root.
+    pub(crate) min_depth: usize,
+
+    /// Follow symlinked files and directories.
+    pub(crate) follow_symlinks: bool,
+
+    /// Skip `.git`/`.hg`/`.svn` directories.
+    pub(crate) skip_vcs_dirs: bool,
+
+    /// Abandons the rest of the walk when cancelled.
+    pub(crate) cancel_token: CancelToken,
+}
+
+/// One directory awaiting a visit: its path, the ignore rules
+/// inherited from its ancestors, and its depth below the root.
+pub(crate) struct DirWork {
+    pub(crate) path: PathBuf,
+    pub(crate) ignores: IgnoreStack,
+    pub(crate) depth: usize,
+}
+
+/// State shared by every worker in the pool.
+/// What the walk itself counted: traversal-side numbers the
+/// searcher cannot observe on its own.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct WalkStats {
+    pub(crate) directories_visited: usize,
+    pub(crate) files_skipped_by_ignore: usize,
+
+    /// Directories that could not be read, with the reason.
+    pub(crate) errors: Vec<(String, String)>,
+}
+
+pub(crate) struct WalkState {
+    /// Directories waiting to be visited by any worker.
+    queue: Mutex<Vec<DirWork>>,
+
+    /// How many workers are mid-directory (and so may still
+    /// push more work onto the queue).
+    active_workers: AtomicUsize,
+
+    /// When following symlinks, the (device, inode) of every
+    /// directory entered so far, so a symlink loop cannot
+    /// recurse into a directory twice.
+    visited_dirs: Mutex<HashSet<(u64, u64)>>,
+
+    /// Hands out discovery indices for reported files.
+    sequence_counter: Arc<AtomicUsize>,
+
+    /// Directories processed so far, for the stats report.
+    directories_visited: AtomicUsize,
+
+    /// Files (and directories) skipped by ignore rules so far.
+    files_skipped_by_ignore: AtomicUsize,
+
+    /// Directories the walk could not read, with the reason.
+    errors: Mutex<Vec<(String, String)>>,
+}
+
+impl WalkState {
+    fn new(sequence_counter: Arc<AtomicUsize>) -> Self {
+        Self {
+            queue: Mutex::new(Vec::new()),
+            active_workers: AtomicUsize::new(0),
+            visited_dirs: Mutex::new(HashSet::new()),
+            sequence_counter,
+            directories_visited: AtomicUsize::new(0),
+            files_skipped_by_ignore: AtomicUsize::new(0),
+            errors: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn count_directory_visited(&self) {
+        self.directories_visited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_skipped_by_ignore(&self) {
+        self.files_skipped_by_ignore.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a directory the walk had to skip, so it can be
+    /// reported instead of silently dropped.
+    pub(crate) fn record_error(&self, path: String, reason: String) {
+        self.errors
+            .lock()
+            .expect("Unable to acquire lock.")
+            .push((path, reason));
+    }
+
+    /// Checks a directory out of the queue, marking the calling
+    /// worker active before the queue lock is released, so a
+    /// momentarily empty queue never looks like a finished walk.
+    pub(crate) fn checkout(&self) -> Option<DirWork> {
+        let mut queue = self.queue.lock().expect("Unable to acquire lock.");
+
+        let work = queue.pop();
+
+        if work.is_some() {
+            self.active_workers.fetch_add(1, Ordering::SeqCst);
+        }
+
+        work
+    }
+
+    pub(crate) fn push(&self, work: DirWork) {
+        self.queue
+            .lock()
+            .expect("Unable to acquire lock.")
+            .push(work);
+    }
+
+    /// Marks the directory from the last `checkout` complete.
+    pub(crate) fn finish(&self) {
+        self.active_workers.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// The walk is complete only when no work is queued *and* no
+    /// worker is mid-directory, since an active worker may yet
+    /// queue more subdirectories.
+    pub(crate) fn is_done(&self) -> bool {
+        let queue = self.queue.lock().expect("Unable to acquire lock.");
+
+        queue.is_empty() && self.active_workers.load(Ordering::SeqCst) == 0
+    }
+
+    /// The discovery index for the next reported file.
+    pub(crate) fn next_sequence(&self) -> usize {
+        self.sequence_counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Records that a directory was entered; false if it was
+    /// entered already (i.e. a symlink loop).
+    pub(crate) fn mark_dir_visited(&self, id: (u64, u64)) -> bool {
+        self.visited_dirs
+            .lock()
+            .expect("Unable to acquire lock.")
+            .insert(id)
+    }
+}
+
+/// A parallel directory walker; see the module docs.
+pub(crate) struct Walker {
+    config: WalkerConfig,
+    worker_count: usize,
+}
+
+impl Walker {
+    pub(crate) fn new(config: WalkerConfig) -> Self {
+        let worker_count = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(FALLBACK_WORKER_COUNT);
+
+        Self {
+            config,
+            worker_count,
+        }
+    }
+
+    /// Overrides the default one-worker-per-core pool size (`-j`).
+    pub(crate) fn worker_count(mut self, count: Option<usize>) -> Self {
+        if let Some(count) = count {
+            self.worker_count = usize::max(count, 1);
+        }
+
+        self
+    }
+
+    /// Walks the tree rooted at `directory_path`, invoking
+    /// `on_file` with the path and discovery index of every file
+    /// that passes the traversal filters. Returns once the whole
+    /// tree has been visited.
+    pub(crate) async fn walk<F>(
+        &self,
+        directory_path: &Path,
+        sequence_counter: Arc<AtomicUsize>,
+        on_file: F,
+    ) -> WalkStats
+    where
+        F: Fn(PathBuf, usize) + Clone + Send + Sync + 'static,
+    {
+        let state = Arc::new(WalkState::new(sequence_counter));
+
+        if self.config.follow_symlinks {
+            if let Ok(meta) = fs::metadata(directory_path).await {
+                if let Some(id) = file_id(&meta) {
+                    state.mark_dir_visited(id);
+                }
+            }
+        }
+
+        state.push(DirWork {
+            path: directory_path.to_path_buf(),
+            ignores: IgnoreStack::empty(),
+            depth: 0,
+        });
+
+        let mut workers = Vec::with_capacity(self.worker_count);
+
+        for _ in 0..self.worker_count {
+            let worker = WalkerWorker::new(state.clone(), self.config.clone(), on_file.clone());
+
+            workers.push(async_std::task::spawn(worker.run()));
+        }
+
+        for worker in workers {
+            worker.await;
+        }
+
+        let errors = std::mem::take(&mut *state.errors.lock().expect("Unable to acquire lock."));
+
+        WalkStats {
+            directories_visited: state.directories_visited.load(Ordering::Relaxed),
+            files_skipped_by_ignore: state.files_skipped_by_ignore.load(Ordering::Relaxed),
+            errors,
+        }
+    }
+}
+
+/// The (device, inode) pair uniquely identifying a file,
+/// used for symlink-loop protection.
+#[cfg(unix)]
+pub(crate) fn file_id(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_id(_meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
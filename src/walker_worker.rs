@@ -0,0 +1,169 @@
+//! The worker half of the parallel traversal: each worker
+//! repeatedly checks a directory out of the shared queue, filters
+//! its children, reports files, and queues subdirectories for
+//! whichever worker gets to them first.
+
+use crate::matcher::Matcher;
+use crate::walker::{file_id, DirWork, WalkState, WalkerConfig};
+use async_std::fs;
+use async_std::path::PathBuf;
+use async_std::prelude::*;
+use std::sync::Arc;
+
+pub(crate) struct WalkerWorker<F> {
+    state: Arc<WalkState>,
+    config: WalkerConfig,
+    on_file: F,
+}
+
+impl<F> WalkerWorker<F>
+where
+    F: Fn(PathBuf, usize) + Send + Sync + 'static,
+{
+    pub(crate) fn new(state: Arc<WalkState>, config: WalkerConfig, on_file: F) -> Self {
+        Self {
+            state,
+            config,
+            on_file,
+        }
+    }
+
+    /// Runs until the whole walk is complete -- not merely until
+    /// the queue is momentarily empty, since another worker may be
+    /// mid-directory and about to queue more.
+    pub(crate) async fn run(self) {
+        loop {
+            // Cancellation (Ctrl-C, -q's first match, a timeout,
+            // a dead output) abandons the rest of the walk; files
+            // already handed to the searchers still complete.
+            if self.config.cancel_token.is_cancelled() {
+                break;
+            }
+
+            if let Some(work) = self.state.checkout() {
+                self.process_directory(work).await;
+                self.state.finish();
+            } else if self.state.is_done() {
+                break;
+            } else {
+                async_std::task::yield_now().await;
+            }
+        }
+    }
+
+    async fn process_directory(&self, work: DirWork) {
+        self.state.count_directory_visited();
+
+        let DirWork {
+            path,
+            ignores: parent_ignores,
+            depth,
+        } = work;
+
+        // Ignore files in this directory extend the rules
+        // inherited from its ancestors.
+        let ignores = if self.config.process_ignore_files {
+            let std_path: std::path::PathBuf = path.clone().into();
+            parent_ignores.descend(&std_path)
+        } else {
+            parent_ignores
+        };
+
+        let mut dir_children = match fs::read_dir(&path).await {
+            Ok(children) => children,
+            Err(e) => {
+                self.state
+                    .record_error(path.display().to_string(), e.to_string());
+
+                return;
+            }
+        };
+
+        // Children of this directory are one level deeper.
+        let entry_depth = depth + 1;
+
+        while let Some(Ok(dir_entry)) = dir_children.next().await {
+            // `DirEntry::metadata` does not traverse symlinks;
+            // with `-L`, a symlink is resolved to its target
+            // (and a broken link is silently skipped).
+            // An entry that vanished between listing and statting
+            // is skipped, like an unreadable one.
+            let mut meta = match dir_entry.metadata().await {
+                Ok(meta) => meta,
+                Err(e) => {
+                    self.state
+                        .record_error(dir_entry.path().display().to_string(), e.to_string());
+
+                    continue;
+                }
+            };
+
+            if meta.file_type().is_symlink() {
+                if !self.config.follow_symlinks {
+                    continue;
+                }
+
+                meta = match fs::metadata(dir_entry.path()).await {
+                    Ok(resolved) => resolved,
+                    Err(_) => continue,
+                };
+            }
+
+            let entry_path: std::path::PathBuf = dir_entry.path().into();
+            if ignores.is_ignored(&entry_path, meta.is_dir()) {
+                self.state.count_skipped_by_ignore();
+                continue;
+            }
+
+            if meta.is_file() {
+                if entry_depth < self.config.min_depth {
+                    continue;
+                }
+
+                if !self.config.type_filter.matches(&entry_path) {
+                    continue;
+                }
+
+                if let Some(path_matcher) = &self.config.path_matcher {
+                    if !path_matcher.is_match(entry_path.to_string_lossy().as_bytes()) {
+                        continue;
+                    }
+                }
+
+                let sequence = self.state.next_sequence();
+
+                (self.on_file)(dir_entry.path(), sequence);
+            } else if meta.is_dir() {
+                if self.config.skip_vcs_dirs
+                    && crate::ignore::is_vcs_dir(&dir_entry.file_name().to_string_lossy())
+                {
+                    continue;
+                }
+
+                // There is nothing searchable below a directory
+                // already at the depth limit.
+                if self
+                    .config
+                    .max_depth
+                    .map_or(false, |max| entry_depth >= max)
+                {
+                    continue;
+                }
+
+                if self.config.follow_symlinks {
+                    if let Some(id) = file_id(&meta) {
+                        if !self.state.mark_dir_visited(id) {
+                            continue;
+                        }
+                    }
+                }
+
+                self.state.push(DirWork {
+                    path: dir_entry.path(),
+                    ignores: ignores.clone(),
+                    depth: entry_depth,
+                });
+            }
+        }
+    }
+}